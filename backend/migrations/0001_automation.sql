@@ -0,0 +1,182 @@
+-- Automation strategies and everything the engine used to add at boot with ALTER TABLE.
+-- Written to converge existing databases: tables are created if missing and columns
+-- added later are added if missing. Expects the Supabase schema (frontend/supabase/schema.sql).
+
+create table if not exists strategies (
+  id uuid default gen_random_uuid() primary key,
+  user_id uuid references auth.users(id) not null,
+  amount decimal not null, -- "Price Limit" / Investment Amount per trade
+  profit_percentage numeric not null, -- Target profit %
+  total_iterations int not null, -- "Order" count
+  iterations_completed int default 0,
+  duration_minutes int not null,
+  start_time timestamptz default now(),
+  end_time timestamptz,
+  status text check (status in ('running', 'paused', 'completed', 'stopped')) default 'running',
+  current_coin_id text, -- If currently in a trade
+  current_coin_symbol text,
+  entry_price decimal, -- Price bought at
+  entry_quantity decimal, -- Quantity bought
+  created_at timestamptz default now()
+);
+
+-- Older databases created profit_percentage as an integer
+alter table strategies alter column profit_percentage type numeric;
+
+-- Legacy single-trade state (superseded by strategy_positions, still read for the backfill below)
+alter table strategies add column if not exists current_order_id uuid;
+alter table strategies add column if not exists high_water_mark numeric;
+alter table strategies add column if not exists profit_target_1_sold boolean;
+alter table strategies add column if not exists profit_target_2_sold boolean;
+alter table strategies add column if not exists profit_target_3_sold boolean;
+alter table strategies add column if not exists break_even_activated boolean;
+
+-- Strategy execution mode: 'live' settles against the simulated wallet, 'paper' only records shadow fills
+alter table strategies add column if not exists mode text not null default 'live';
+
+-- Portfolio-level risk caps (NULL exposure caps fall back to amount / amount * max_positions)
+alter table strategies add column if not exists max_positions int not null default 1;
+alter table strategies add column if not exists max_coin_exposure numeric;
+alter table strategies add column if not exists max_total_exposure numeric;
+alter table strategies add column if not exists max_correlated_positions int not null default 2;
+
+-- Position sizing: fixed, fixed_fractional, volatility_target or kelly (never above amount)
+alter table strategies add column if not exists sizing_mode text not null default 'fixed';
+alter table strategies add column if not exists risk_percent numeric not null default 1;
+alter table strategies add column if not exists target_volatility numeric not null default 0.5;
+alter table strategies add column if not exists kelly_fraction numeric not null default 0.5;
+
+-- Scaled take-profit: [{"target_pct": 2, "fraction": 0.5}, ...] mapped onto profit_target_{1,2,3}_sold
+alter table strategies add column if not exists take_profit_ladder jsonb;
+-- Constants applied from a walk-forward optimization (NULL = engine defaults)
+alter table strategies add column if not exists tuned_params jsonb;
+
+-- Buy/Sell history
+create table if not exists strategy_logs (
+  id uuid default gen_random_uuid() primary key,
+  strategy_id uuid references strategies(id) on delete cascade,
+  action text check (action in ('buy', 'sell', 'sell_partial', 'sell_force')),
+  coin_id text,
+  coin_symbol text,
+  price decimal,
+  quantity decimal,
+  amount decimal, -- Total value (price * quantity)
+  profit decimal, -- For sell orders only
+  timestamp timestamptz default now()
+);
+
+-- Ladder tranches and force exits are logged as sell_partial / sell_force
+alter table strategy_logs drop constraint if exists strategy_logs_action_check;
+alter table strategy_logs add constraint strategy_logs_action_check
+  check (action in ('buy', 'sell', 'sell_partial', 'sell_force'));
+
+alter table strategies enable row level security;
+drop policy if exists "Users can view own strategies" on strategies;
+drop policy if exists "Users can insert own strategies" on strategies;
+drop policy if exists "Users can update own strategies" on strategies;
+create policy "Users can view own strategies" on strategies for select using (auth.uid() = user_id);
+create policy "Users can insert own strategies" on strategies for insert with check (auth.uid() = user_id);
+create policy "Users can update own strategies" on strategies for update using (auth.uid() = user_id);
+
+alter table strategy_logs enable row level security;
+drop policy if exists "Users can view own logs" on strategy_logs;
+create policy "Users can view own logs" on strategy_logs for select using (
+  exists (select 1 from strategies where id = strategy_logs.strategy_id and user_id = auth.uid())
+);
+
+-- Hypothetical fills for paper strategies (never touches profiles/holdings)
+create table if not exists paper_orders (
+  id uuid primary key,
+  strategy_id uuid references strategies(id) on delete cascade not null,
+  user_id uuid not null,
+  coin_id text not null,
+  coin_symbol text not null,
+  order_type text not null,
+  quantity numeric not null,
+  price_per_unit numeric not null,
+  total_amount numeric not null,
+  fee numeric not null,
+  created_at timestamptz default now() not null
+);
+
+-- One row per trade, each with its own trailing-stop and take-profit state
+create table if not exists strategy_positions (
+  id uuid default gen_random_uuid() primary key,
+  strategy_id uuid references strategies(id) on delete cascade not null,
+  coin_id text not null,
+  quantity numeric not null,
+  entry_price numeric not null,
+  cost numeric not null,
+  high_water_mark numeric not null,
+  profit_target_1_sold boolean not null default false,
+  profit_target_2_sold boolean not null default false,
+  profit_target_3_sold boolean not null default false,
+  break_even_activated boolean not null default false,
+  status text not null default 'open',
+  exit_price numeric,
+  profit numeric,
+  opened_at timestamptz default now() not null,
+  closed_at timestamptz
+);
+create index if not exists idx_strategy_positions_open on strategy_positions (strategy_id) where status = 'open';
+alter table strategy_positions add column if not exists realized_profit numeric not null default 0;
+
+-- Audit trail: one row per entry, exit, partial exit and (throttled) skipped cycle
+create table if not exists strategy_decisions (
+  id uuid default gen_random_uuid() primary key,
+  strategy_id uuid references strategies(id) on delete cascade not null,
+  position_id uuid references strategy_positions(id) on delete set null,
+  kind text not null,
+  coin_id text,
+  details jsonb not null,
+  created_at timestamptz default now() not null
+);
+create index if not exists idx_strategy_decisions_strategy on strategy_decisions (strategy_id, created_at desc);
+
+-- OHLCV candles aggregated from the live ticker (interval: 1m, 5m, 15m, 1h; open_time in epoch ms)
+create table if not exists candles (
+  coin_id text not null,
+  interval text not null,
+  open_time bigint not null,
+  open numeric not null,
+  high numeric not null,
+  low numeric not null,
+  close numeric not null,
+  volume numeric not null,
+  primary key (coin_id, interval, open_time)
+);
+
+-- Paper fills, positions and decisions are readable by the strategy's owner like its logs;
+-- candles are written and served by the backend only
+alter table paper_orders enable row level security;
+drop policy if exists "Users can view own paper orders" on paper_orders;
+create policy "Users can view own paper orders" on paper_orders for select using (auth.uid() = user_id);
+
+alter table strategy_positions enable row level security;
+drop policy if exists "Users can view own positions" on strategy_positions;
+create policy "Users can view own positions" on strategy_positions for select using (
+  exists (select 1 from strategies where id = strategy_positions.strategy_id and user_id = auth.uid())
+);
+
+alter table strategy_decisions enable row level security;
+drop policy if exists "Users can view own decisions" on strategy_decisions;
+create policy "Users can view own decisions" on strategy_decisions for select using (
+  exists (select 1 from strategies where id = strategy_decisions.strategy_id and user_id = auth.uid())
+);
+
+alter table candles enable row level security;
+-- sqlx creates its history table in public before running this migration
+alter table _sqlx_migrations enable row level security;
+
+-- Full precision for low-value coins (NUMERIC(15, 2) truncated 0.07224 to 0.07)
+alter table orders
+  alter column price_per_unit type numeric,
+  alter column quantity type numeric,
+  alter column total_amount type numeric;
+
+-- Carry over trades that were opened before positions were tracked per row
+insert into strategy_positions (strategy_id, coin_id, quantity, entry_price, cost, high_water_mark)
+select s.id, s.current_coin_id, s.amount / s.entry_price, s.entry_price, s.amount, coalesce(s.high_water_mark, s.entry_price)
+from strategies s
+where s.current_coin_id is not null and s.current_order_id is null and s.entry_price > 0
+  and not exists (select 1 from strategy_positions p where p.strategy_id = s.id and p.status = 'open');
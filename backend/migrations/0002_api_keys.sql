@@ -0,0 +1,18 @@
+-- API keys for scripts: only the SHA-256 of the full key is stored
+create table if not exists api_keys (
+  id uuid primary key,
+  user_id uuid not null,
+  name text not null,
+  key_id text not null unique,
+  key_hash text not null,
+  scopes text[] not null,
+  ip_allowlist text[] not null default '{}',
+  require_signature boolean not null default false,
+  created_at timestamptz not null default now(),
+  last_used_at timestamptz,
+  revoked_at timestamptz
+);
+create index if not exists idx_api_keys_user on api_keys (user_id);
+
+-- Backend-only: not readable through the Supabase REST API
+alter table api_keys enable row level security;
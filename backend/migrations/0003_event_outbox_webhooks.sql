@@ -0,0 +1,51 @@
+-- Durable copy of order and strategy events; fills and stops are written in the same
+-- transaction as the change itself
+create table if not exists event_outbox (
+  id bigserial primary key,
+  event_type text not null,
+  user_id uuid,
+  payload jsonb not null,
+  created_at timestamptz not null default now()
+);
+alter table event_outbox add column if not exists dispatched_at timestamptz;
+create index if not exists idx_event_outbox_pending on event_outbox (id) where dispatched_at is null;
+
+-- User-registered webhook endpoints and one delivery row per (endpoint, outbox event)
+create table if not exists webhook_endpoints (
+  id uuid primary key,
+  user_id uuid not null,
+  url text not null,
+  secret text not null,
+  event_types text[] not null default '{}',
+  created_at timestamptz not null default now()
+);
+create index if not exists idx_webhook_endpoints_user on webhook_endpoints (user_id);
+
+create table if not exists webhook_deliveries (
+  id bigserial primary key,
+  endpoint_id uuid not null references webhook_endpoints (id) on delete cascade,
+  outbox_id bigint not null,
+  event_type text not null,
+  payload jsonb not null,
+  status text not null default 'pending',
+  attempts int not null default 0,
+  next_attempt_at timestamptz not null default now(),
+  last_error text,
+  last_status_code int,
+  created_at timestamptz not null default now(),
+  delivered_at timestamptz,
+  unique (endpoint_id, outbox_id)
+);
+create index if not exists idx_webhook_deliveries_due on webhook_deliveries (next_attempt_at) where status = 'pending';
+
+-- Backend-only: endpoint secrets and other users' fills must not be readable through the
+-- Supabase REST API. RLS with no policies denies anon/authenticated; the backend owns the tables
+alter table event_outbox enable row level security;
+alter table webhook_endpoints enable row level security;
+alter table webhook_deliveries enable row level security;
+
+create or replace view webhook_dead_letters with (security_invoker = true) as
+select d.id, e.user_id, d.endpoint_id, e.url, d.event_type, d.payload, d.attempts,
+       d.last_error, d.last_status_code, d.created_at, d.next_attempt_at as failed_at
+from webhook_deliveries d join webhook_endpoints e on e.id = d.endpoint_id
+where d.status = 'dead';
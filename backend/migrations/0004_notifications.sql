@@ -0,0 +1,39 @@
+-- Notification channels (email, telegram, webhook) and the rules that send to them
+create table if not exists notification_channels (
+  id uuid primary key,
+  user_id uuid not null,
+  kind text not null,
+  target text not null,
+  created_at timestamptz not null default now()
+);
+create index if not exists idx_notification_channels_user on notification_channels (user_id);
+
+create table if not exists notification_rules (
+  id uuid primary key,
+  user_id uuid not null,
+  trigger text not null,
+  channel_id uuid not null references notification_channels (id) on delete cascade,
+  strategy_id uuid,
+  created_at timestamptz not null default now(),
+  last_fired_at timestamptz
+);
+create index if not exists idx_notification_rules_user on notification_rules (user_id, trigger);
+
+create table if not exists notification_log (
+  id bigserial primary key,
+  user_id uuid not null,
+  rule_id uuid,
+  channel_id uuid not null,
+  trigger text not null,
+  title text not null,
+  status text not null,
+  error text,
+  created_at timestamptz not null default now()
+);
+create index if not exists idx_notification_log_user on notification_log (user_id, id desc);
+
+-- Backend-only: channel targets (emails, chat ids, URLs) are not readable through the
+-- Supabase REST API
+alter table notification_channels enable row level security;
+alter table notification_rules enable row level security;
+alter table notification_log enable row level security;
@@ -0,0 +1,19 @@
+-- Price alerts checked by the matching engine on every ticker batch
+create table if not exists price_alerts (
+  id uuid primary key,
+  user_id uuid not null,
+  coin_id text not null,
+  condition jsonb not null,
+  repeat boolean not null default false,
+  cooldown_secs int not null default 3600,
+  status text not null default 'active',
+  note text,
+  trigger_count int not null default 0,
+  last_triggered_at timestamptz,
+  created_at timestamptz not null default now()
+);
+create index if not exists idx_price_alerts_user on price_alerts (user_id);
+create index if not exists idx_price_alerts_active on price_alerts (coin_id) where status = 'active';
+
+-- Backend-only: not readable through the Supabase REST API
+alter table price_alerts enable row level security;
@@ -0,0 +1,380 @@
+use crate::services::api_keys;
+use crate::state::AppState;
+use axum::{
+    async_trait,
+    body::Body,
+    extract::{ConnectInfo, FromRequestParts, Request, State},
+    http::{header::{ACCEPT, AUTHORIZATION}, request::Parts, HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use jsonwebtoken::jwk::{AlgorithmParameters, JwkSet};
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Claims we rely on from a Supabase access token
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,           // Supabase auth user id
+    role: Option<String>,  // "authenticated" for signed-in users
+    email: Option<String>,
+    exp: i64,              // Expiry, epoch seconds (required by `Validation`)
+}
+
+const API_KEY_HEADER: &str = "x-api-key";
+const TIMESTAMP_HEADER: &str = "x-timestamp";
+const SIGNATURE_HEADER: &str = "x-signature";
+const PROXY_SECRET_HEADER: &str = "x-proxy-secret";
+const CLIENT_IP_HEADER: &str = "x-client-ip";
+/// Largest body a signed request may carry (it is buffered to check the signature)
+const MAX_SIGNED_BODY_BYTES: usize = 1024 * 1024;
+
+/// The caller, as proven by a verified bearer token or API key
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct AuthUser {
+    pub user_id: Uuid,
+    pub role: Option<String>,
+    pub email: Option<String>,
+    pub api_key_id: Option<Uuid>,     // Set when authenticated with an API key
+    pub scopes: Option<Vec<String>>, // API key scopes; None for browser sessions (full access)
+    pub expires_at: Option<i64>,     // Token expiry (epoch seconds); None for API keys
+}
+
+/// Verifies Supabase-issued JWTs, either with the project's HS256 secret or with keys
+/// from a JWKS file (asymmetric signing keys, matched by `kid`)
+pub struct JwtVerifier {
+    secret: Option<DecodingKey>,
+    keys: HashMap<String, (DecodingKey, Algorithm)>, // kid -> key
+    audience: String,
+    issuer: Option<String>,
+}
+
+impl JwtVerifier {
+    pub fn new(
+        secret: Option<&str>,
+        jwks_file: Option<&str>,
+        audience: &str,
+        issuer: Option<&str>,
+    ) -> anyhow::Result<Self> {
+        let mut keys = HashMap::new();
+        if let Some(path) = jwks_file {
+            let jwks: JwkSet = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+            for jwk in &jwks.keys {
+                let Some(kid) = jwk.common.key_id.clone() else {
+                    warn!("⚠️ Skipping JWKS key without a kid in {}", path);
+                    continue;
+                };
+                let algorithm = match &jwk.common.key_algorithm {
+                    Some(alg) => Algorithm::from_str(&alg.to_string())?,
+                    None => match &jwk.algorithm {
+                        AlgorithmParameters::EllipticCurve(_) => Algorithm::ES256,
+                        AlgorithmParameters::RSA(_) => Algorithm::RS256,
+                        AlgorithmParameters::OctetKeyPair(_) => Algorithm::EdDSA,
+                        AlgorithmParameters::OctetKey(_) => Algorithm::HS256,
+                    },
+                };
+                keys.insert(kid, (DecodingKey::from_jwk(jwk)?, algorithm));
+            }
+        }
+
+        let verifier = Self {
+            secret: secret.map(|s| DecodingKey::from_secret(s.as_bytes())),
+            keys,
+            audience: audience.to_string(),
+            issuer: issuer.map(str::to_string),
+        };
+
+        if verifier.is_configured() {
+            info!(
+                "🔐 JWT auth enabled (HS256 secret: {}, JWKS keys: {})",
+                verifier.secret.is_some(),
+                verifier.keys.len()
+            );
+        } else {
+            warn!("⚠️ No SUPABASE_JWT_SECRET or SUPABASE_JWKS_FILE set, all /api requests will be rejected");
+        }
+        Ok(verifier)
+    }
+
+    fn is_configured(&self) -> bool {
+        self.secret.is_some() || !self.keys.is_empty()
+    }
+
+    pub fn verify(&self, token: &str) -> Result<AuthUser, (StatusCode, String)> {
+        if !self.is_configured() {
+            return Err((
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Authentication is not configured".to_string(),
+            ));
+        }
+
+        let unauthorized = |reason: String| (StatusCode::UNAUTHORIZED, reason);
+        let header = decode_header(token).map_err(|e| unauthorized(format!("Invalid token: {}", e)))?;
+
+        let (key, algorithm) = match header.kid.as_deref().and_then(|kid| self.keys.get(kid)) {
+            Some((key, algorithm)) => (key, *algorithm),
+            None if header.alg == Algorithm::HS256 => match &self.secret {
+                Some(secret) => (secret, Algorithm::HS256),
+                None => return Err(unauthorized("Unknown signing key".to_string())),
+            },
+            None => return Err(unauthorized("Unknown signing key".to_string())),
+        };
+        if header.alg != algorithm {
+            return Err(unauthorized("Unexpected token algorithm".to_string()));
+        }
+
+        let mut validation = Validation::new(algorithm);
+        validation.set_audience(&[&self.audience]);
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+
+        let claims = decode::<Claims>(token, key, &validation)
+            .map_err(|e| unauthorized(format!("Invalid token: {}", e)))?
+            .claims;
+        let user_id = Uuid::parse_str(&claims.sub)
+            .map_err(|_| unauthorized("Token subject is not a user id".to_string()))?;
+
+        Ok(AuthUser {
+            user_id,
+            role: claims.role,
+            email: claims.email,
+            api_key_id: None,
+            scopes: None,
+            expires_at: Some(claims.exp),
+        })
+    }
+
+    pub fn authenticate(&self, headers: &HeaderMap) -> Result<AuthUser, (StatusCode, String)> {
+        let token = headers
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .ok_or((
+                StatusCode::UNAUTHORIZED,
+                "Missing bearer token".to_string(),
+            ))?;
+        self.verify(token)
+    }
+}
+
+/// Rejects unauthenticated requests and hands the verified user to the handlers.
+/// Browser sessions send a Supabase JWT; scripts send `X-API-Key` (optionally signed).
+pub async fn require_auth(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, (StatusCode, String)> {
+    let required = required_scope(request.method(), request.uri().path());
+    let (user, mut request) = if request.headers().contains_key(API_KEY_HEADER) {
+        authenticate_api_key(&state, request).await?
+    } else if let Some(token) = event_stream_token(&request) {
+        (state.auth.verify(&token)?, request)
+    } else {
+        (state.auth.authenticate(request.headers())?, request)
+    };
+
+    if let Some(scopes) = &user.scopes {
+        match required {
+            Some(scope) if scopes.iter().any(|s| s == scope) => {}
+            Some(scope) => {
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    format!("API key lacks the '{}' scope", scope),
+                ))
+            }
+            None => {
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    "This endpoint requires a signed-in session".to_string(),
+                ))
+            }
+        }
+    }
+
+    request.extensions_mut().insert(user);
+    Ok(next.run(request).await)
+}
+
+/// Scope an API key needs for a route; `None` means browser sessions only
+fn required_scope(method: &Method, path: &str) -> Option<&'static str> {
+    if path.starts_with("/api/keys")
+        || path.starts_with("/api/webhooks")
+        || path.starts_with("/api/notifications")
+    {
+        return None;
+    }
+    // Alerts deliver through notification channels, so changing them is session-only too
+    if *method != Method::GET && path.starts_with("/api/alerts") {
+        return None;
+    }
+    if *method == Method::POST && path.starts_with("/api/orders/") {
+        return Some("trade");
+    }
+    if *method == Method::POST && path.starts_with("/api/automation/") {
+        return Some("automation");
+    }
+    Some("read")
+}
+
+/// Checks an `X-API-Key` request. Unsigned requests send the full key; signed requests
+/// may send just the key id plus `X-Timestamp` (ms) and `X-Signature`. The body is
+/// buffered for signature checks, so the request is handed back rebuilt.
+async fn authenticate_api_key(
+    state: &AppState,
+    request: Request,
+) -> Result<(AuthUser, Request), (StatusCode, String)> {
+    let unauthorized = |reason: &str| (StatusCode::UNAUTHORIZED, reason.to_string());
+    let presented = header_value(request.headers(), API_KEY_HEADER).unwrap_or_default();
+
+    let credential = api_keys::find_active(&state.pool, api_keys::key_id_of(&presented))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+        .ok_or_else(|| unauthorized("Invalid API key"))?;
+
+    if !credential.ip_allowlist.is_empty() {
+        let allowed = client_ip(&request, state)
+            .is_some_and(|ip| api_keys::ip_allowed(&credential.ip_allowlist, ip));
+        if !allowed {
+            warn!("🚫 API key {} used from a non-allowlisted address", credential.id);
+            return Err((
+                StatusCode::FORBIDDEN,
+                "Address not allowed for this API key".to_string(),
+            ));
+        }
+    }
+
+    let signature = header_value(request.headers(), SIGNATURE_HEADER);
+    let timestamp = header_value(request.headers(), TIMESTAMP_HEADER);
+
+    let request = match signature {
+        Some(signature) => {
+            let pepper = state
+                .api_key_pepper
+                .as_deref()
+                .ok_or_else(|| unauthorized("Signed requests are not enabled on this server"))?;
+            let timestamp =
+                timestamp.ok_or_else(|| unauthorized("Signed requests need X-Timestamp"))?;
+            let sent_at = timestamp
+                .trim()
+                .parse::<i64>()
+                .map_err(|_| unauthorized("X-Timestamp must be epoch milliseconds"))?;
+            if (chrono::Utc::now().timestamp_millis() - sent_at).abs()
+                > api_keys::SIGNATURE_WINDOW_MS
+            {
+                return Err(unauthorized("Request timestamp outside the allowed window"));
+            }
+
+            let (parts, body) = request.into_parts();
+            let body = axum::body::to_bytes(body, MAX_SIGNED_BODY_BYTES)
+                .await
+                .map_err(|_| (StatusCode::PAYLOAD_TOO_LARGE, "Body too large to sign".to_string()))?;
+            let path_and_query = parts.uri.path_and_query().map_or("", |p| p.as_str());
+            if !api_keys::verify_signature(
+                &api_keys::signing_secret(pepper, &credential.key_id),
+                &timestamp,
+                parts.method.as_str(),
+                path_and_query,
+                &body,
+                &signature,
+            ) {
+                return Err(unauthorized("Invalid request signature"));
+            }
+            Request::from_parts(parts, Body::from(body))
+        }
+        None if credential.require_signature => {
+            return Err(unauthorized("This API key requires signed requests"));
+        }
+        None => {
+            if !api_keys::hash_matches(&credential.key_hash, &presented) {
+                return Err(unauthorized("Invalid API key"));
+            }
+            request
+        }
+    };
+
+    api_keys::touch(&state.pool, credential.id);
+    let user = AuthUser {
+        user_id: credential.user_id,
+        role: None,
+        email: None,
+        api_key_id: Some(credential.id),
+        scopes: Some(credential.scopes),
+        expires_at: None,
+    };
+    Ok((user, request))
+}
+
+/// `?token=` on an SSE request without a bearer header: browsers' `EventSource` can't
+/// send headers
+fn event_stream_token(request: &Request) -> Option<String> {
+    if request.headers().contains_key(AUTHORIZATION)
+        || !header_value(request.headers(), ACCEPT.as_str())
+            .is_some_and(|accept| accept.contains("text/event-stream"))
+    {
+        return None;
+    }
+    url::form_urlencoded::parse(request.uri().query()?.as_bytes())
+        .find(|(name, _)| name == "token")
+        .map(|(_, token)| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+}
+
+fn header_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_string())
+}
+
+/// Peer address, or the address the fronting proxy saw when we're deployed behind one.
+/// The Next.js proxies call from a single server, so they pass the browser's address as
+/// `X-Client-IP`, which only counts alongside the shared `X-Proxy-Secret`.
+pub fn client_ip(request: &Request, state: &AppState) -> Option<IpAddr> {
+    if let Some(secret) = state.frontend_proxy_secret.as_deref() {
+        let vouched = header_value(request.headers(), PROXY_SECRET_HEADER)
+            .is_some_and(|presented| api_keys::constant_time_eq(presented.as_bytes(), secret.as_bytes()));
+        if vouched {
+            if let Some(ip) = header_value(request.headers(), CLIENT_IP_HEADER).and_then(|ip| ip.parse().ok()) {
+                return Some(ip);
+            }
+        }
+    }
+    if state.trust_forwarded_for {
+        // The last hop is the one our proxy appended; earlier entries are client-supplied
+        let forwarded = request
+            .headers()
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.rsplit(',').next())
+            .and_then(|ip| ip.trim().parse().ok());
+        if forwarded.is_some() {
+            return forwarded;
+        }
+    }
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0.ip())
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for AuthUser {
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        // Already verified by `require_auth`
+        if let Some(user) = parts.extensions.get::<AuthUser>() {
+            return Ok(user.clone());
+        }
+        state.auth.authenticate(&parts.headers)
+    }
+}
@@ -0,0 +1,158 @@
+use crate::auth::AuthUser;
+use crate::services::alerts::{
+    self, AlertCondition, PriceAlert, DEFAULT_COOLDOWN_SECS, MAX_ALERTS_PER_USER,
+    MIN_COOLDOWN_SECS,
+};
+use crate::state::AppState;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct AlertRequest {
+    pub coin_id: String, // e.g. "btc" (USDT pair)
+    pub condition: AlertCondition,
+    pub repeat: Option<bool>,       // Default false: fire once, then stay triggered
+    pub cooldown_secs: Option<i32>, // Between repeats, default 3600
+    pub note: Option<String>,       // Included in the notification
+}
+
+/// Validated request fields: coin, cooldown and note
+struct AlertSettings {
+    coin_id: String,
+    cooldown_secs: i32,
+    note: Option<String>,
+}
+
+fn validate(payload: &AlertRequest) -> Result<AlertSettings, (StatusCode, String)> {
+    let coin_id = payload.coin_id.trim().to_lowercase();
+    let coin_id = coin_id.strip_suffix("usdt").unwrap_or(&coin_id).to_string();
+    if coin_id.is_empty() || coin_id.len() > 20 || !coin_id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err((StatusCode::BAD_REQUEST, "Invalid coin_id".to_string()));
+    }
+
+    payload
+        .condition
+        .validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid condition: {}", e)))?;
+
+    let cooldown_secs = payload.cooldown_secs.unwrap_or(DEFAULT_COOLDOWN_SECS);
+    if cooldown_secs < MIN_COOLDOWN_SECS {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("cooldown_secs must be at least {}", MIN_COOLDOWN_SECS),
+        ));
+    }
+
+    let note = payload
+        .note
+        .as_deref()
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+        .map(str::to_string);
+    if note.as_ref().is_some_and(|n| n.len() > 500) {
+        return Err((StatusCode::BAD_REQUEST, "note must be at most 500 characters".to_string()));
+    }
+
+    Ok(AlertSettings {
+        coin_id,
+        cooldown_secs,
+        note,
+    })
+}
+
+pub async fn create_alert(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(payload): Json<AlertRequest>,
+) -> Result<Json<PriceAlert>, (StatusCode, String)> {
+    let settings = validate(&payload)?;
+
+    let existing = alerts::count(&state.pool, user.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+    if existing >= MAX_ALERTS_PER_USER {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("At most {} price alerts per user", MAX_ALERTS_PER_USER),
+        ));
+    }
+
+    let alert = alerts::create(
+        &state.pool,
+        user.user_id,
+        &settings.coin_id,
+        &payload.condition,
+        payload.repeat.unwrap_or(false),
+        settings.cooldown_secs,
+        settings.note.as_deref(),
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    state.matching_engine.alerts().upsert(alert.clone()).await;
+    Ok(Json(alert))
+}
+
+pub async fn list_alerts(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> Result<Json<Vec<PriceAlert>>, (StatusCode, String)> {
+    alerts::list(&state.pool, user.user_id)
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))
+}
+
+/// Replaces the alert and re-arms it (a triggered one-shot alert becomes active again)
+pub async fn update_alert(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+    Json(payload): Json<AlertRequest>,
+) -> Result<Json<PriceAlert>, (StatusCode, String)> {
+    let alert_uuid = Uuid::parse_str(&id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid Alert ID".to_string()))?;
+    let settings = validate(&payload)?;
+
+    let alert = alerts::update(
+        &state.pool,
+        user.user_id,
+        alert_uuid,
+        &settings.coin_id,
+        &payload.condition,
+        payload.repeat.unwrap_or(false),
+        settings.cooldown_secs,
+        settings.note.as_deref(),
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "Alert not found".to_string()))?;
+
+    state.matching_engine.alerts().upsert(alert.clone()).await;
+    Ok(Json(alert))
+}
+
+pub async fn delete_alert(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let alert_uuid = Uuid::parse_str(&id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid Alert ID".to_string()))?;
+
+    let deleted = alerts::delete(&state.pool, user.user_id, alert_uuid)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    if deleted {
+        state.matching_engine.alerts().remove(alert_uuid).await;
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err((StatusCode::NOT_FOUND, "Alert not found".to_string()))
+    }
+}
@@ -1,21 +1,42 @@
+use crate::auth::AuthUser;
+use crate::services::automation::{TakeProfitLevel, MAX_TAKE_PROFIT_LEVELS};
+use crate::services::events::{append_to_outbox, DomainEvent};
+use crate::services::optimizer::{OptimizeJob, OptimizeRequest};
+use crate::services::realtime::Channel;
+use crate::services::sizing::SizingMode;
 use crate::state::AppState;
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
     Json,
 };
+use futures::stream::{self, Stream, StreamExt};
+use std::convert::Infallible;
+use tokio::sync::broadcast::error::RecvError;
 use sqlx::Row;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 use uuid::Uuid;
 
 #[derive(Debug, Deserialize)]
 pub struct CreateStrategyRequest {
-    pub user_id: String, // Ideally from Auth context, keeping explicit for now
+    pub user_id: Option<String>, // Deprecated: the owner is the token's subject; must match if sent
     pub amount: Decimal,
     pub profit_percentage: Decimal,
     pub total_iterations: i32,
     pub duration_minutes: i32,
+    pub mode: Option<String>, // "live" (default) or "paper"
+    pub max_positions: Option<i32>, // Concurrent positions, default 1
+    pub max_coin_exposure: Option<Decimal>,
+    pub max_total_exposure: Option<Decimal>,
+    pub max_correlated_positions: Option<i32>, // Per correlation group (e.g. L1 coins), default 2
+    pub sizing_mode: Option<String>, // fixed (default), fixed_fractional, volatility_target, kelly
+    pub risk_percent: Option<Decimal>, // fixed_fractional: % of equity lost if the ATR stop hits, default 1
+    pub target_volatility: Option<Decimal>, // volatility_target: ATR as % of price, default 0.5
+    pub kelly_fraction: Option<Decimal>, // kelly: multiplier on full Kelly, default 0.5
+    pub take_profit_ladder: Option<Vec<TakeProfitLevel>>, // Up to 3 ascending tranches, default 25% at 50/75/100% of profit_percentage
 }
 
 #[derive(Debug, Serialize)]
@@ -35,11 +56,78 @@ pub struct StrategyDto {
     pub duration_minutes: i32,
     pub status: String,
     pub current_coin_id: Option<String>,
+    pub mode: String,
+    pub max_positions: i32,
+    pub open_positions: i64,
+    pub sizing_mode: String,
     pub created_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct PositionDto {
+    pub id: Uuid,
+    pub coin_id: String,
+    pub quantity: Decimal,
+    pub entry_price: Decimal,
+    pub cost: Decimal,
+    pub high_water_mark: Decimal,
+    pub status: String,
+    pub exit_price: Option<Decimal>,
+    pub profit: Option<Decimal>,
+    pub opened_at: chrono::DateTime<chrono::Utc>,
+    pub closed_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct DecisionDto {
+    pub id: Uuid,
+    pub position_id: Option<Uuid>,
+    pub kind: String, // entry, skip, partial_exit or exit
+    pub coin_id: Option<String>,
+    pub details: sqlx::types::Json<serde_json::Value>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DecisionQuery {
+    pub kind: Option<String>,
+    pub position_id: Option<Uuid>, // Entry and exit decisions of one trade
+    pub before: Option<chrono::DateTime<chrono::Utc>>, // Paging: decisions older than this
+    pub limit: Option<i64>,        // Default 50, max 200
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EventsQuery {
+    pub replay: Option<i64>, // Recent strategy_logs sent before live events, default 50, max 500
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct StrategyLogDto {
+    pub id: Uuid,
+    pub action: Option<String>, // buy, sell, sell_partial, sell_force
+    pub coin_id: Option<String>,
+    pub price: Option<Decimal>,
+    pub quantity: Option<Decimal>,
+    pub amount: Option<Decimal>,
+    pub profit: Option<Decimal>,
+    pub timestamp: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct PaperOrderDto {
+    pub id: Uuid,
+    pub coin_symbol: String,
+    pub order_type: String,
+    pub quantity: Decimal,
+    pub price_per_unit: Decimal,
+    pub total_amount: Decimal,
+    pub fee: Decimal,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
 pub async fn start_strategy(
     State(state): State<AppState>,
+    user: AuthUser,
     Json(payload): Json<CreateStrategyRequest>,
 ) -> Result<Json<StrategyResponse>, (StatusCode, String)> {
     println!("DEBUG: Received start_strategy request. Payload: {:?}", payload);
@@ -53,36 +141,80 @@ pub async fn start_strategy(
         ));
     }
 
-    let user_uuid = Uuid::parse_str(&payload.user_id)
-        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid User ID".to_string()))?;
-
-    let strategy_id = Uuid::new_v4();
+    if payload.user_id.as_deref().is_some_and(|id| Uuid::parse_str(id).ok() != Some(user.user_id)) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "user_id does not match the authenticated user".to_string(),
+        ));
+    }
+    let user_uuid = user.user_id;
 
-    // 🛡️ SECURITY: Validated User Balance
-    // 🛡️ SECURITY: Validated User Balance
-    let balance_query = sqlx::query(
-        "SELECT balance_inr FROM profiles WHERE id = $1"
-    )
-    .bind(user_uuid)
-    .fetch_optional(&state.pool)
-    .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e)))?;
+    let mode = payload.mode.as_deref().unwrap_or("live");
+    if mode != "live" && mode != "paper" {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Mode must be 'live' or 'paper'".to_string(),
+        ));
+    }
 
-    let user_balance: Decimal = match balance_query {
-        Some(record) => record.try_get("balance_inr").unwrap_or(Decimal::ZERO),
-        None => return Err((StatusCode::BAD_REQUEST, "User profile not found".to_string())),
-    };
+    let max_positions = payload.max_positions.unwrap_or(1);
+    if !(1..=10).contains(&max_positions) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "max_positions must be between 1 and 10".to_string(),
+        ));
+    }
+    let max_correlated_positions = payload.max_correlated_positions.unwrap_or(2);
+    if max_correlated_positions < 1 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "max_correlated_positions must be at least 1".to_string(),
+        ));
+    }
+    if payload.max_coin_exposure.is_some_and(|cap| cap < payload.amount)
+        || payload.max_total_exposure.is_some_and(|cap| cap < payload.amount)
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Exposure caps must allow at least one position of `amount`".to_string(),
+        ));
+    }
 
-    if user_balance < payload.amount {
+    let sizing_mode = payload.sizing_mode.as_deref().unwrap_or("fixed");
+    SizingMode::from_str(sizing_mode).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let risk_percent = payload.risk_percent.unwrap_or(Decimal::ONE);
+    let target_volatility = payload.target_volatility.unwrap_or(Decimal::new(5, 1));
+    let kelly_fraction = payload.kelly_fraction.unwrap_or(Decimal::new(5, 1));
+    if risk_percent <= Decimal::ZERO
+        || risk_percent > Decimal::from(100)
+        || target_volatility <= Decimal::ZERO
+        || kelly_fraction <= Decimal::ZERO
+        || kelly_fraction > Decimal::ONE
+    {
         return Err((
             StatusCode::BAD_REQUEST,
-            format!("Insufficient Balance. Available: ${}, Required: ${}", user_balance, payload.amount),
+            "risk_percent must be in (0, 100], target_volatility > 0 and kelly_fraction in (0, 1]".to_string(),
         ));
     }
 
+    if let Some(ladder) = &payload.take_profit_ladder {
+        validate_ladder(ladder).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+
+    let strategy_id = Uuid::new_v4();
+
+    // Paper strategies never touch the wallet, so they don't need funds
+    if mode == "live" {
+        let full_exposure = payload.amount * Decimal::from(max_positions);
+        let required = payload
+            .max_total_exposure
+            .map_or(full_exposure, |cap| cap.min(full_exposure));
+        validate_balance(&state, user_uuid, required).await?;
+    }
+
     // Insert Strategy
     sqlx::query(
-        "INSERT INTO strategies (id, user_id, amount, profit_percentage, total_iterations, duration_minutes, status) VALUES ($1, $2, $3, $4, $5, $6, 'running')"
+        "INSERT INTO strategies (id, user_id, amount, profit_percentage, total_iterations, duration_minutes, status, mode, max_positions, max_coin_exposure, max_total_exposure, max_correlated_positions, sizing_mode, risk_percent, target_volatility, kelly_fraction, take_profit_ladder) VALUES ($1, $2, $3, $4, $5, $6, 'running', $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)"
     )
     .bind(strategy_id)
     .bind(user_uuid)
@@ -90,39 +222,139 @@ pub async fn start_strategy(
     .bind(payload.profit_percentage)
     .bind(payload.total_iterations)
     .bind(payload.duration_minutes)
+    .bind(mode)
+    .bind(max_positions)
+    .bind(payload.max_coin_exposure)
+    .bind(payload.max_total_exposure)
+    .bind(max_correlated_positions)
+    .bind(sizing_mode)
+    .bind(risk_percent)
+    .bind(target_volatility)
+    .bind(kelly_fraction)
+    .bind(payload.take_profit_ladder.map(sqlx::types::Json))
     .execute(&state.pool)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
 
+    state.events.publish(DomainEvent::StrategyStateChanged {
+        strategy_id,
+        user_id: user_uuid,
+        status: "running".to_string(),
+    });
+
     Ok(Json(StrategyResponse {
         id: strategy_id.to_string(),
         status: "running".to_string(),
-        message: "Automation strategy started successfully".to_string(),
+        message: format!("Automation strategy started successfully ({} mode)", mode),
     }))
 }
 
+fn validate_ladder(ladder: &[TakeProfitLevel]) -> Result<(), String> {
+    if ladder.is_empty() || ladder.len() > MAX_TAKE_PROFIT_LEVELS {
+        return Err(format!("take_profit_ladder must have 1 to {} levels", MAX_TAKE_PROFIT_LEVELS));
+    }
+    if ladder.iter().any(|l| l.target_pct <= Decimal::ZERO || l.fraction <= Decimal::ZERO) {
+        return Err("Ladder target_pct and fraction must be greater than 0".to_string());
+    }
+    if ladder.windows(2).any(|w| w[1].target_pct <= w[0].target_pct) {
+        return Err("Ladder targets must be strictly ascending".to_string());
+    }
+    if ladder.iter().map(|l| l.fraction).sum::<Decimal>() > Decimal::ONE {
+        return Err("Ladder fractions must not sum to more than 1".to_string());
+    }
+    Ok(())
+}
+
+async fn validate_balance(
+    state: &AppState,
+    user_uuid: Uuid,
+    amount: Decimal,
+) -> Result<(), (StatusCode, String)> {
+    // 🛡️ SECURITY: Validated User Balance
+    let balance_query = sqlx::query(
+        "SELECT balance_inr FROM profiles WHERE id = $1"
+    )
+    .bind(user_uuid)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e)))?;
+
+    let user_balance: Decimal = match balance_query {
+        Some(record) => record.try_get("balance_inr").unwrap_or(Decimal::ZERO),
+        None => return Err((StatusCode::BAD_REQUEST, "User profile not found".to_string())),
+    };
+
+    if user_balance < amount {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Insufficient Balance. Available: ${}, Required: ${}", user_balance, amount),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Parses a strategy id and checks the caller owns it. Other users' strategies are
+/// reported as not found so their ids can't be probed.
+async fn owned_strategy_id(
+    state: &AppState,
+    user: &AuthUser,
+    id: &str,
+) -> Result<Uuid, (StatusCode, String)> {
+    let strategy_uuid = Uuid::parse_str(id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid Strategy ID".to_string()))?;
+
+    let owned: Option<bool> =
+        sqlx::query_scalar("SELECT true FROM strategies WHERE id = $1 AND user_id = $2")
+            .bind(strategy_uuid)
+            .bind(user.user_id)
+            .fetch_optional(&state.pool)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    match owned {
+        Some(_) => Ok(strategy_uuid),
+        None => Err((StatusCode::NOT_FOUND, "Strategy not found".to_string())),
+    }
+}
+
 pub async fn stop_strategy(
     State(state): State<AppState>,
+    user: AuthUser,
     Path(id): Path<String>,
 ) -> Result<Json<StrategyResponse>, (StatusCode, String)> {
     let strategy_uuid = Uuid::parse_str(&id)
         .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid Strategy ID".to_string()))?;
 
-    // Update Status
-    let result = sqlx::query("UPDATE strategies SET status = 'stopped' WHERE id = $1")
+    let db_error = |e: String| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e));
+    let mut tx = state.pool.begin().await.map_err(|e| db_error(e.to_string()))?;
+
+    // Update Status (and record the stop for webhooks in the same transaction)
+    let result = sqlx::query("UPDATE strategies SET status = 'stopped' WHERE id = $1 AND user_id = $2")
         .bind(strategy_uuid)
-        .execute(&state.pool)
+        .bind(user.user_id)
+        .execute(&mut *tx)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+        .map_err(|e| db_error(e.to_string()))?;
 
     if result.rows_affected() == 0 {
         return Err((StatusCode::NOT_FOUND, "Strategy not found".to_string()));
     }
+    let stopped = DomainEvent::StrategyStateChanged {
+        strategy_id: strategy_uuid,
+        user_id: user.user_id,
+        status: "stopped".to_string(),
+    };
+    append_to_outbox(&mut *tx, &stopped)
+        .await
+        .map_err(|e| db_error(e.to_string()))?;
+    tx.commit().await.map_err(|e| db_error(e.to_string()))?;
+    state.events.publish_committed(stopped);
     
     println!("DEBUG: Successfully stopped strategy {}. Rows affected: {}", id, result.rows_affected());
 
     Ok(Json(StrategyResponse {
-        id: id,
+        id,
         status: "stopped".to_string(),
         message: "Strategy stopped".to_string(),
     }))
@@ -130,16 +362,16 @@ pub async fn stop_strategy(
 
 pub async fn panic_strategy(
     State(state): State<AppState>,
+    user: AuthUser,
     Path(id): Path<String>,
 ) -> Result<Json<StrategyResponse>, (StatusCode, String)> {
-    let strategy_uuid = Uuid::parse_str(&id)
-        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid Strategy ID".to_string()))?;
+    let strategy_uuid = owned_strategy_id(&state, &user, &id).await?;
 
     state.automation_engine.force_exit_strategy(strategy_uuid).await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Force exit failed: {}", e)))?;
 
     Ok(Json(StrategyResponse {
-        id: id,
+        id,
         status: "stopped".to_string(),
         message: "Strategy force stopped and positions liquidated".to_string(),
     }))
@@ -147,11 +379,8 @@ pub async fn panic_strategy(
 
 pub async fn get_strategies(
     State(state): State<AppState>,
+    user: AuthUser,
 ) -> Result<Json<Vec<StrategyDto>>, (StatusCode, String)> {
-    // Ideally filter by user_id from auth context, fetching all for now or passing user_id as query param?
-    // For simplicity, let's just fetch all running/recent strategies. 
-    // In production, we MUST filter by user. Assuming single user/demo for now based on context.
-    
     println!("🔍 [get_strategies] Starting database query...");
     
     // First, test if database connection is alive
@@ -196,8 +425,9 @@ pub async fn get_strategies(
     
     println!("🔍 [get_strategies] Executing query to fetch strategies...");
     let strategies = sqlx::query_as::<_, StrategyDto>(
-        "SELECT id, amount, profit_percentage, total_iterations, iterations_completed, duration_minutes, status, current_coin_id, created_at FROM strategies ORDER BY created_at DESC LIMIT 20"
+        "SELECT s.id, s.amount, s.profit_percentage, s.total_iterations, s.iterations_completed, s.duration_minutes, s.status, s.current_coin_id, s.mode, s.max_positions, s.sizing_mode, (SELECT COUNT(*) FROM strategy_positions p WHERE p.strategy_id = s.id AND p.status = 'open') AS open_positions, s.created_at FROM strategies s WHERE s.user_id = $1 ORDER BY s.created_at DESC LIMIT 20"
     )
+    .bind(user.user_id)
     .fetch_all(&state.pool)
     .await
     .map_err(|e| {
@@ -208,3 +438,209 @@ pub async fn get_strategies(
     println!("✅ [get_strategies] Successfully fetched {} strategies", strategies.len());
     Ok(Json(strategies))
 }
+
+pub async fn get_paper_orders(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<PaperOrderDto>>, (StatusCode, String)> {
+    let strategy_uuid = owned_strategy_id(&state, &user, &id).await?;
+
+    let orders = sqlx::query_as::<_, PaperOrderDto>(
+        "SELECT id, coin_symbol, order_type, quantity, price_per_unit, total_amount, fee, created_at FROM paper_orders WHERE strategy_id = $1 ORDER BY created_at DESC LIMIT 100"
+    )
+    .bind(strategy_uuid)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(Json(orders))
+}
+
+pub async fn submit_optimization(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(payload): Json<OptimizeRequest>,
+) -> Result<Json<StrategyResponse>, (StatusCode, String)> {
+    let job_id = state
+        .optimizer
+        .submit(user.user_id, payload)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    Ok(Json(StrategyResponse {
+        id: job_id.to_string(),
+        status: "running".to_string(),
+        message: "Optimization job submitted".to_string(),
+    }))
+}
+
+pub async fn get_optimization(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<OptimizeJob>, (StatusCode, String)> {
+    let job_uuid = Uuid::parse_str(&id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid Job ID".to_string()))?;
+
+    state
+        .optimizer
+        .get(job_uuid)
+        .await
+        .filter(|job| job.user_id == user.user_id)
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, "Optimization job not found".to_string()))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApplyOptimizationRequest {
+    pub strategy_id: String,
+}
+
+/// Copies a completed job's recommended parameters onto one of the caller's strategies;
+/// running strategies use them from their next cycle
+pub async fn apply_optimization(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+    Json(payload): Json<ApplyOptimizationRequest>,
+) -> Result<Json<StrategyResponse>, (StatusCode, String)> {
+    let job_uuid = Uuid::parse_str(&id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid Job ID".to_string()))?;
+    let job = state
+        .optimizer
+        .get(job_uuid)
+        .await
+        .filter(|job| job.user_id == user.user_id)
+        .ok_or((StatusCode::NOT_FOUND, "Optimization job not found".to_string()))?;
+    let Some(report) = job.result else {
+        return Err((StatusCode::CONFLICT, format!("Optimization job is {}", job.status)));
+    };
+
+    let strategy_uuid = owned_strategy_id(&state, &user, &payload.strategy_id).await?;
+    let params = report.recommended;
+    sqlx::query("UPDATE strategies SET profit_percentage = $2, tuned_params = $3 WHERE id = $1")
+        .bind(strategy_uuid)
+        .bind(params.profit_percentage)
+        .bind(sqlx::types::Json(&params))
+        .execute(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    tracing::info!("🧪 Applied optimization {} to strategy {}: {:?}", job_uuid, strategy_uuid, params);
+    Ok(Json(StrategyResponse {
+        id: strategy_uuid.to_string(),
+        status: "updated".to_string(),
+        message: "Optimized parameters applied".to_string(),
+    }))
+}
+
+pub async fn get_positions(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<PositionDto>>, (StatusCode, String)> {
+    let strategy_uuid = owned_strategy_id(&state, &user, &id).await?;
+
+    let positions = sqlx::query_as::<_, PositionDto>(
+        "SELECT id, coin_id, quantity, entry_price, cost, high_water_mark, status, exit_price, profit, opened_at, closed_at FROM strategy_positions WHERE strategy_id = $1 ORDER BY opened_at DESC LIMIT 100"
+    )
+    .bind(strategy_uuid)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(Json(positions))
+}
+
+pub async fn get_decisions(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+    Query(query): Query<DecisionQuery>,
+) -> Result<Json<Vec<DecisionDto>>, (StatusCode, String)> {
+    let strategy_uuid = owned_strategy_id(&state, &user, &id).await?;
+
+    let decisions = sqlx::query_as::<_, DecisionDto>(
+        "SELECT id, position_id, kind, coin_id, details, created_at FROM strategy_decisions
+         WHERE strategy_id = $1
+           AND ($2::text IS NULL OR kind = $2)
+           AND ($3::uuid IS NULL OR position_id = $3)
+           AND ($4::timestamptz IS NULL OR created_at < $4)
+         ORDER BY created_at DESC LIMIT $5"
+    )
+    .bind(strategy_uuid)
+    .bind(&query.kind)
+    .bind(query.position_id)
+    .bind(query.before)
+    .bind(query.limit.unwrap_or(50).clamp(1, 200))
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(Json(decisions))
+}
+
+/// Live activity of one strategy as Server-Sent Events: recent `strategy_logs` first
+/// (`event: log`), then `action`, `status`, `decision` and `trailing_stop` events as
+/// they happen. `id` is the event's sequence number on the user's automation channel.
+pub async fn strategy_events(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+    Query(query): Query<EventsQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, String)> {
+    let strategy_uuid = owned_strategy_id(&state, &user, &id).await?;
+
+    // Subscribe before reading the backlog so nothing falls between the two
+    let events = state.hub.subscribe();
+
+    let mut logs = sqlx::query_as::<_, StrategyLogDto>(
+        "SELECT id, action, coin_id, price, quantity, amount, profit, timestamp FROM strategy_logs
+         WHERE strategy_id = $1 ORDER BY timestamp DESC LIMIT $2"
+    )
+    .bind(strategy_uuid)
+    .bind(query.replay.unwrap_or(50).clamp(0, 500))
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+    logs.reverse();
+
+    let replay = stream::iter(logs).map(|log| {
+        Ok(Event::default()
+            .event("log")
+            .json_data(&log)
+            .unwrap_or_else(|_| Event::default().event("log")))
+    });
+
+    let user_id = user.user_id;
+    let live = stream::unfold(events, move |mut events| async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    let for_strategy = event.channel == Channel::Automation
+                        && event.visible_to(user_id)
+                        && event.data.get("strategy_id").and_then(|v| v.as_str())
+                            == Some(strategy_uuid.to_string().as_str());
+                    if !for_strategy {
+                        continue;
+                    }
+                    let sse = Event::default()
+                        .event(event.kind)
+                        .id(event.seq.to_string())
+                        .json_data(&event.data)
+                        .unwrap_or_else(|_| Event::default().event(event.kind));
+                    return Some((Ok(sse), events));
+                }
+                Err(RecvError::Lagged(missed)) => {
+                    // Reconnecting replays the log, which covers what was dropped
+                    let sse = Event::default().event("lagged").data(missed.to_string());
+                    return Some((Ok(sse), events));
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Ok(Sse::new(replay.chain(live)).keep_alive(KeepAlive::default()))
+}
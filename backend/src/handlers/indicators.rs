@@ -1,139 +1,373 @@
-use crate::models::{IndicatorRequest, IndicatorResponse};
-use axum::Json;
+use crate::models::{Candle, CandleIndicatorResponse, IndicatorRequest, IndicatorResponse};
+use crate::services::candles::Interval;
+use crate::services::indicators::{self, IndicatorKey, Series};
+use crate::state::AppState;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use futures::stream::{self, StreamExt};
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
 
-pub async fn calculate_rsi(Json(request): Json<IndicatorRequest>) -> Json<IndicatorResponse> {
-    // Simple RSI calculation (simplified version)
-    let period = request.period.unwrap_or(14);
-    let prices = &request.prices;
+/// Indicators that need highs and lows, not just closes
+const OHLC_INDICATORS: &[&str] = &["atr", "stochastic", "adx", "obv", "vwap", "ichimoku"];
+/// Indicators that also need volumes
+const VOLUME_INDICATORS: &[&str] = &["obv", "vwap"];
+const MAX_CANDLES: usize = 1000;
+const MAX_BATCH_COINS: usize = 100;
+const MAX_BATCH_SPECS: usize = 10;
 
-    if prices.len() < period as usize + 1 {
-        return Json(IndicatorResponse {
-            value: dec!(50), // Neutral RSI
-            values: None,
-        });
+#[derive(Debug, Deserialize)]
+pub struct CandleIndicatorQuery {
+    pub coin: String,
+    pub interval: Option<String>, // 1m (default), 5m, 15m or 1h
+    pub period: Option<u32>,
+    pub limit: Option<usize>, // Candles to compute over, default 200, max 1000
+}
+
+/// Same indicators as the POST endpoint, computed from our own candle store
+pub async fn get_indicator(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(query): Query<CandleIndicatorQuery>,
+) -> Result<Json<Arc<CandleIndicatorResponse>>, (StatusCode, String)> {
+    let interval = Interval::from_str(query.interval.as_deref().unwrap_or("1m"))
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let key = IndicatorKey {
+        name: name.to_lowercase(),
+        coin_id: query.coin.trim().to_lowercase(),
+        interval,
+        period: query.period.map(|p| p as usize),
+        limit: query.limit.unwrap_or(200).clamp(1, MAX_CANDLES),
+    };
+
+    if let Some(cached) = state.indicator_cache.get(&key).await {
+        return Ok(Json(cached));
     }
 
-    // Calculate average gain and loss
-    let mut gains = Decimal::ZERO;
-    let mut losses = Decimal::ZERO;
+    let candles = state
+        .matching_engine
+        .candles()
+        .history(&key.coin_id, interval, key.limit)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+    if candles.is_empty() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("No {} candles for {}", interval.as_str(), key.coin_id),
+        ));
+    }
+
+    let closes: Vec<Decimal> = candles.iter().map(|c| c.close).collect();
+    let indicator = compute(&key.name, &closes, Some(&candles), key.period)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let response = Arc::new(CandleIndicatorResponse {
+        coin_id: key.coin_id.clone(),
+        interval: interval.as_str().to_string(),
+        open_times: candles.iter().map(|c| c.open_time).collect(),
+        indicator,
+    });
+    state.indicator_cache.insert(key, response.clone()).await;
 
-    for i in 1..=period as usize {
-        let change = prices[i] - prices[i - 1];
-        if change > dec!(0) {
-            gains += change;
-        } else {
-            losses += change.abs();
+    Ok(Json(response))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IndicatorSpec {
+    pub name: String,
+    pub period: Option<u32>,
+}
+
+impl IndicatorSpec {
+    /// Column name in the batch matrix, e.g. "rsi14" or "macd"
+    fn key(&self) -> String {
+        match self.period {
+            Some(period) => format!("{}{}", self.name.to_lowercase(), period),
+            None => self.name.to_lowercase(),
         }
     }
+}
 
-    let avg_gain = gains / Decimal::from(period);
-    let avg_loss = losses / Decimal::from(period);
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum FilterOperand {
+    Value(Decimal),
+    Field(String), // "price", a spec key ("rsi14") or a line of one ("bollinger20.lower")
+}
 
-    let rs = if avg_loss > dec!(0) {
-        avg_gain / avg_loss
-    } else {
-        dec!(100)
-    };
+/// Screener condition, e.g. {"left": "rsi14", "op": "<", "right": 30}
+/// or {"left": "price", "op": "<", "right": "bollinger20.lower"}
+#[derive(Debug, Deserialize)]
+pub struct ScreenFilter {
+    pub left: String,
+    pub op: String, // <, <=, >, >=
+    pub right: FilterOperand,
+}
 
-    let rsi = dec!(100) - (dec!(100) / (dec!(1) + rs));
+#[derive(Debug, Deserialize)]
+pub struct BatchIndicatorRequest {
+    pub coins: Option<Vec<String>>, // Default: the engine's top-volume universe
+    pub top: Option<usize>,         // Universe size when `coins` is omitted, default 50
+    pub interval: Option<String>,   // 1m (default), 5m, 15m or 1h
+    pub limit: Option<usize>,       // Candles per coin, default 200
+    pub indicators: Vec<IndicatorSpec>,
+    pub filters: Option<Vec<ScreenFilter>>, // All must hold for a coin to be returned
+}
 
-    Json(IndicatorResponse {
-        value: rsi,
-        values: None,
-    })
+#[derive(Debug, Serialize)]
+pub struct BatchIndicatorRow {
+    pub coin_id: String,
+    pub price: Option<Decimal>,
+    pub values: HashMap<String, Option<Decimal>>, // Latest reading per column
+    pub error: Option<String>,
 }
 
-pub async fn calculate_sma(Json(request): Json<IndicatorRequest>) -> Json<IndicatorResponse> {
-    let period = request.period.unwrap_or(20);
-    let prices = &request.prices;
+#[derive(Debug, Serialize)]
+pub struct BatchIndicatorResponse {
+    pub interval: String,
+    pub scanned: usize,
+    pub results: Vec<BatchIndicatorRow>,
+}
 
-    if prices.is_empty() {
-        return Json(IndicatorResponse {
-            value: Decimal::ZERO,
-            values: None,
-        });
+/// Latest readings of many indicators for many coins. With `filters` it acts as a screener
+/// and only returns the coins matching every condition.
+pub async fn batch_indicators(
+    State(state): State<AppState>,
+    Json(request): Json<BatchIndicatorRequest>,
+) -> Result<Json<BatchIndicatorResponse>, (StatusCode, String)> {
+    let interval = Interval::from_str(request.interval.as_deref().unwrap_or("1m"))
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let limit = request.limit.unwrap_or(200).clamp(1, MAX_CANDLES);
+    if request.indicators.is_empty() || request.indicators.len() > MAX_BATCH_SPECS {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Provide between 1 and {} indicators", MAX_BATCH_SPECS),
+        ));
+    }
+    let filters = request.filters.unwrap_or_default();
+    if let Some(bad) = filters.iter().find(|f| !matches!(f.op.as_str(), "<" | "<=" | ">" | ">=")) {
+        return Err((StatusCode::BAD_REQUEST, format!("Unsupported operator '{}'", bad.op)));
     }
 
-    let start = if prices.len() > period as usize {
-        prices.len() - period as usize
-    } else {
-        0
+    let coins: Vec<String> = match request.coins {
+        Some(coins) => coins.iter().map(|c| c.trim().to_lowercase()).collect(),
+        None => state
+            .matching_engine
+            .get_top_volume_coins(request.top.unwrap_or(50).min(MAX_BATCH_COINS))
+            .await
+            .into_iter()
+            .map(|(coin_id, _)| coin_id)
+            .collect(),
     };
+    if coins.len() > MAX_BATCH_COINS {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("At most {} coins per batch", MAX_BATCH_COINS),
+        ));
+    }
 
-    let sum: Decimal = prices[start..].iter().sum();
-    let count = prices[start..].len() as u32;
-    let sma = if count > 0 {
-        sum / Decimal::from(count)
-    } else {
-        Decimal::ZERO
-    };
+    let specs = &request.indicators;
+    let candle_store = state.matching_engine.candles();
+    let scanned = coins.len();
+    let mut rows: Vec<BatchIndicatorRow> = stream::iter(coins)
+        .map(|coin_id| async move {
+            match candle_store.history(&coin_id, interval, limit).await {
+                Ok(candles) => indicator_row(coin_id, &candles, specs),
+                Err(e) => BatchIndicatorRow {
+                    coin_id,
+                    price: None,
+                    values: HashMap::new(),
+                    error: Some(format!("Database error: {}", e)),
+                },
+            }
+        })
+        .buffer_unordered(10)
+        .collect()
+        .await;
 
-    Json(IndicatorResponse {
-        value: sma,
-        values: None,
-    })
+    if !filters.is_empty() {
+        rows.retain(|row| row.error.is_none() && filters.iter().all(|f| passes(row, f)));
+    }
+    rows.sort_by(|a, b| a.coin_id.cmp(&b.coin_id));
+
+    Ok(Json(BatchIndicatorResponse {
+        interval: interval.as_str().to_string(),
+        scanned,
+        results: rows,
+    }))
 }
 
-pub async fn calculate_ema(Json(request): Json<IndicatorRequest>) -> Json<IndicatorResponse> {
-    let period = request.period.unwrap_or(20);
-    let prices = &request.prices;
+fn indicator_row(coin_id: String, candles: &[Candle], specs: &[IndicatorSpec]) -> BatchIndicatorRow {
+    let closes: Vec<Decimal> = candles.iter().map(|c| c.close).collect();
+    let mut values = HashMap::new();
+    let mut errors = Vec::new();
 
-    if prices.is_empty() {
-        return Json(IndicatorResponse {
-            value: Decimal::ZERO,
-            values: None,
-        });
+    for spec in specs {
+        let key = spec.key();
+        let period = spec.period.map(|p| p as usize);
+        match compute(&spec.name.to_lowercase(), &closes, Some(candles), period) {
+            Ok(result) => {
+                values.insert(key.clone(), indicators::latest(result.values.as_deref().unwrap_or_default()));
+                for (line, series) in result.series.unwrap_or_default() {
+                    values.insert(format!("{}.{}", key, line), indicators::latest(&series));
+                }
+            }
+            Err(e) => errors.push(e),
+        }
     }
 
-    let multiplier = dec!(2) / (Decimal::from(period) + dec!(1));
-    let mut ema = prices[0];
-
-    for price in prices.iter().skip(1) {
-        ema = (price * multiplier) + (ema * (dec!(1) - multiplier));
+    BatchIndicatorRow {
+        coin_id,
+        price: closes.last().copied(),
+        values,
+        error: (!errors.is_empty()).then(|| errors.join("; ")),
     }
-
-    Json(IndicatorResponse {
-        value: ema,
-        values: None,
-    })
 }
 
-pub async fn calculate_macd(Json(request): Json<IndicatorRequest>) -> Json<IndicatorResponse> {
-    // MACD = EMA(12) - EMA(26)
-    // For simplicity, we'll use the last price as MACD value
-    let prices = &request.prices;
+fn passes(row: &BatchIndicatorRow, filter: &ScreenFilter) -> bool {
+    let field = |name: &str| match name {
+        "price" => row.price,
+        _ => row.values.get(name).copied().flatten(),
+    };
+    let left = field(&filter.left);
+    let right = match &filter.right {
+        FilterOperand::Value(value) => Some(*value),
+        FilterOperand::Field(name) => field(name),
+    };
+    // Indicators that haven't warmed up never match
+    let (Some(left), Some(right)) = (left, right) else {
+        return false;
+    };
 
-    if prices.len() < 26 {
-        return Json(IndicatorResponse {
-            value: Decimal::ZERO,
-            values: None,
-        });
+    match filter.op.as_str() {
+        "<" => left < right,
+        "<=" => left <= right,
+        ">" => left > right,
+        _ => left >= right,
     }
+}
 
-    // Calculate EMA(12) and EMA(26)
-    let ema12 = calculate_ema_helper(prices, 12);
-    let ema26 = calculate_ema_helper(prices, 26);
-    let macd = ema12 - ema26;
+pub async fn calculate_indicator(
+    Path(name): Path<String>,
+    Json(request): Json<IndicatorRequest>,
+) -> Result<Json<IndicatorResponse>, (StatusCode, String)> {
+    let name = name.to_lowercase();
+    let candles = if OHLC_INDICATORS.contains(&name.as_str()) {
+        Some(request_candles(&request, VOLUME_INDICATORS.contains(&name.as_str()))
+            .map_err(|e| (StatusCode::BAD_REQUEST, e))?)
+    } else {
+        None
+    };
 
-    Json(IndicatorResponse {
-        value: macd,
-        values: None,
-    })
+    let period = request.period.map(|p| p as usize);
+    compute(&name, &request.prices, candles.as_deref(), period)
+        .map(Json)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
 }
 
-fn calculate_ema_helper(prices: &[Decimal], period: u32) -> Decimal {
-    if prices.is_empty() {
-        return Decimal::ZERO;
+/// Zips the request's price arrays into candles (opens aren't sent, so open = close)
+fn request_candles(request: &IndicatorRequest, needs_volume: bool) -> Result<Vec<Candle>, String> {
+    let len = request.prices.len();
+    let (Some(highs), Some(lows)) = (&request.highs, &request.lows) else {
+        return Err("This indicator requires highs and lows".to_string());
+    };
+    if needs_volume && request.volumes.is_none() {
+        return Err("This indicator requires volumes".to_string());
+    }
+    let volumes = request.volumes.clone().unwrap_or_else(|| vec![Decimal::ZERO; len]);
+    if highs.len() != len || lows.len() != len || volumes.len() != len {
+        return Err("prices, highs, lows and volumes must have the same length".to_string());
     }
 
-    let multiplier = dec!(2) / (Decimal::from(period) + dec!(1));
-    let mut ema = prices[0];
+    Ok((0..len)
+        .map(|i| Candle {
+            open_time: i as i64,
+            open: request.prices[i],
+            high: highs[i],
+            low: lows[i],
+            close: request.prices[i],
+            volume: volumes[i],
+        })
+        .collect())
+}
 
-    for price in prices.iter().skip(1) {
-        ema = (price * multiplier) + (ema * (dec!(1) - multiplier));
-    }
+/// Computes `name` over `closes` (and `candles` for OHLC indicators). `period` overrides
+/// the main lookback; the other parameters use their conventional defaults.
+pub(crate) fn compute(
+    name: &str,
+    closes: &[Decimal],
+    candles: Option<&[Candle]>,
+    period: Option<usize>,
+) -> Result<IndicatorResponse, String> {
+    let ohlc = || candles.ok_or_else(|| format!("{} requires OHLC candles", name));
+
+    let (main, lines): (Series, Vec<(&str, Series)>) = match name {
+        "sma" => (indicators::sma(closes, period.unwrap_or(20)), vec![]),
+        "ema" => (indicators::ema(closes, period.unwrap_or(20)), vec![]),
+        "wma" => (indicators::wma(closes, period.unwrap_or(20)), vec![]),
+        "rsi" => (indicators::rsi(closes, period.unwrap_or(14)), vec![]),
+        "macd" => {
+            let macd = indicators::macd(closes, 12, 26, 9);
+            (
+                macd.line.clone(),
+                vec![("line", macd.line), ("signal", macd.signal), ("histogram", macd.histogram)],
+            )
+        }
+        "bollinger" => {
+            let bands = indicators::bollinger(closes, period.unwrap_or(20), dec!(2));
+            (
+                bands.middle.clone(),
+                vec![("upper", bands.upper), ("middle", bands.middle), ("lower", bands.lower)],
+            )
+        }
+        "atr" => (indicators::atr(ohlc()?, period.unwrap_or(14)), vec![]),
+        "stochastic" => {
+            let stoch = indicators::stochastic(ohlc()?, period.unwrap_or(14), 3);
+            (stoch.k.clone(), vec![("k", stoch.k), ("d", stoch.d)])
+        }
+        "adx" => {
+            let adx = indicators::adx(ohlc()?, period.unwrap_or(14));
+            (
+                adx.adx.clone(),
+                vec![("adx", adx.adx), ("plus_di", adx.plus_di), ("minus_di", adx.minus_di)],
+            )
+        }
+        "obv" => (indicators::obv(ohlc()?), vec![]),
+        "vwap" => (indicators::vwap(ohlc()?), vec![]),
+        "ichimoku" => {
+            let cloud = indicators::ichimoku(ohlc()?, 9, 26, 52);
+            (
+                cloud.tenkan.clone(),
+                vec![
+                    ("tenkan", cloud.tenkan),
+                    ("kijun", cloud.kijun),
+                    ("senkou_a", cloud.senkou_a),
+                    ("senkou_b", cloud.senkou_b),
+                    ("chikou", cloud.chikou),
+                ],
+            )
+        }
+        _ => return Err(format!("Unknown indicator '{}'", name)),
+    };
+
+    // RSI reads neutral until it has enough data
+    let fallback = if name == "rsi" { dec!(50) } else { Decimal::ZERO };
 
-    ema
+    Ok(IndicatorResponse {
+        value: indicators::latest(&main).unwrap_or(fallback),
+        values: Some(main),
+        series: (!lines.is_empty()).then(|| {
+            lines
+                .into_iter()
+                .map(|(line, values)| (line.to_string(), values))
+                .collect::<HashMap<_, _>>()
+        }),
+    })
 }
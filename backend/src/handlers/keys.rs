@@ -0,0 +1,130 @@
+use crate::auth::AuthUser;
+use crate::services::api_keys::{self, ApiKey, MAX_KEYS_PER_USER, SCOPES};
+use crate::state::AppState;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateKeyRequest {
+    pub name: String,
+    pub scopes: Vec<String>,               // Any of read, trade, automation
+    pub ip_allowlist: Option<Vec<String>>, // IPs or CIDRs; omitted allows any address
+    pub require_signature: Option<bool>,   // Reject unsigned requests, default false
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreatedKeyResponse {
+    #[serde(flatten)]
+    pub key: ApiKey,
+    pub secret: String, // Full key, shown only once
+    pub signing_secret: Option<String>, // HMAC secret for signed requests, shown only once (None without API_KEY_PEPPER)
+}
+
+pub async fn create_key(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(payload): Json<CreateKeyRequest>,
+) -> Result<Json<CreatedKeyResponse>, (StatusCode, String)> {
+    let name = payload.name.trim();
+    if name.is_empty() || name.len() > 100 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "name must be 1 to 100 characters".to_string(),
+        ));
+    }
+
+    let mut scopes = payload.scopes;
+    scopes.sort();
+    scopes.dedup();
+    if scopes.is_empty() || scopes.iter().any(|s| !SCOPES.contains(&s.as_str())) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("scopes must be a non-empty subset of {:?}", SCOPES),
+        ));
+    }
+
+    let ip_allowlist = payload.ip_allowlist.unwrap_or_default();
+    if let Some(entry) = ip_allowlist
+        .iter()
+        .find(|e| !api_keys::valid_allowlist_entry(e))
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Invalid IP or CIDR in ip_allowlist: {}", entry),
+        ));
+    }
+
+    let require_signature = payload.require_signature.unwrap_or(false);
+    if require_signature && state.api_key_pepper.is_none() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Signed requests are not enabled on this server (API_KEY_PEPPER is not set)".to_string(),
+        ));
+    }
+
+    let active = api_keys::count_active(&state.pool, user.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+    if active >= MAX_KEYS_PER_USER {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("At most {} active API keys per user", MAX_KEYS_PER_USER),
+        ));
+    }
+
+    let (key, secret) = api_keys::create(
+        &state.pool,
+        user.user_id,
+        name,
+        &scopes,
+        &ip_allowlist,
+        require_signature,
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    tracing::info!("🔑 User {} created API key {} ({:?})", user.user_id, key.key_id, key.scopes);
+    let signing_secret = state
+        .api_key_pepper
+        .as_deref()
+        .map(|pepper| api_keys::signing_secret(pepper, &key.key_id));
+    Ok(Json(CreatedKeyResponse {
+        key,
+        secret,
+        signing_secret,
+    }))
+}
+
+pub async fn list_keys(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> Result<Json<Vec<ApiKey>>, (StatusCode, String)> {
+    api_keys::list(&state.pool, user.user_id)
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))
+}
+
+pub async fn revoke_key(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let key_uuid = Uuid::parse_str(&id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid Key ID".to_string()))?;
+
+    let revoked = api_keys::revoke(&state.pool, user.user_id, key_uuid)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    if revoked {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err((StatusCode::NOT_FOUND, "API key not found".to_string()))
+    }
+}
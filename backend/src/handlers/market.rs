@@ -0,0 +1,156 @@
+use crate::models::Candle;
+use crate::services::automation::{AutomationEngine, CoinAnalysis, StrategyParams};
+use crate::services::candles::Interval;
+use crate::services::depth::DepthView;
+use crate::state::AppState;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+const MAX_CANDLES: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct CandleQuery {
+    pub interval: Option<String>, // 1m (default), 5m, 15m or 1h
+    pub limit: Option<usize>,     // Default 100, max 1000
+}
+
+#[derive(Debug, Serialize)]
+pub struct CandleResponse {
+    pub coin_id: String,
+    pub interval: Interval,
+    pub candles: Vec<Candle>, // Oldest first; the last one may still be open
+}
+
+pub async fn get_candles(
+    State(state): State<AppState>,
+    Path(coin_id): Path<String>,
+    Query(query): Query<CandleQuery>,
+) -> Result<Json<CandleResponse>, (StatusCode, String)> {
+    let interval = Interval::from_str(query.interval.as_deref().unwrap_or("1m"))
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let limit = query.limit.unwrap_or(100).clamp(1, MAX_CANDLES);
+    let coin_id = coin_id.trim().to_lowercase();
+
+    let candles = state
+        .matching_engine
+        .candles()
+        .history(&coin_id, interval, limit)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(Json(CandleResponse {
+        coin_id,
+        interval,
+        candles,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScreenerQuery {
+    pub sort: Option<String>,  // entry_score (default), rsi, volume_ratio, price_change_percent, coin_id
+    pub order: Option<String>, // desc (default) or asc
+    pub min_score: Option<Decimal>,
+    pub max_rsi: Option<Decimal>,
+    pub min_volume_ratio: Option<Decimal>,
+    pub eligible: Option<bool>, // Only coins passing every entry filter
+    pub limit: Option<usize>,   // Default 50
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScreenerRow {
+    #[serde(flatten)]
+    pub analysis: CoinAnalysis,
+    /// First entry filter the coin fails under default strategy parameters (null = buy candidate)
+    pub rejection: Option<&'static str>,
+}
+
+/// The automation engine's latest per-coin analysis, so users can see why coins were picked or skipped
+pub async fn get_screener(
+    State(state): State<AppState>,
+    Query(query): Query<ScreenerQuery>,
+) -> Result<Json<Vec<ScreenerRow>>, (StatusCode, String)> {
+    let params = StrategyParams::default();
+    let mut rows: Vec<ScreenerRow> = state
+        .automation_engine
+        .latest_analyses()
+        .await
+        .into_iter()
+        .filter(|a| query.min_score.is_none_or(|min| a.entry_score >= min))
+        .filter(|a| query.max_rsi.is_none_or(|max| a.rsi <= max))
+        .filter(|a| query.min_volume_ratio.is_none_or(|min| a.volume_ratio >= min))
+        .map(|analysis| ScreenerRow {
+            rejection: AutomationEngine::entry_rejection(&analysis, &params),
+            analysis,
+        })
+        .filter(|row| query.eligible != Some(true) || row.rejection.is_none())
+        .collect();
+
+    let sort = query.sort.as_deref().unwrap_or("entry_score");
+    match sort {
+        "entry_score" => rows.sort_by_key(|r| r.analysis.entry_score),
+        "rsi" => rows.sort_by_key(|r| r.analysis.rsi),
+        "volume_ratio" => rows.sort_by_key(|r| r.analysis.volume_ratio),
+        "price_change_percent" => rows.sort_by_key(|r| r.analysis.price_change_percent),
+        "coin_id" => rows.sort_by(|a, b| a.analysis.coin_id.cmp(&b.analysis.coin_id)),
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Unsupported sort field '{}'", sort),
+            ))
+        }
+    }
+    match query.order.as_deref().unwrap_or("desc") {
+        "desc" => rows.reverse(),
+        "asc" => {}
+        other => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Order must be 'asc' or 'desc', got '{}'", other),
+            ))
+        }
+    }
+
+    rows.truncate(query.limit.unwrap_or(50));
+    Ok(Json(rows))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DepthQuery {
+    pub levels: Option<usize>, // Per side, default 20, max 1000
+}
+
+/// Local L2 book. The first request for a coin starts syncing it and returns 503 until ready.
+/// Only coins in the ticker universe are tracked, so unknown symbols can't tie up book slots.
+pub async fn get_depth(
+    State(state): State<AppState>,
+    Path(coin_id): Path<String>,
+    Query(query): Query<DepthQuery>,
+) -> Result<Json<DepthView>, (StatusCode, String)> {
+    let coin_id = coin_id.trim().to_lowercase();
+    if !state.matching_engine.is_listed(&coin_id).await {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("{} is not a listed USDT pair", coin_id),
+        ));
+    }
+
+    let levels = query.levels.unwrap_or(20).clamp(1, 1000);
+    state
+        .matching_engine
+        .depth()
+        .book(&coin_id, levels)
+        .await
+        .map(Json)
+        .ok_or_else(|| {
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                format!("Order book for {} is syncing, retry shortly", coin_id),
+            )
+        })
+}
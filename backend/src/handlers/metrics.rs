@@ -0,0 +1,29 @@
+use crate::rate_limit::RateLimitMetrics;
+use crate::services::binance::BinanceMetrics;
+use crate::services::notifications::NotificationMetrics;
+use crate::services::realtime::RealtimeMetrics;
+use crate::services::webhooks::WebhookMetrics;
+use crate::state::AppState;
+use axum::{extract::State, Json};
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct MetricsResponse {
+    pub binance: BinanceMetrics, // REST usage against the exchange's weight limits
+    pub rate_limits: RateLimitMetrics, // Requests allowed / rejected by our own /api quotas
+    pub realtime: RealtimeMetrics,     // Open /ws connections and events pushed
+    pub events_published: u64,         // Domain events on the internal bus since boot
+    pub webhooks: WebhookMetrics,      // Webhook deliveries, failed attempts and dead letters
+    pub notifications: NotificationMetrics, // Alerts sent / failed across all channels
+}
+
+pub async fn get_metrics(State(state): State<AppState>) -> Json<MetricsResponse> {
+    Json(MetricsResponse {
+        binance: state.binance.metrics(),
+        rate_limits: state.rate_limiter.metrics(),
+        realtime: state.hub.metrics(),
+        events_published: state.events.published(),
+        webhooks: state.webhooks.metrics(),
+        notifications: state.notifier.metrics(),
+    })
+}
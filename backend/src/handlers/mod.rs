@@ -1,5 +1,12 @@
 pub mod calculations;
 pub mod indicators;
+pub mod keys;
+pub mod market;
+pub mod metrics;
+pub mod notifications;
 pub mod orders;
+pub mod alerts;
 pub mod automation;
 pub mod portfolio;
+pub mod webhooks;
+pub mod ws;
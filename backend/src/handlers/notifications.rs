@@ -0,0 +1,199 @@
+use crate::auth::AuthUser;
+use crate::services::notifications::{
+    self, NotificationChannel, NotificationLogEntry, NotificationRule, MAX_CHANNELS_PER_USER,
+    MAX_RULES_PER_USER, STRATEGY_TRIGGERS, TRIGGERS,
+};
+use crate::state::AppState;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateChannelRequest {
+    pub kind: String,   // email, telegram or webhook (whichever the server has configured)
+    pub target: String, // Email address, Telegram chat id / @channel, or URL
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRuleRequest {
+    pub trigger: String, // order_filled, strategy_stopped, stop_loss_hit, daily_pnl, price_alert
+    pub channel_id: Uuid,
+    pub strategy_id: Option<Uuid>, // Narrow strategy_stopped / stop_loss_hit to one strategy
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LogQuery {
+    pub limit: Option<i64>,
+}
+
+fn db_error(e: anyhow::Error) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e))
+}
+
+fn parse_id(id: &str, what: &str) -> Result<Uuid, (StatusCode, String)> {
+    Uuid::parse_str(id).map_err(|_| (StatusCode::BAD_REQUEST, format!("Invalid {} ID", what)))
+}
+
+pub async fn create_channel(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(payload): Json<CreateChannelRequest>,
+) -> Result<Json<NotificationChannel>, (StatusCode, String)> {
+    let kind = payload.kind.trim().to_lowercase();
+    let target = payload.target.trim();
+    let adapter = state.notifier.adapter(&kind).ok_or_else(|| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Unsupported channel kind '{}', available: {:?}", kind, state.notifier.kinds()),
+        )
+    })?;
+    adapter
+        .validate_target(target)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let existing = notifications::count_channels(&state.pool, user.user_id)
+        .await
+        .map_err(db_error)?;
+    if existing >= MAX_CHANNELS_PER_USER {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("At most {} notification channels per user", MAX_CHANNELS_PER_USER),
+        ));
+    }
+
+    let channel = notifications::create_channel(&state.pool, user.user_id, &kind, target)
+        .await
+        .map_err(db_error)?;
+    tracing::info!("🔔 User {} added a {} notification channel", user.user_id, channel.kind);
+    Ok(Json(channel))
+}
+
+pub async fn list_channels(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> Result<Json<Vec<NotificationChannel>>, (StatusCode, String)> {
+    notifications::list_channels(&state.pool, user.user_id)
+        .await
+        .map(Json)
+        .map_err(db_error)
+}
+
+pub async fn delete_channel(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let channel_id = parse_id(&id, "Channel")?;
+    if notifications::delete_channel(&state.pool, user.user_id, channel_id)
+        .await
+        .map_err(db_error)?
+    {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err((StatusCode::NOT_FOUND, "Channel not found".to_string()))
+    }
+}
+
+/// Sends a test message right away; the outcome shows up in the notification log
+pub async fn test_channel(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let channel_id = parse_id(&id, "Channel")?;
+    if state
+        .notifier
+        .send_test(user.user_id, channel_id)
+        .await
+        .map_err(db_error)?
+    {
+        Ok(StatusCode::ACCEPTED)
+    } else {
+        Err((StatusCode::NOT_FOUND, "Channel not found".to_string()))
+    }
+}
+
+pub async fn create_rule(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(payload): Json<CreateRuleRequest>,
+) -> Result<Json<NotificationRule>, (StatusCode, String)> {
+    let trigger = payload.trigger.trim().to_lowercase();
+    if !TRIGGERS.contains(&trigger.as_str()) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Unknown trigger '{}', expected one of {:?}", trigger, TRIGGERS),
+        ));
+    }
+    if payload.strategy_id.is_some() && !STRATEGY_TRIGGERS.contains(&trigger.as_str()) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("strategy_id only applies to {:?}", STRATEGY_TRIGGERS),
+        ));
+    }
+
+    let existing = notifications::count_rules(&state.pool, user.user_id)
+        .await
+        .map_err(db_error)?;
+    if existing >= MAX_RULES_PER_USER {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("At most {} notification rules per user", MAX_RULES_PER_USER),
+        ));
+    }
+
+    notifications::create_rule(
+        &state.pool,
+        user.user_id,
+        &trigger,
+        payload.channel_id,
+        payload.strategy_id,
+    )
+    .await
+    .map_err(db_error)?
+    .map(Json)
+    .ok_or((StatusCode::NOT_FOUND, "Channel not found".to_string()))
+}
+
+pub async fn list_rules(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> Result<Json<Vec<NotificationRule>>, (StatusCode, String)> {
+    notifications::list_rules(&state.pool, user.user_id)
+        .await
+        .map(Json)
+        .map_err(db_error)
+}
+
+pub async fn delete_rule(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let rule_id = parse_id(&id, "Rule")?;
+    if notifications::delete_rule(&state.pool, user.user_id, rule_id)
+        .await
+        .map_err(db_error)?
+    {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err((StatusCode::NOT_FOUND, "Rule not found".to_string()))
+    }
+}
+
+pub async fn get_log(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(query): Query<LogQuery>,
+) -> Result<Json<Vec<NotificationLogEntry>>, (StatusCode, String)> {
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    notifications::list_log(&state.pool, user.user_id, limit)
+        .await
+        .map(Json)
+        .map_err(db_error)
+}
@@ -1,13 +1,30 @@
+use crate::auth::AuthUser;
 use crate::models::{OrderValidationRequest, OrderValidationResponse};
 use crate::services::orders;
-use axum::{extract::State, Json};
+use axum::{extract::State, http::StatusCode, Json};
 
 use crate::state::AppState; // Import AppState
 
+/// Pins the request to the caller, rejecting a body that names someone else
+fn bind_to_user(request: &mut OrderValidationRequest, user: &AuthUser) -> Result<(), StatusCode> {
+    if !request.user_id.is_empty() && request.user_id != user.user_id.to_string() {
+        tracing::warn!(
+            "🚫 Order request for user {} rejected (token subject {})",
+            request.user_id,
+            user.user_id
+        );
+        return Err(StatusCode::FORBIDDEN);
+    }
+    request.user_id = user.user_id.to_string();
+    Ok(())
+}
+
 pub async fn validate_order(
     State(state): State<AppState>,
-    Json(request): Json<OrderValidationRequest>,
+    user: AuthUser,
+    Json(mut request): Json<OrderValidationRequest>,
 ) -> Result<Json<OrderValidationResponse>, axum::http::StatusCode> {
+    bind_to_user(&mut request, &user)?;
     match orders::validate_order(&state.pool, request).await {
         Ok(response) => Ok(Json(response)),
         Err(e) => {
@@ -19,16 +36,35 @@ pub async fn validate_order(
 
 pub async fn process_order(
     State(state): State<AppState>,
-    Json(request): Json<OrderValidationRequest>,
+    user: AuthUser,
+    Json(mut request): Json<OrderValidationRequest>,
 ) -> Result<Json<OrderValidationResponse>, axum::http::StatusCode> {
+    bind_to_user(&mut request, &user)?;
+
     // 1. Add order to Memory Engine if it has an ID and is a Limit Order
     if let Some(order_id) = &request.id {
+        // Only the owner may hand an order to the matching engine
+        let order_uuid = uuid::Uuid::parse_str(order_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+        let owner: Option<uuid::Uuid> =
+            sqlx::query_scalar("SELECT user_id FROM orders WHERE id = $1")
+                .bind(order_uuid)
+                .fetch_optional(&state.pool)
+                .await
+                .map_err(|e| {
+                    tracing::error!("Failed to look up order {}: {}", order_id, e);
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })?;
+        if owner != Some(user.user_id) {
+            return Err(StatusCode::NOT_FOUND);
+        }
+
         if let Some(price) = request.price {
             // It's a limit order
             state
                 .matching_engine
                 .add_order(
-                    order_id.clone(),
+                    order_uuid,
+                    user.user_id,
                     request.coin_id.clone(),
                     request.order_type.clone(),
                     price,
@@ -41,7 +77,7 @@ pub async fn process_order(
         tracing::warn!("⚠️ Process Order called without Order ID or Price");
     }
 
-    validate_order(State(state), Json(request)).await
+    validate_order(State(state), user, Json(request)).await
 }
 
 #[derive(serde::Serialize, sqlx::FromRow)]
@@ -57,10 +93,12 @@ pub struct OrderDto {
 
 pub async fn get_recent_orders(
     State(state): State<AppState>,
+    user: AuthUser,
 ) -> Result<Json<Vec<OrderDto>>, axum::http::StatusCode> {
     let orders = sqlx::query_as::<_, OrderDto>(
-        "SELECT id, coin_symbol, order_type, order_status, price_per_unit, quantity, created_at FROM orders ORDER BY created_at DESC LIMIT 10"
+        "SELECT id, coin_symbol, order_type, order_status, price_per_unit, quantity, created_at FROM orders WHERE user_id = $1 ORDER BY created_at DESC LIMIT 10"
     )
+    .bind(user.user_id)
     .fetch_all(&state.pool)
     .await
     .map_err(|e| {
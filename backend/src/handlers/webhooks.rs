@@ -0,0 +1,142 @@
+use crate::auth::AuthUser;
+use crate::services::webhooks::{
+    self, WebhookDelivery, WebhookEndpoint, EVENT_TYPES, MAX_ENDPOINTS_PER_USER,
+};
+use crate::state::AppState;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,                      // http(s) URL that receives POSTed events
+    pub event_types: Option<Vec<String>>, // Omitted or empty subscribes to every event type
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreatedWebhookResponse {
+    #[serde(flatten)]
+    pub endpoint: WebhookEndpoint,
+    pub secret: String, // Signing secret, shown only once
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeliveriesQuery {
+    pub status: Option<String>, // pending, delivered or dead (the dead-letter list)
+    pub limit: Option<i64>,
+}
+
+pub async fn create_webhook(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(payload): Json<CreateWebhookRequest>,
+) -> Result<Json<CreatedWebhookResponse>, (StatusCode, String)> {
+    let url = payload.url.trim();
+    state
+        .webhooks
+        .check_target(url)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("url {}", e)))?;
+
+    let mut event_types = payload.event_types.unwrap_or_default();
+    event_types.sort();
+    event_types.dedup();
+    if let Some(unknown) = event_types
+        .iter()
+        .find(|t| !EVENT_TYPES.contains(&t.as_str()))
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Unknown event type '{}', expected any of {:?}", unknown, EVENT_TYPES),
+        ));
+    }
+
+    let existing = webhooks::count_endpoints(&state.pool, user.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+    if existing >= MAX_ENDPOINTS_PER_USER {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("At most {} webhook endpoints per user", MAX_ENDPOINTS_PER_USER),
+        ));
+    }
+
+    let (endpoint, secret) =
+        webhooks::create_endpoint(&state.pool, user.user_id, url, &event_types)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    tracing::info!("📬 User {} registered webhook {} ({})", user.user_id, endpoint.id, endpoint.url);
+    Ok(Json(CreatedWebhookResponse { endpoint, secret }))
+}
+
+pub async fn list_webhooks(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> Result<Json<Vec<WebhookEndpoint>>, (StatusCode, String)> {
+    webhooks::list_endpoints(&state.pool, user.user_id)
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))
+}
+
+pub async fn delete_webhook(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let endpoint_uuid = Uuid::parse_str(&id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid Webhook ID".to_string()))?;
+
+    let deleted = webhooks::delete_endpoint(&state.pool, user.user_id, endpoint_uuid)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    if deleted {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err((StatusCode::NOT_FOUND, "Webhook not found".to_string()))
+    }
+}
+
+pub async fn list_deliveries(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(query): Query<DeliveriesQuery>,
+) -> Result<Json<Vec<WebhookDelivery>>, (StatusCode, String)> {
+    let status = query.status.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    if let Some(status) = status {
+        if !matches!(status, "pending" | "delivered" | "dead") {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "status must be pending, delivered or dead".to_string(),
+            ));
+        }
+    }
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+
+    webhooks::list_deliveries(&state.pool, user.user_id, status, limit)
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))
+}
+
+pub async fn redeliver(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let requeued = webhooks::redeliver(&state.pool, user.user_id, id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    if requeued {
+        Ok(StatusCode::ACCEPTED)
+    } else {
+        Err((StatusCode::NOT_FOUND, "Dead delivery not found".to_string()))
+    }
+}
@@ -0,0 +1,198 @@
+use crate::auth::AuthUser;
+use crate::services::realtime::{Channel, HubEvent};
+use crate::state::AppState;
+use axum::{
+    extract::{
+        ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    http::{HeaderMap, StatusCode},
+    response::Response,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
+
+/// Server pings idle connections this often so proxies don't drop them
+const PING_INTERVAL_SECS: u64 = 30;
+/// Close code sent when the session's token expires (4000-4999 are application codes)
+const CLOSE_TOKEN_EXPIRED: u16 = 4001;
+
+#[derive(Debug, Deserialize)]
+pub struct WsQuery {
+    pub token: Option<String>, // Browsers can't set headers on a WebSocket handshake
+}
+
+/// `{"op": "subscribe", "channels": ["tickers", "orders"]}`
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum ClientMessage {
+    Subscribe {
+        #[serde(default)]
+        channels: Vec<String>,
+    },
+    Unsubscribe {
+        #[serde(default)]
+        channels: Vec<String>,
+    },
+    /// A fresh access token for the same user, extending the connection past the old expiry
+    Auth {
+        token: String,
+    },
+    Ping,
+}
+
+pub async fn ws_handler(
+    State(state): State<AppState>,
+    Query(query): Query<WsQuery>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Result<Response, (StatusCode, String)> {
+    let user = match query.token.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+        Some(token) => state.auth.verify(token)?,
+        None => state.auth.authenticate(&headers)?,
+    };
+
+    Ok(ws.on_upgrade(move |socket| handle_socket(state, user, socket)))
+}
+
+async fn handle_socket(state: AppState, user: AuthUser, mut socket: WebSocket) {
+    let hub = state.hub.clone();
+    let mut events = hub.subscribe();
+    let mut subscribed: HashSet<Channel> = HashSet::new();
+    let mut expires_at = user.expires_at;
+    let mut ping = tokio::time::interval(Duration::from_secs(PING_INTERVAL_SECS));
+    ping.tick().await;
+
+    hub.connection_opened();
+    info!("🔌 WebSocket client connected (user {})", user.user_id);
+
+    loop {
+        let outgoing = tokio::select! {
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    handle_client_message(&state, &user, &text, &mut subscribed, &mut expires_at)
+                }
+                Some(Ok(Message::Close(_))) | None | Some(Err(_)) => break,
+                Some(Ok(_)) => continue,
+            },
+            event = events.recv() => match event {
+                Ok(event) if subscribed.contains(&event.channel) && event.visible_to(user.user_id) => {
+                    event_message(&event)
+                }
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => {
+                    // Sequence numbers will show which channels skipped; resync those over REST
+                    warn!("⚠️ WebSocket client {} lagged, {} events dropped", user.user_id, missed);
+                    json!({ "type": "lagged", "missed": missed }).to_string()
+                }
+                Err(RecvError::Closed) => break,
+            },
+            _ = ping.tick() => {
+                if socket.send(Message::Ping(Vec::new())).await.is_err() {
+                    break;
+                }
+                continue;
+            }
+            // Private channels must stop when the session does
+            _ = token_expiry(expires_at) => {
+                info!("🔌 WebSocket token for user {} expired, closing", user.user_id);
+                let _ = socket
+                    .send(Message::Close(Some(CloseFrame {
+                        code: CLOSE_TOKEN_EXPIRED,
+                        reason: "Token expired".into(),
+                    })))
+                    .await;
+                break;
+            }
+        };
+
+        if socket.send(Message::Text(outgoing)).await.is_err() {
+            break;
+        }
+    }
+
+    hub.connection_closed();
+    info!("🔌 WebSocket client disconnected (user {})", user.user_id);
+}
+
+/// Resolves when the token expiring at `expires_at` (epoch seconds) runs out
+async fn token_expiry(expires_at: Option<i64>) {
+    match expires_at {
+        Some(exp) => {
+            let remaining = (exp - chrono::Utc::now().timestamp()).max(0) as u64;
+            tokio::time::sleep(Duration::from_secs(remaining)).await
+        }
+        None => std::future::pending().await,
+    }
+}
+
+fn handle_client_message(
+    state: &AppState,
+    user: &AuthUser,
+    text: &str,
+    subscribed: &mut HashSet<Channel>,
+    expires_at: &mut Option<i64>,
+) -> String {
+    let message = match serde_json::from_str::<ClientMessage>(text) {
+        Ok(message) => message,
+        Err(e) => return error_message(format!("Invalid message: {}", e)),
+    };
+
+    match message {
+        ClientMessage::Subscribe { channels } => match parse_channels(&channels) {
+            Ok(channels) => {
+                subscribed.extend(channels);
+                subscriptions_message(subscribed)
+            }
+            Err(e) => error_message(e),
+        },
+        ClientMessage::Unsubscribe { channels } => match parse_channels(&channels) {
+            Ok(channels) => {
+                for channel in channels {
+                    subscribed.remove(&channel);
+                }
+                subscriptions_message(subscribed)
+            }
+            Err(e) => error_message(e),
+        },
+        ClientMessage::Auth { token } => match state.auth.verify(token.trim()) {
+            Ok(renewed) if renewed.user_id == user.user_id => {
+                *expires_at = renewed.expires_at;
+                json!({ "type": "authenticated", "expires_at": renewed.expires_at }).to_string()
+            }
+            Ok(_) => error_message("Token belongs to a different user".to_string()),
+            Err((_, reason)) => error_message(reason),
+        },
+        ClientMessage::Ping => json!({ "type": "pong" }).to_string(),
+    }
+}
+
+/// An empty list means every channel
+fn parse_channels(names: &[String]) -> Result<Vec<Channel>, String> {
+    if names.is_empty() {
+        return Ok(Channel::ALL.to_vec());
+    }
+    names.iter().map(|name| name.parse()).collect()
+}
+
+fn event_message(event: &Arc<HubEvent>) -> String {
+    serde_json::to_string(event.as_ref())
+        .unwrap_or_else(|e| error_message(format!("Failed to encode event: {}", e)))
+}
+
+fn subscriptions_message(subscribed: &HashSet<Channel>) -> String {
+    let channels: Vec<&Channel> = Channel::ALL
+        .iter()
+        .filter(|c| subscribed.contains(c))
+        .collect();
+    json!({ "type": "subscribed", "channels": channels }).to_string()
+}
+
+fn error_message(message: String) -> String {
+    json!({ "type": "error", "message": message }).to_string()
+}
@@ -2,16 +2,19 @@ use axum::{
     extract::State,
     http::Method,
     http::StatusCode,
-    routing::{get, post},
+    routing::{delete, get, post, put},
     Json, Router,
 };
 use sqlx::Row;
 use tower_http::cors::{Any, CorsLayer};
 
+mod auth;
 mod config;
 mod database;
 mod handlers;
+mod migrations;
 mod models;
+mod rate_limit;
 mod services;
 mod state;
 mod utils;
@@ -91,9 +94,92 @@ async fn main() -> anyhow::Result<()> {
     };
     let pool = db.pool().clone();
 
-    // 🚀 Start High-Performance Matching Engine
-    let matching_engine =
-        std::sync::Arc::new(services::matching_engine::MatchingEngine::new(pool.clone()));
+    // `crypto-backend migrate ...` manages the schema and exits
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("migrate") {
+        return migrations::run_cli(&pool, &args[1..]).await;
+    }
+
+    // Refuse to start against a schema this build doesn't know (edited or unknown
+    // migrations, missing or mistyped columns); pending migrations are applied here
+    migrations::check_on_boot(&pool, config.migrate_on_boot).await?;
+
+    // Every Binance REST call goes through one client so request weight is counted once
+    let binance = std::sync::Arc::new(services::binance::BinanceClient::new(
+        &config.binance_api_url,
+    ));
+
+    // Order-book depth: live Binance diff stream, or a recording when DEPTH_REPLAY_FILE is set
+    let depth_source: std::sync::Arc<dyn services::depth::DepthSource> =
+        match &config.depth_replay_file {
+            Some(path) => std::sync::Arc::new(services::depth::FileReplaySource::open(path)?),
+            None => std::sync::Arc::new(services::depth::BinanceDepthSource::new(binance.clone())),
+        };
+
+    // Prices: live Binance ticker stream, or a recording when TICKER_REPLAY_PATH is set
+    let ticker_source: std::sync::Arc<dyn services::ticker_feed::TickerSource> =
+        match &config.ticker_replay_path {
+            Some(path) => {
+                let speed = config
+                    .ticker_replay_speed
+                    .parse::<services::ticker_feed::ReplaySpeed>()
+                    .map_err(anyhow::Error::msg)?;
+                tracing::warn!("⚠️ Replaying recorded tickers: pending limit orders will match against recorded prices");
+                std::sync::Arc::new(services::ticker_feed::TickerReplaySource::open(path, speed)?)
+            }
+            None => std::sync::Arc::new(services::ticker_feed::BinanceTickerSource),
+        };
+    let ticker_recorder = match &config.ticker_record_dir {
+        Some(dir) => Some(std::sync::Arc::new(services::ticker_feed::TickerRecorder::start(dir)?)),
+        None => None,
+    };
+
+    // Components talk through domain events (fills, cancellations, ticks, strategy state);
+    // order and strategy events are also kept in event_outbox for webhook delivery
+    let events = std::sync::Arc::new(services::events::EventBus::with_outbox(pool.clone()));
+    if config.allow_private_webhook_targets {
+        tracing::warn!("⚠️ ALLOW_PRIVATE_WEBHOOK_TARGETS is on: webhooks may reach internal addresses");
+    }
+    let webhooks = std::sync::Arc::new(services::webhooks::WebhookDispatcher::new(
+        pool.clone(),
+        config.allow_private_webhook_targets,
+    )?);
+    webhooks.clone().start();
+
+    // Fill / stop / stop-loss / daily P&L notifications; email and Telegram when configured
+    let mut adapters: Vec<std::sync::Arc<dyn services::notifications::ChannelAdapter>> =
+        vec![std::sync::Arc::new(services::notifications::WebhookAdapter::new(
+            config.allow_private_webhook_targets,
+        )?)];
+    if let Some(smtp_url) = &config.smtp_url {
+        adapters.push(std::sync::Arc::new(services::notifications::EmailAdapter::new(
+            smtp_url,
+            &config.smtp_from,
+        )?));
+    }
+    if let Some(token) = &config.telegram_bot_token {
+        adapters.push(std::sync::Arc::new(services::notifications::TelegramAdapter::new(
+            &config.telegram_api_url,
+            token,
+        )));
+    }
+    let notifier = std::sync::Arc::new(services::notifications::Notifier::new(pool.clone(), adapters));
+    tracing::info!("🔔 Notification channels: {:?}", notifier.kinds());
+    notifier.clone().watch(&events);
+    notifier.clone().start_daily_summaries(config.daily_summary_hour);
+
+    // Push events for /ws clients (prices, fills, balances, strategy activity)
+    let hub = std::sync::Arc::new(services::realtime::RealtimeHub::new());
+    hub.clone().forward_from(&events);
+
+    // 🚀 Start High-Performance Matching Engine (also evaluates price alerts on each ticker batch)
+    let matching_engine = std::sync::Arc::new(services::matching_engine::MatchingEngine::new(
+        pool.clone(),
+        depth_source,
+        ticker_source,
+        ticker_recorder,
+        events.clone(),
+    ));
     let me_clone = matching_engine.clone();
     tokio::spawn(async move {
         me_clone.start().await;
@@ -105,42 +191,82 @@ async fn main() -> anyhow::Result<()> {
         std::sync::Arc::new(crate::services::automation::AutomationEngine::new(
             pool.clone(),
             (*matching_engine).clone(),
+            binance.clone(),
+            hub.clone(),
+            events.clone(),
         ));
     let ae_clone = automation_engine.clone();
     tokio::spawn(async move {
         ae_clone.start().await;
     });
 
+    let optimizer = std::sync::Arc::new(services::optimizer::Optimizer::new(binance.clone()));
+    let indicator_cache =
+        std::sync::Arc::new(services::indicators::IndicatorCache::new());
+
+    let auth = std::sync::Arc::new(auth::JwtVerifier::new(
+        config.jwt_secret.as_deref(),
+        config.jwks_file.as_deref(),
+        &config.jwt_audience,
+        config.jwt_issuer.as_deref(),
+    )?);
+    if config.api_key_pepper.is_none() {
+        tracing::warn!("⚠️ No API_KEY_PEPPER set, signed API key requests are disabled");
+    }
+    if config.frontend_proxy_secret.is_none() {
+        tracing::warn!("⚠️ No FRONTEND_PROXY_SECRET set, browser traffic via the Next.js proxies shares one per-IP rate limit");
+    }
+    let rate_limiter = std::sync::Arc::new(rate_limit::RateLimiter::new(
+        config.rate_limits.clone(),
+        config.ip_rate_limit.clone(),
+    ));
+
     let state = AppState {
         pool,
         matching_engine,
         automation_engine,
+        optimizer,
+        indicator_cache,
+        binance,
+        auth,
+        rate_limiter,
+        hub,
+        events,
+        webhooks,
+        notifier,
+        trust_forwarded_for: config.trust_forwarded_for,
+        api_key_pepper: config.api_key_pepper.as_deref().map(std::sync::Arc::from),
+        frontend_proxy_secret: config.frontend_proxy_secret.as_deref().map(std::sync::Arc::from),
     };
 
     // Build application
-    let app = Router::new()
-        .route("/", get(health_check)) // Root route also returns OK
-        .route("/health", get(health_check))
-        .route("/health/db", get(health_check_db)) // Database health check
+    // Everything under /api needs a Supabase access token or an API key
+    let api = Router::new()
         .route(
             "/api/portfolio/calculate",
             post(handlers::portfolio::calculate_portfolio),
         )
         .route(
-            "/api/indicators/rsi",
-            post(handlers::indicators::calculate_rsi),
+            "/api/indicators/batch",
+            post(handlers::indicators::batch_indicators),
+        )
+        // sma, ema, wma, rsi, macd, bollinger, atr, stochastic, adx, obv, vwap, ichimoku
+        .route(
+            "/api/indicators/:name",
+            get(handlers::indicators::get_indicator)
+                .post(handlers::indicators::calculate_indicator),
         )
         .route(
-            "/api/indicators/sma",
-            post(handlers::indicators::calculate_sma),
+            "/api/market/screener",
+            get(handlers::market::get_screener),
         )
         .route(
-            "/api/indicators/ema",
-            post(handlers::indicators::calculate_ema),
+            "/api/market/depth/:coin",
+            get(handlers::market::get_depth),
         )
         .route(
-            "/api/indicators/macd",
-            post(handlers::indicators::calculate_macd),
+            "/api/candles/:coin",
+            get(handlers::market::get_candles),
         )
         .route(
             "/api/orders/validate",
@@ -173,6 +299,109 @@ async fn main() -> anyhow::Result<()> {
             "/api/automation/strategies",
             get(handlers::automation::get_strategies),
         )
+        .route(
+            "/api/automation/optimize",
+            post(handlers::automation::submit_optimization),
+        )
+        .route(
+            "/api/automation/optimize/:id",
+            get(handlers::automation::get_optimization),
+        )
+        .route(
+            "/api/automation/optimize/:id/apply",
+            post(handlers::automation::apply_optimization),
+        )
+        .route(
+            "/api/automation/:id/positions",
+            get(handlers::automation::get_positions),
+        )
+        .route(
+            "/api/automation/:id/decisions",
+            get(handlers::automation::get_decisions),
+        )
+        .route(
+            "/api/automation/:id/paper-orders",
+            get(handlers::automation::get_paper_orders),
+        )
+        .route(
+            "/api/automation/:id/events",
+            get(handlers::automation::strategy_events),
+        )
+        // Price alerts, checked on every ticker batch by the matching engine
+        .route(
+            "/api/alerts",
+            get(handlers::alerts::list_alerts).post(handlers::alerts::create_alert),
+        )
+        .route(
+            "/api/alerts/:id",
+            put(handlers::alerts::update_alert).delete(handlers::alerts::delete_alert),
+        )
+        // API keys for scripts (managed from a browser session only)
+        .route(
+            "/api/keys",
+            get(handlers::keys::list_keys).post(handlers::keys::create_key),
+        )
+        .route("/api/keys/:id", delete(handlers::keys::revoke_key))
+        // Signed HTTP callbacks for fills and strategy stops (browser session only)
+        .route(
+            "/api/webhooks",
+            get(handlers::webhooks::list_webhooks).post(handlers::webhooks::create_webhook),
+        )
+        .route("/api/webhooks/:id", delete(handlers::webhooks::delete_webhook))
+        .route(
+            "/api/webhooks/deliveries",
+            get(handlers::webhooks::list_deliveries),
+        )
+        .route(
+            "/api/webhooks/deliveries/:id/retry",
+            post(handlers::webhooks::redeliver),
+        )
+        // Notification channels (email, Telegram, webhook) and the rules that use them
+        .route(
+            "/api/notifications/channels",
+            get(handlers::notifications::list_channels)
+                .post(handlers::notifications::create_channel),
+        )
+        .route(
+            "/api/notifications/channels/:id",
+            delete(handlers::notifications::delete_channel),
+        )
+        .route(
+            "/api/notifications/channels/:id/test",
+            post(handlers::notifications::test_channel),
+        )
+        .route(
+            "/api/notifications/rules",
+            get(handlers::notifications::list_rules).post(handlers::notifications::create_rule),
+        )
+        .route(
+            "/api/notifications/rules/:id",
+            delete(handlers::notifications::delete_rule),
+        )
+        .route("/api/notifications/log", get(handlers::notifications::get_log))
+        // Per-caller quotas; added first so it runs after auth has identified the caller
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::rate_limit,
+        ))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_auth,
+        ));
+
+    let app = Router::new()
+        .route("/", get(health_check)) // Root route also returns OK
+        .route("/health", get(health_check))
+        .route("/health/db", get(health_check_db)) // Database health check
+        .route("/api/metrics", get(handlers::metrics::get_metrics))
+        // Authenticates itself (?token=<jwt>), browsers can't send headers on the handshake
+        .route("/ws", get(handlers::ws::ws_handler))
+        .merge(api)
+        // Per-IP quota ahead of everything, including requests that fail authentication
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::rate_limit_ip,
+        ))
         .with_state(state) // Pass the entire AppState
         .layer(
             CorsLayer::new()
@@ -185,7 +414,12 @@ async fn main() -> anyhow::Result<()> {
     tracing::info!("🚀 Crypto Backend server running on http://{}", addr);
 
     let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
+    // Peer addresses are needed for API key IP allowlists
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
@@ -200,8 +434,14 @@ fn redact_database_url(database_url: &str) -> String {
     }
 }
 
-async fn health_check() -> &'static str {
-    "OK"
+/// Liveness for the platform (always 200) with the price feed state for humans and alerts
+async fn health_check(State(state): State<AppState>) -> Json<serde_json::Value> {
+    let feed = state.matching_engine.feed_health().await;
+    Json(serde_json::json!({
+        "status": if feed.healthy { "ok" } else { "degraded" },
+        "price_feed": feed,
+        "timestamp": chrono::Utc::now().to_rfc3339()
+    }))
 }
 
 async fn health_check_db(State(state): State<AppState>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
@@ -0,0 +1,326 @@
+use chrono::{DateTime, Utc};
+use sqlx::migrate::{Migration, Migrator};
+use sqlx::{PgPool, Row};
+use std::collections::HashMap;
+use tracing::info;
+
+/// Versioned schema changes in `backend/migrations`, embedded at compile time. Applied
+/// versions are recorded with their SHA-384 checksum in `_sqlx_migrations`.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Columns the code depends on, with the type `describe_type` reports for them. Checked
+/// after migrating so that hand edits (or a restore of an older dump) don't go unnoticed.
+const EXPECTED_COLUMNS: &[(&str, &str, &str)] = &[
+    ("orders", "price_per_unit", "numeric"), // Unconstrained: NUMERIC(15, 2) truncates cheap coins
+    ("orders", "quantity", "numeric"),
+    ("orders", "total_amount", "numeric"),
+    ("strategies", "profit_percentage", "numeric"),
+    ("strategies", "mode", "text"),
+    ("strategies", "max_positions", "integer"),
+    ("strategies", "max_coin_exposure", "numeric"),
+    ("strategies", "max_total_exposure", "numeric"),
+    ("strategies", "max_correlated_positions", "integer"),
+    ("strategies", "sizing_mode", "text"),
+    ("strategies", "risk_percent", "numeric"),
+    ("strategies", "target_volatility", "numeric"),
+    ("strategies", "kelly_fraction", "numeric"),
+    ("strategies", "take_profit_ladder", "jsonb"),
+    ("strategies", "tuned_params", "jsonb"),
+    ("strategy_positions", "high_water_mark", "numeric"),
+    ("strategy_positions", "realized_profit", "numeric"),
+    ("strategy_decisions", "details", "jsonb"),
+    ("paper_orders", "fee", "numeric"),
+    ("candles", "open_time", "bigint"),
+    ("api_keys", "key_hash", "text"),
+    ("api_keys", "scopes", "ARRAY"),
+    ("event_outbox", "dispatched_at", "timestamp with time zone"),
+    ("webhook_endpoints", "event_types", "ARRAY"),
+    ("webhook_deliveries", "next_attempt_at", "timestamp with time zone"),
+    ("notification_channels", "target", "text"),
+    ("notification_rules", "last_fired_at", "timestamp with time zone"),
+    ("notification_log", "status", "text"),
+    ("price_alerts", "condition", "jsonb"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    Modified, // Applied, but the file has changed since (checksum mismatch)
+    Failed,   // Recorded as unsuccessful; needs manual repair
+    Unknown,  // Applied by a build that has a migration this one doesn't
+}
+
+impl MigrationState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MigrationState::Applied => "applied",
+            MigrationState::Pending => "pending",
+            MigrationState::Modified => "MODIFIED",
+            MigrationState::Failed => "FAILED",
+            MigrationState::Unknown => "UNKNOWN",
+        }
+    }
+
+    fn is_drift(self) -> bool {
+        matches!(
+            self,
+            MigrationState::Modified | MigrationState::Failed | MigrationState::Unknown
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub state: MigrationState,
+    pub installed_on: Option<DateTime<Utc>>,
+}
+
+struct AppliedRow {
+    description: String,
+    success: bool,
+    checksum: Vec<u8>,
+    installed_on: DateTime<Utc>,
+}
+
+/// Embedded migrations merged with what the database has recorded, ordered by version.
+/// Read-only: a database that was never migrated shows everything as pending.
+pub async fn status(pool: &PgPool) -> anyhow::Result<Vec<MigrationStatus>> {
+    let has_table: bool = sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+        .fetch_one(pool)
+        .await?;
+
+    let mut applied: HashMap<i64, AppliedRow> = HashMap::new();
+    if has_table {
+        let rows = sqlx::query(
+            "SELECT version, description, success, checksum, installed_on FROM _sqlx_migrations",
+        )
+        .fetch_all(pool)
+        .await?;
+        for row in rows {
+            applied.insert(
+                row.try_get("version")?,
+                AppliedRow {
+                    description: row.try_get("description")?,
+                    success: row.try_get("success")?,
+                    checksum: row.try_get("checksum")?,
+                    installed_on: row.try_get("installed_on")?,
+                },
+            );
+        }
+    }
+
+    let mut statuses: Vec<MigrationStatus> = MIGRATOR
+        .iter()
+        .filter(|m| m.migration_type.is_up_migration())
+        .map(|migration| {
+            let recorded = applied.remove(&migration.version);
+            let state = match &recorded {
+                None => MigrationState::Pending,
+                Some(row) if !row.success => MigrationState::Failed,
+                Some(row) if row.checksum != *migration.checksum => MigrationState::Modified,
+                Some(_) => MigrationState::Applied,
+            };
+            MigrationStatus {
+                version: migration.version,
+                description: migration.description.to_string(),
+                state,
+                installed_on: recorded.map(|row| row.installed_on),
+            }
+        })
+        .collect();
+
+    statuses.extend(applied.into_iter().map(|(version, row)| MigrationStatus {
+        version,
+        description: row.description,
+        state: MigrationState::Unknown,
+        installed_on: Some(row.installed_on),
+    }));
+    statuses.sort_by_key(|s| s.version);
+    Ok(statuses)
+}
+
+/// Differences between the live schema and `EXPECTED_COLUMNS`, one line each
+pub async fn schema_drift(pool: &PgPool) -> anyhow::Result<Vec<String>> {
+    let rows = sqlx::query(
+        "SELECT table_name::text AS table_name, column_name::text AS column_name,
+                data_type::text AS data_type, numeric_precision::int AS numeric_precision,
+                numeric_scale::int AS numeric_scale
+         FROM information_schema.columns
+         WHERE table_schema = current_schema()",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut actual: HashMap<(String, String), String> = HashMap::new();
+    for row in rows {
+        let table: String = row.try_get("table_name")?;
+        let column: String = row.try_get("column_name")?;
+        let data_type: String = row.try_get("data_type")?;
+        let precision: Option<i32> = row.try_get("numeric_precision")?;
+        let scale: Option<i32> = row.try_get("numeric_scale")?;
+        actual.insert((table, column), describe_type(&data_type, precision, scale));
+    }
+
+    let mut drift = Vec::new();
+    for (table, column, expected) in EXPECTED_COLUMNS {
+        match actual.get(&(table.to_string(), column.to_string())) {
+            None => drift.push(format!("{}.{} is missing", table, column)),
+            Some(found) if found != expected => drift.push(format!(
+                "{}.{} is {} (expected {})",
+                table, column, found, expected
+            )),
+            Some(_) => {}
+        }
+    }
+    Ok(drift)
+}
+
+/// information_schema reports NUMERIC(15, 2) and NUMERIC both as "numeric"
+fn describe_type(data_type: &str, precision: Option<i32>, scale: Option<i32>) -> String {
+    match (data_type, precision) {
+        ("numeric", Some(precision)) => format!("numeric({}, {})", precision, scale.unwrap_or(0)),
+        _ => data_type.to_string(),
+    }
+}
+
+/// Applies pending migrations, each in its own transaction (under an advisory lock so
+/// concurrent instances don't race). Returns how many were applied.
+pub async fn up(pool: &PgPool) -> anyhow::Result<usize> {
+    let pending = status(pool)
+        .await?
+        .iter()
+        .filter(|s| s.state == MigrationState::Pending)
+        .count();
+    MIGRATOR.run(pool).await?;
+    Ok(pending)
+}
+
+/// Runs the pending migrations inside one transaction and rolls it back, so a migration
+/// that would fail on this database is caught without changing anything
+pub async fn dry_run(pool: &PgPool) -> anyhow::Result<Vec<(i64, Result<(), String>)>> {
+    let pending: Vec<&Migration> = {
+        let statuses = status(pool).await?;
+        MIGRATOR
+            .iter()
+            .filter(|m| {
+                statuses
+                    .iter()
+                    .any(|s| s.version == m.version && s.state == MigrationState::Pending)
+            })
+            .collect()
+    };
+
+    let mut results = Vec::with_capacity(pending.len());
+    let mut tx = pool.begin().await?;
+    for migration in pending {
+        match sqlx::raw_sql(&migration.sql).execute(&mut *tx).await {
+            Ok(_) => results.push((migration.version, Ok(()))),
+            Err(e) => {
+                // The transaction is aborted; later migrations can't be tried against it
+                results.push((migration.version, Err(e.to_string())));
+                break;
+            }
+        }
+    }
+    tx.rollback().await?;
+    Ok(results)
+}
+
+/// Startup gate: refuses to run against a database whose migration history doesn't match
+/// this build, applies pending migrations (or refuses, when `apply_pending` is off) and
+/// then checks the resulting schema
+pub async fn check_on_boot(pool: &PgPool, apply_pending: bool) -> anyhow::Result<()> {
+    let statuses = status(pool).await?;
+    let drifted: Vec<String> = statuses
+        .iter()
+        .filter(|s| s.state.is_drift())
+        .map(|s| format!("{} {} ({})", s.version, s.description, s.state.as_str()))
+        .collect();
+    if !drifted.is_empty() {
+        anyhow::bail!(
+            "Migration history does not match this build: {}. Run `crypto-backend migrate status` for details",
+            drifted.join(", ")
+        );
+    }
+
+    let pending = statuses
+        .iter()
+        .filter(|s| s.state == MigrationState::Pending)
+        .count();
+    if pending > 0 {
+        if !apply_pending {
+            anyhow::bail!(
+                "{} pending migration(s) and MIGRATE_ON_BOOT is off. Run `crypto-backend migrate up` first",
+                pending
+            );
+        }
+        info!("🗄️ Applying {} pending migration(s)...", pending);
+        up(pool).await?;
+    }
+
+    let drift = schema_drift(pool).await?;
+    if !drift.is_empty() {
+        anyhow::bail!("Schema drift detected: {}", drift.join("; "));
+    }
+    info!("🗄️ Schema is at migration {}", statuses.last().map_or(0, |s| s.version));
+    Ok(())
+}
+
+/// `crypto-backend migrate [status | up | up --dry-run | dry-run]`
+pub async fn run_cli(pool: &PgPool, args: &[String]) -> anyhow::Result<()> {
+    let command = args.first().map(String::as_str).unwrap_or("status");
+    let dry_run_flag = args.iter().skip(1).any(|a| a == "--dry-run");
+
+    match (command, dry_run_flag) {
+        ("status", _) => {
+            for s in status(pool).await? {
+                println!(
+                    "{:>6}  {:<9} {:<32} {}",
+                    s.version,
+                    s.state.as_str(),
+                    s.description,
+                    s.installed_on.map(|t| t.to_rfc3339()).unwrap_or_default()
+                );
+            }
+            let drift = schema_drift(pool).await?;
+            if drift.is_empty() {
+                println!("No schema drift");
+            } else {
+                for line in drift {
+                    println!("drift: {}", line);
+                }
+            }
+            Ok(())
+        }
+        ("up", true) | ("dry-run", _) => {
+            let results = dry_run(pool).await?;
+            if results.is_empty() {
+                println!("Nothing to apply");
+            }
+            for (version, result) in &results {
+                match result {
+                    Ok(()) => println!("{:>6}  ok", version),
+                    Err(e) => println!("{:>6}  FAILED: {}", version, e),
+                }
+            }
+            println!("Dry run: rolled back, nothing was changed");
+            if results.iter().any(|(_, r)| r.is_err()) {
+                anyhow::bail!("A pending migration fails against this database");
+            }
+            Ok(())
+        }
+        ("up", false) => {
+            let applied = up(pool).await?;
+            println!("Applied {} migration(s)", applied);
+            let drift = schema_drift(pool).await?;
+            for line in drift {
+                println!("drift: {}", line);
+            }
+            Ok(())
+        }
+        _ => anyhow::bail!("Usage: crypto-backend migrate [status | up | up --dry-run | dry-run]"),
+    }
+}
@@ -1,10 +1,12 @@
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct OrderValidationRequest {
     pub id: Option<String>, // Optional Order ID for processing
-    pub user_id: String,
+    #[serde(default)]
+    pub user_id: String, // Overwritten with the token's subject; must match if sent
     pub coin_id: String,
     pub coin_symbol: String,
     pub order_type: String,
@@ -69,14 +71,29 @@ pub struct PortfolioSummary {
 #[derive(Debug, Deserialize, Serialize)]
 pub struct IndicatorRequest {
     pub coin_id: String,
-    pub prices: Vec<Decimal>,
+    pub prices: Vec<Decimal>, // Closes, oldest first
     pub period: Option<u32>,
+    // Required by OHLC indicators (atr, stochastic, adx, ichimoku); volumes by obv and vwap
+    pub highs: Option<Vec<Decimal>>,
+    pub lows: Option<Vec<Decimal>>,
+    pub volumes: Option<Vec<Decimal>>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct IndicatorResponse {
-    pub value: Decimal,
-    pub values: Option<Vec<Decimal>>,
+    pub value: Decimal,                      // Latest reading of the main line
+    pub values: Option<Vec<Option<Decimal>>>, // Main line aligned with the input (null while warming up)
+    pub series: Option<HashMap<String, Vec<Option<Decimal>>>>, // Every line of multi-line indicators
+}
+
+/// Indicator computed server-side from stored candles
+#[derive(Debug, Serialize)]
+pub struct CandleIndicatorResponse {
+    pub coin_id: String,
+    pub interval: String,
+    pub open_times: Vec<i64>, // Candle open times (epoch ms) the series are aligned with
+    #[serde(flatten)]
+    pub indicator: IndicatorResponse,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -142,3 +159,14 @@ pub struct Order {
     pub created_at: Option<chrono::DateTime<chrono::Utc>>,
     pub completed_at: Option<chrono::DateTime<chrono::Utc>>,
 }
+
+/// OHLCV candle (open_time in epoch milliseconds, volume in base asset)
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Candle {
+    pub open_time: i64,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    pub volume: Decimal,
+}
@@ -0,0 +1,311 @@
+use crate::auth::{client_ip, AuthUser};
+use crate::state::AppState;
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header::RETRY_AFTER, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Buckets are swept once the table grows past this many entries
+const SWEEP_THRESHOLD: usize = 10_000;
+
+/// Built-in quotas; `RATE_LIMITS` entries for the same route replace them
+const DEFAULT_RULES: &str = "POST /api/automation/start=5/60,\
+POST /api/automation/optimize=3/60,\
+POST /api/orders/process=30/60,\
+POST /api/orders/validate=60/60,\
+POST /api/alerts=20/60,\
+POST /api/keys=5/60,\
+POST /api/webhooks=5/60,\
+POST /api/notifications/channels/:id/test=5/60,\
+*=300/60";
+
+/// Quota per client IP for all traffic, checked before authentication. Generous enough
+/// for several users behind one NAT; it's there to throttle floods and credential guessing.
+pub const DEFAULT_IP_RULE: &str = "1200/60";
+
+/// `capacity` requests per `period`, refilled continuously. `route` is the axum route
+/// pattern (e.g. `/api/automation/:id/panic`), `method` `None` matches any method.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimitRule {
+    pub method: Option<String>,
+    pub route: Option<String>, // None = default rule
+    pub capacity: u32,
+    pub period: Duration,
+}
+
+impl RateLimitRule {
+    fn label(&self) -> String {
+        match (&self.method, &self.route) {
+            (Some(method), Some(route)) => format!("{} {}", method, route),
+            (None, Some(route)) => route.clone(),
+            _ => "*".to_string(),
+        }
+    }
+
+    fn matches(&self, method: &str, route: &str) -> bool {
+        self.route.as_deref() == Some(route)
+            && self
+                .method
+                .as_deref()
+                .is_none_or(|m| m.eq_ignore_ascii_case(method))
+    }
+
+    fn refill_per_sec(&self) -> f64 {
+        self.capacity as f64 / self.period.as_secs_f64()
+    }
+}
+
+/// Parses `"POST /api/orders/process=30/60, /api/candles/:coin=120/60, *=300/60"`
+/// (`[METHOD] ROUTE=REQUESTS/SECONDS`, `*` for the default) on top of the built-in rules
+pub fn parse_rules(overrides: Option<&str>) -> Result<Vec<RateLimitRule>, String> {
+    let mut rules = parse_list(DEFAULT_RULES)?;
+    if let Some(overrides) = overrides {
+        for rule in parse_list(overrides)? {
+            rules.retain(|r| r.method != rule.method || r.route != rule.route);
+            rules.push(rule);
+        }
+    }
+    Ok(rules)
+}
+
+/// Parses the pre-auth per-IP quota, `REQUESTS/SECONDS`
+pub fn parse_ip_rule(raw: Option<&str>) -> Result<RateLimitRule, String> {
+    let raw = raw.unwrap_or(DEFAULT_IP_RULE);
+    parse_rule(&format!("*={}", raw))
+        .map_err(|_| format!("Invalid per-IP rate limit '{}', expected REQUESTS/SECONDS", raw))
+}
+
+fn parse_list(list: &str) -> Result<Vec<RateLimitRule>, String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(parse_rule)
+        .collect()
+}
+
+fn parse_rule(entry: &str) -> Result<RateLimitRule, String> {
+    let invalid = || {
+        format!(
+            "Invalid rate limit '{}', expected [METHOD] /route=REQUESTS/SECONDS",
+            entry
+        )
+    };
+    let (target, quota) = entry.rsplit_once('=').ok_or_else(invalid)?;
+    let (capacity, period) = quota.split_once('/').ok_or_else(invalid)?;
+    let capacity: u32 = capacity.trim().parse().map_err(|_| invalid())?;
+    let period: u64 = period.trim().parse().map_err(|_| invalid())?;
+    if capacity == 0 || period == 0 {
+        return Err(invalid());
+    }
+
+    let mut parts = target.split_whitespace();
+    let (method, route) = match (parts.next(), parts.next(), parts.next()) {
+        (Some("*"), None, None) => (None, None),
+        (Some(route), None, None) if route.starts_with('/') => (None, Some(route)),
+        (Some(method), Some(route), None) if route.starts_with('/') => {
+            (Some(method.to_uppercase()), Some(route))
+        }
+        _ => return Err(invalid()),
+    };
+
+    Ok(RateLimitRule {
+        method,
+        route: route.map(str::to_string),
+        capacity,
+        period: Duration::from_secs(period),
+    })
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+#[derive(Default)]
+struct Counters {
+    allowed: u64,
+    limited: u64,
+    limited_by_route: HashMap<String, u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RateLimitMetrics {
+    pub allowed: u64,
+    pub limited: u64,
+    pub limited_by_route: HashMap<String, u64>,
+    pub active_buckets: usize,
+}
+
+/// Token buckets per (rule, caller). Callers are API keys or users plus their client IP,
+/// or just the IP for the pre-auth bucket. The IP rule is kept last in `rules`.
+pub struct RateLimiter {
+    rules: Vec<RateLimitRule>,
+    ip_rule_index: usize,
+    buckets: Mutex<HashMap<(usize, String), Bucket>>,
+    counters: Mutex<Counters>,
+}
+
+impl RateLimiter {
+    pub fn new(mut rules: Vec<RateLimitRule>, ip_rule: RateLimitRule) -> Self {
+        rules.push(ip_rule);
+        Self {
+            ip_rule_index: rules.len() - 1,
+            rules,
+            buckets: Mutex::new(HashMap::new()),
+            counters: Mutex::new(Counters::default()),
+        }
+    }
+
+    fn rule_for(&self, method: &str, route: &str) -> Option<usize> {
+        let route_rules = &self.rules[..self.ip_rule_index];
+        route_rules
+            .iter()
+            .position(|r| r.matches(method, route))
+            .or_else(|| route_rules.iter().position(|r| r.route.is_none()))
+    }
+
+    /// Takes a token, or returns how long until one is available
+    fn check(&self, rule_index: usize, caller: &str) -> Result<(), Duration> {
+        let rule = &self.rules[rule_index];
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() > SWEEP_THRESHOLD {
+            // Buckets that have refilled completely carry no state worth keeping
+            let rules = &self.rules;
+            buckets.retain(|(index, _), bucket| {
+                let rule = &rules[*index];
+                let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+                bucket.tokens + elapsed * rule.refill_per_sec() < rule.capacity as f64
+            });
+        }
+
+        let bucket = buckets
+            .entry((rule_index, caller.to_string()))
+            .or_insert(Bucket {
+                tokens: rule.capacity as f64,
+                updated: now,
+            });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rule.refill_per_sec()).min(rule.capacity as f64);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / rule.refill_per_sec(),
+            ))
+        }
+    }
+
+    fn record(&self, rule_index: usize, allowed: bool) {
+        let mut counters = self.counters.lock().unwrap();
+        if allowed {
+            counters.allowed += 1;
+        } else {
+            counters.limited += 1;
+            let label = if rule_index == self.ip_rule_index {
+                "per IP".to_string()
+            } else {
+                self.rules[rule_index].label()
+            };
+            *counters.limited_by_route.entry(label).or_default() += 1;
+        }
+    }
+
+    pub fn metrics(&self) -> RateLimitMetrics {
+        let counters = self.counters.lock().unwrap();
+        RateLimitMetrics {
+            allowed: counters.allowed,
+            limited: counters.limited,
+            limited_by_route: counters.limited_by_route.clone(),
+            active_buckets: self.buckets.lock().unwrap().len(),
+        }
+    }
+}
+
+/// Per-IP quota for every request, before authentication, so unauthenticated and
+/// failed-auth traffic is throttled too
+pub async fn rate_limit_ip(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let caller = match client_ip(&request, &state) {
+        Some(ip) => format!("ip:{}", ip),
+        None => "ip:unknown".to_string(),
+    };
+    let rule_index = state.rate_limiter.ip_rule_index;
+    let route = request.uri().path().to_string();
+    enforce(&state, rule_index, caller, &route, request, next).await
+}
+
+/// Applies the route's quota to the caller; runs after `require_auth` so callers are
+/// known users or API keys, each with one bucket wherever they connect from
+pub async fn rate_limit(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let route = request.extensions().get::<MatchedPath>().map_or_else(
+        || request.uri().path().to_string(),
+        |p| p.as_str().to_string(),
+    );
+    let Some(rule_index) = state
+        .rate_limiter
+        .rule_for(request.method().as_str(), &route)
+    else {
+        return next.run(request).await;
+    };
+
+    let caller = match request.extensions().get::<AuthUser>() {
+        Some(AuthUser {
+            api_key_id: Some(key_id),
+            ..
+        }) => format!("key:{}", key_id),
+        Some(user) => format!("user:{}", user.user_id),
+        None => match client_ip(&request, &state) {
+            Some(ip) => format!("ip:{}", ip),
+            None => "ip:unknown".to_string(),
+        },
+    };
+
+    enforce(&state, rule_index, caller, &route, request, next).await
+}
+
+async fn enforce(
+    state: &AppState,
+    rule_index: usize,
+    caller: String,
+    route: &str,
+    request: Request,
+    next: Next,
+) -> Response {
+    match state.rate_limiter.check(rule_index, &caller) {
+        Ok(()) => {
+            state.rate_limiter.record(rule_index, true);
+            next.run(request).await
+        }
+        Err(wait) => {
+            state.rate_limiter.record(rule_index, false);
+            let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+            warn!(
+                "🚦 Rate limited {} on {} {} (retry in {}s)",
+                caller,
+                request.method(),
+                route,
+                retry_after
+            );
+            let mut response = (
+                StatusCode::TOO_MANY_REQUESTS,
+                format!("Too many requests, retry in {}s", retry_after),
+            )
+                .into_response();
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(retry_after));
+            response
+        }
+    }
+}
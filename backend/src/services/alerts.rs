@@ -0,0 +1,625 @@
+use crate::services::candles::{CandleAggregator, Interval};
+use crate::services::events::{DomainEvent, EventBus};
+use crate::services::indicators;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+pub const MAX_ALERTS_PER_USER: i64 = 100;
+pub const MIN_COOLDOWN_SECS: i32 = 60;
+pub const DEFAULT_COOLDOWN_SECS: i32 = 3600;
+/// Longest look-back for percent-change and volume-spike windows
+pub const MAX_WINDOW_MINUTES: u32 = 1440;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Above,
+    Below,
+}
+
+/// What an alert watches for. `PercentChange` is signed: `5` fires on a rise of 5% or
+/// more over the window, `-5` on a drop of 5% or more.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AlertCondition {
+    CrossesAbove {
+        price: Decimal,
+    },
+    CrossesBelow {
+        price: Decimal,
+    },
+    PercentChange {
+        percent: Decimal,
+        window_minutes: u32,
+    },
+    VolumeSpike {
+        multiplier: Decimal,  // Current minute's volume vs the average minute of the window
+        window_minutes: u32,
+    },
+    Rsi {
+        direction: Direction,
+        level: Decimal,
+        #[serde(default = "default_rsi_period")]
+        period: usize,
+        #[serde(default = "default_rsi_interval")]
+        interval: String, // 1m, 5m, 15m or 1h candles
+    },
+}
+
+fn default_rsi_period() -> usize {
+    14
+}
+
+fn default_rsi_interval() -> String {
+    "1m".to_string()
+}
+
+impl AlertCondition {
+    pub fn validate(&self) -> Result<(), String> {
+        let window_ok = |minutes: u32| (1..=MAX_WINDOW_MINUTES).contains(&minutes);
+        match self {
+            AlertCondition::CrossesAbove { price } | AlertCondition::CrossesBelow { price } => {
+                if *price <= Decimal::ZERO {
+                    return Err("price must be positive".to_string());
+                }
+            }
+            AlertCondition::PercentChange {
+                percent,
+                window_minutes,
+            } => {
+                if percent.is_zero() {
+                    return Err("percent must be non-zero (negative for drops)".to_string());
+                }
+                if !window_ok(*window_minutes) {
+                    return Err(format!("window_minutes must be 1 to {}", MAX_WINDOW_MINUTES));
+                }
+            }
+            AlertCondition::VolumeSpike {
+                multiplier,
+                window_minutes,
+            } => {
+                if *multiplier <= Decimal::ONE {
+                    return Err("multiplier must be above 1".to_string());
+                }
+                if !(2..=MAX_WINDOW_MINUTES).contains(window_minutes) {
+                    return Err(format!("window_minutes must be 2 to {}", MAX_WINDOW_MINUTES));
+                }
+            }
+            AlertCondition::Rsi {
+                level,
+                period,
+                interval,
+                ..
+            } => {
+                if *level <= Decimal::ZERO || *level >= Decimal::from(100) {
+                    return Err("level must be between 0 and 100".to_string());
+                }
+                if !(2..=100).contains(period) {
+                    return Err("period must be 2 to 100".to_string());
+                }
+                interval.parse::<Interval>()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// e.g. "BTC crossed above 65000"
+    pub fn describe(&self, coin_id: &str, value: Decimal) -> String {
+        let coin = coin_id.to_uppercase();
+        match self {
+            AlertCondition::CrossesAbove { price } => format!("{} crossed above {}", coin, price),
+            AlertCondition::CrossesBelow { price } => format!("{} crossed below {}", coin, price),
+            AlertCondition::PercentChange { window_minutes, .. } => format!(
+                "{} moved {}% in {} min",
+                coin,
+                value.round_dp(2),
+                window_minutes
+            ),
+            AlertCondition::VolumeSpike { window_minutes, .. } => format!(
+                "{} volume spiked to {}x its {} min average",
+                coin,
+                value.round_dp(1),
+                window_minutes
+            ),
+            AlertCondition::Rsi {
+                direction,
+                level,
+                interval,
+                ..
+            } => format!(
+                "{} {} RSI is {} ({} {})",
+                coin,
+                interval,
+                value.round_dp(1),
+                if *direction == Direction::Above { "above" } else { "below" },
+                level
+            ),
+        }
+    }
+
+    /// Candle interval and count covering `window_minutes` (1m candles for up to 8h)
+    fn window_candles(window_minutes: u32) -> (Interval, usize) {
+        if window_minutes <= 480 {
+            (Interval::M1, window_minutes as usize)
+        } else {
+            (Interval::M15, window_minutes.div_ceil(15) as usize)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct PriceAlert {
+    pub id: Uuid,
+    #[serde(skip)]
+    pub user_id: Uuid,
+    pub coin_id: String,
+    #[sqlx(json)]
+    pub condition: AlertCondition,
+    pub repeat: bool,       // Keep firing (at most once per cooldown) instead of once
+    pub cooldown_secs: i32,
+    pub status: String,     // active, triggered (one-shot alerts that fired)
+    pub note: Option<String>,
+    pub trigger_count: i32,
+    pub last_triggered_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl PriceAlert {
+    fn cooling_down(&self, now: DateTime<Utc>) -> bool {
+        self.last_triggered_at.is_some_and(|at| {
+            now < at + chrono::Duration::seconds(self.cooldown_secs as i64)
+        })
+    }
+}
+
+/// One coin's price in a ticker batch and the price it replaced
+pub struct PriceMove {
+    pub coin_id: String,
+    pub previous: Option<Decimal>,
+    pub price: Decimal,
+}
+
+pub async fn create(
+    pool: &PgPool,
+    user_id: Uuid,
+    coin_id: &str,
+    condition: &AlertCondition,
+    repeat: bool,
+    cooldown_secs: i32,
+    note: Option<&str>,
+) -> anyhow::Result<PriceAlert> {
+    Ok(sqlx::query_as::<_, PriceAlert>(
+        "INSERT INTO price_alerts (id, user_id, coin_id, condition, repeat, cooldown_secs, note)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)
+         RETURNING *",
+    )
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(coin_id)
+    .bind(serde_json::to_value(condition)?)
+    .bind(repeat)
+    .bind(cooldown_secs)
+    .bind(note)
+    .fetch_one(pool)
+    .await?)
+}
+
+/// Replaces the alert's settings and re-arms it. `None` when it isn't the user's.
+#[allow(clippy::too_many_arguments)]
+pub async fn update(
+    pool: &PgPool,
+    user_id: Uuid,
+    id: Uuid,
+    coin_id: &str,
+    condition: &AlertCondition,
+    repeat: bool,
+    cooldown_secs: i32,
+    note: Option<&str>,
+) -> anyhow::Result<Option<PriceAlert>> {
+    Ok(sqlx::query_as::<_, PriceAlert>(
+        "UPDATE price_alerts
+         SET coin_id = $3, condition = $4, repeat = $5, cooldown_secs = $6, note = $7,
+             status = 'active', last_triggered_at = NULL
+         WHERE id = $1 AND user_id = $2
+         RETURNING *",
+    )
+    .bind(id)
+    .bind(user_id)
+    .bind(coin_id)
+    .bind(serde_json::to_value(condition)?)
+    .bind(repeat)
+    .bind(cooldown_secs)
+    .bind(note)
+    .fetch_optional(pool)
+    .await?)
+}
+
+pub async fn list(pool: &PgPool, user_id: Uuid) -> anyhow::Result<Vec<PriceAlert>> {
+    Ok(sqlx::query_as::<_, PriceAlert>(
+        "SELECT * FROM price_alerts WHERE user_id = $1 ORDER BY created_at DESC",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?)
+}
+
+pub async fn count(pool: &PgPool, user_id: Uuid) -> anyhow::Result<i64> {
+    Ok(sqlx::query_scalar("SELECT COUNT(*) FROM price_alerts WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?)
+}
+
+pub async fn delete(pool: &PgPool, user_id: Uuid, id: Uuid) -> anyhow::Result<bool> {
+    let result = sqlx::query("DELETE FROM price_alerts WHERE id = $1 AND user_id = $2")
+        .bind(id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Active alerts by coin, checked against every ticker batch
+#[derive(Clone)]
+pub struct AlertBook {
+    pool: PgPool,
+    events: Arc<EventBus>,
+    alerts: Arc<Mutex<HashMap<String, Vec<PriceAlert>>>>, // CoinID -> Alerts
+}
+
+impl AlertBook {
+    pub fn new(pool: PgPool, events: Arc<EventBus>) -> Self {
+        Self {
+            pool,
+            events,
+            alerts: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub async fn load(&self) -> anyhow::Result<()> {
+        let rows = sqlx::query_as::<_, PriceAlert>("SELECT * FROM price_alerts WHERE status = 'active'")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut alerts = self.alerts.lock().await;
+        alerts.clear();
+        let count = rows.len();
+        for alert in rows {
+            alerts.entry(alert.coin_id.clone()).or_default().push(alert);
+        }
+        info!("Loaded {} active price alerts into memory", count);
+        Ok(())
+    }
+
+    /// Adds or replaces an alert (after create / update); inactive ones are dropped
+    pub async fn upsert(&self, alert: PriceAlert) {
+        let mut alerts = self.alerts.lock().await;
+        for coin_alerts in alerts.values_mut() {
+            coin_alerts.retain(|a| a.id != alert.id);
+        }
+        if alert.status == "active" {
+            alerts.entry(alert.coin_id.clone()).or_default().push(alert);
+        }
+    }
+
+    pub async fn remove(&self, id: Uuid) {
+        let mut alerts = self.alerts.lock().await;
+        for coin_alerts in alerts.values_mut() {
+            coin_alerts.retain(|a| a.id != id);
+        }
+    }
+
+    /// Fires every alert whose condition holds after this batch. One-shot alerts leave
+    /// the book; repeating ones wait out their cooldown.
+    ///
+    /// Conditions read candles, so they are checked on a copy of the due alerts: the book
+    /// is only locked to take that copy and to record what fired, never across an await.
+    pub async fn evaluate(&self, moves: &[PriceMove], candles: &CandleAggregator) {
+        let now = Utc::now();
+        let due: Vec<(&PriceMove, PriceAlert)> = {
+            let alerts = self.alerts.lock().await;
+            if alerts.is_empty() {
+                return;
+            }
+            moves
+                .iter()
+                .flat_map(|price_move| {
+                    alerts
+                        .get(&price_move.coin_id)
+                        .into_iter()
+                        .flatten()
+                        .filter(|alert| !alert.cooling_down(now))
+                        .map(move |alert| (price_move, alert.clone()))
+                })
+                .collect()
+        };
+
+        let mut hits = Vec::new();
+        for (price_move, alert) in due {
+            if let Some(value) = Self::check(&alert.condition, price_move, candles).await {
+                hits.push((price_move, alert, value));
+            }
+        }
+        if hits.is_empty() {
+            return;
+        }
+
+        let mut alerts = self.alerts.lock().await;
+        for (price_move, checked, value) in hits {
+            let Some(coin_alerts) = alerts.get_mut(&checked.coin_id) else {
+                continue;
+            };
+            // Skip alerts deleted, edited or already fired while they were being checked
+            let Some(index) = coin_alerts.iter().position(|a| {
+                a.id == checked.id && a.condition == checked.condition && !a.cooling_down(now)
+            }) else {
+                continue;
+            };
+
+            let alert = &mut coin_alerts[index];
+            alert.last_triggered_at = Some(now);
+            alert.trigger_count += 1;
+            self.fire(alert, price_move.price, value);
+            if !alert.repeat {
+                coin_alerts.remove(index);
+            }
+        }
+    }
+
+    /// The observed value when the condition holds: the price for crosses, the percent
+    /// move, the volume ratio or the RSI reading
+    async fn check(
+        condition: &AlertCondition,
+        price_move: &PriceMove,
+        candles: &CandleAggregator,
+    ) -> Option<Decimal> {
+        let price = price_move.price;
+        match condition {
+            AlertCondition::CrossesAbove { price: level } => {
+                let previous = price_move.previous?;
+                (previous < *level && price >= *level).then_some(price)
+            }
+            AlertCondition::CrossesBelow { price: level } => {
+                let previous = price_move.previous?;
+                (previous > *level && price <= *level).then_some(price)
+            }
+            AlertCondition::PercentChange {
+                percent,
+                window_minutes,
+            } => {
+                let (interval, count) = AlertCondition::window_candles(*window_minutes);
+                let window = candles.recent(&price_move.coin_id, interval, count, true).await;
+                // Only judge a full window, otherwise a fresh start looks like a flat market
+                if window.len() < count {
+                    return None;
+                }
+                let reference = window.first()?.open;
+                if reference <= Decimal::ZERO {
+                    return None;
+                }
+                let change = (price - reference) / reference * Decimal::from(100);
+                let hit = if percent.is_sign_positive() {
+                    change >= *percent
+                } else {
+                    change <= *percent
+                };
+                hit.then_some(change)
+            }
+            AlertCondition::VolumeSpike {
+                multiplier,
+                window_minutes,
+            } => {
+                let window = candles
+                    .recent(&price_move.coin_id, Interval::M1, *window_minutes as usize + 1, true)
+                    .await;
+                if window.len() < *window_minutes as usize + 1 {
+                    return None;
+                }
+                let (current, previous) = window.split_last()?;
+                let average = previous.iter().map(|c| c.volume).sum::<Decimal>()
+                    / Decimal::from(previous.len());
+                if average <= Decimal::ZERO {
+                    return None;
+                }
+                let ratio = current.volume / average;
+                (ratio >= *multiplier).then_some(ratio)
+            }
+            AlertCondition::Rsi {
+                direction,
+                level,
+                period,
+                interval,
+            } => {
+                let interval = interval.parse::<Interval>().ok()?;
+                // Wilder smoothing needs some history before it settles
+                let history = candles
+                    .recent(&price_move.coin_id, interval, period * 3 + 1, true)
+                    .await;
+                let closes: Vec<Decimal> = history.iter().map(|c| c.close).collect();
+                let rsi = indicators::latest(&indicators::rsi(&closes, *period))?;
+                let hit = match direction {
+                    Direction::Above => rsi >= *level,
+                    Direction::Below => rsi <= *level,
+                };
+                hit.then_some(rsi)
+            }
+        }
+    }
+
+    fn fire(&self, alert: &PriceAlert, price: Decimal, value: Decimal) {
+        let message = alert.condition.describe(&alert.coin_id, value);
+        info!("🔔 Price alert {} fired for user {}: {}", alert.id, alert.user_id, message);
+
+        self.events.publish(DomainEvent::PriceAlertTriggered {
+            alert_id: alert.id,
+            user_id: alert.user_id,
+            coin_id: alert.coin_id.clone(),
+            condition: alert.condition.clone(),
+            price,
+            value,
+            message,
+            note: alert.note.clone(),
+        });
+
+        // The matching loop never waits on the database
+        let pool = self.pool.clone();
+        let (id, repeat) = (alert.id, alert.repeat);
+        tokio::spawn(async move {
+            let result = sqlx::query(
+                "UPDATE price_alerts
+                 SET trigger_count = trigger_count + 1, last_triggered_at = NOW(),
+                     status = CASE WHEN $2 THEN status ELSE 'triggered' END
+                 WHERE id = $1",
+            )
+            .bind(id)
+            .bind(repeat)
+            .execute(&pool)
+            .await;
+            if let Err(e) = result {
+                warn!("⚠️ Failed to record trigger of price alert {}: {}", id, e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::candles::Tick;
+    use sqlx::postgres::PgPoolOptions;
+    use std::str::FromStr;
+
+    const MINUTE_MS: i64 = 60_000;
+
+    /// Nothing here reaches the database (trigger updates just fail in the background)
+    fn pool() -> PgPool {
+        PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/unused")
+            .unwrap()
+    }
+
+    fn dec(v: &str) -> Decimal {
+        Decimal::from_str(v).unwrap()
+    }
+
+    fn alert(condition: AlertCondition, repeat: bool) -> PriceAlert {
+        PriceAlert {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            coin_id: "btc".to_string(),
+            condition,
+            repeat,
+            cooldown_secs: 600,
+            status: "active".to_string(),
+            note: None,
+            trigger_count: 0,
+            last_triggered_at: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    fn price_move(previous: Option<&str>, price: &str) -> PriceMove {
+        PriceMove {
+            coin_id: "btc".to_string(),
+            previous: previous.map(dec),
+            price: dec(price),
+        }
+    }
+
+    /// One tick per minute, so every price after the first opens a new 1m candle
+    async fn candles_from(prices: &[&str]) -> CandleAggregator {
+        let candles = CandleAggregator::new(pool());
+        for (minute, price) in prices.iter().enumerate() {
+            let tick = Tick {
+                coin_id: "btc".to_string(),
+                price: dec(price),
+                volume_24h: Decimal::ZERO,
+            };
+            candles.ingest(minute as i64 * MINUTE_MS, &[tick]).await;
+        }
+        candles
+    }
+
+    #[tokio::test]
+    async fn crosses_fire_only_when_the_level_is_crossed() {
+        let candles = CandleAggregator::new(pool());
+        let above = AlertCondition::CrossesAbove { price: dec("100") };
+        let below = AlertCondition::CrossesBelow { price: dec("100") };
+
+        let crossing = |previous, price| price_move(Some(previous), price);
+        assert_eq!(AlertBook::check(&above, &crossing("99", "100"), &candles).await, Some(dec("100")));
+        assert_eq!(AlertBook::check(&above, &crossing("101", "102"), &candles).await, None); // Already above
+        assert_eq!(AlertBook::check(&above, &price_move(None, "105"), &candles).await, None); // No previous price
+        assert_eq!(AlertBook::check(&below, &crossing("101", "99.5"), &candles).await, Some(dec("99.5")));
+        assert_eq!(AlertBook::check(&below, &crossing("99", "98"), &candles).await, None);
+    }
+
+    #[tokio::test]
+    async fn percent_change_needs_a_full_window() {
+        let rise = AlertCondition::PercentChange {
+            percent: dec("5"),
+            window_minutes: 3,
+        };
+        let drop = AlertCondition::PercentChange {
+            percent: dec("-5"),
+            window_minutes: 3,
+        };
+
+        // Window opens at 100; 106 is +6%
+        let candles = candles_from(&["100", "103", "106"]).await;
+        let up = price_move(Some("103"), "106");
+        assert_eq!(AlertBook::check(&rise, &up, &candles).await, Some(dec("6")));
+        assert_eq!(AlertBook::check(&drop, &up, &candles).await, None);
+
+        let candles = candles_from(&["100", "97", "94"]).await;
+        let down = price_move(Some("97"), "94");
+        assert_eq!(AlertBook::check(&drop, &down, &candles).await, Some(dec("-6")));
+        assert_eq!(AlertBook::check(&rise, &down, &candles).await, None);
+
+        // Two minutes of history can't judge a three minute window
+        let candles = candles_from(&["100", "110"]).await;
+        assert_eq!(AlertBook::check(&rise, &price_move(Some("100"), "110"), &candles).await, None);
+    }
+
+    #[test]
+    fn cooldown_runs_from_the_last_trigger() {
+        let now = Utc::now();
+        let mut alert = alert(AlertCondition::CrossesAbove { price: dec("100") }, true);
+        assert!(!alert.cooling_down(now));
+
+        alert.last_triggered_at = Some(now - chrono::Duration::seconds(599));
+        assert!(alert.cooling_down(now));
+        alert.last_triggered_at = Some(now - chrono::Duration::seconds(600));
+        assert!(!alert.cooling_down(now));
+    }
+
+    #[tokio::test]
+    async fn one_shot_alerts_leave_the_book_and_repeating_ones_cool_down() {
+        let book = AlertBook::new(pool(), Arc::new(EventBus::new()));
+        let candles = CandleAggregator::new(pool());
+        let once = alert(AlertCondition::CrossesAbove { price: dec("100") }, false);
+        let repeating = alert(AlertCondition::CrossesAbove { price: dec("100") }, true);
+        book.upsert(once.clone()).await;
+        book.upsert(repeating.clone()).await;
+
+        book.evaluate(&[price_move(Some("99"), "101")], &candles).await;
+        {
+            let alerts = book.alerts.lock().await;
+            let remaining = &alerts["btc"];
+            assert_eq!(remaining.len(), 1);
+            assert_eq!(remaining[0].id, repeating.id);
+            assert_eq!(remaining[0].trigger_count, 1);
+        }
+
+        // Crosses again within the cooldown: nothing fires
+        book.evaluate(&[price_move(Some("99"), "101")], &candles).await;
+        assert_eq!(book.alerts.lock().await["btc"][0].trigger_count, 1);
+    }
+}
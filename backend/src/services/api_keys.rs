@@ -0,0 +1,227 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::net::IpAddr;
+use tracing::warn;
+use uuid::Uuid;
+
+pub const SCOPES: [&str; 3] = ["read", "trade", "automation"];
+/// Signed requests must carry a timestamp within this distance of server time
+pub const SIGNATURE_WINDOW_MS: i64 = 30_000;
+pub const MAX_KEYS_PER_USER: i64 = 20;
+
+/// A key as shown to its owner (the secret is only returned once, at creation)
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub name: String,
+    pub key_id: String,            // Public part, e.g. "ck_3fa9c2d1"
+    pub scopes: Vec<String>,       // read, trade, automation
+    pub ip_allowlist: Vec<String>, // IPs or CIDRs; empty allows any address
+    pub require_signature: bool,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// What the auth middleware needs to check a presented key
+#[derive(Debug, sqlx::FromRow)]
+pub struct KeyCredential {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub key_id: String,
+    pub key_hash: String,
+    pub scopes: Vec<String>,
+    pub ip_allowlist: Vec<String>,
+    pub require_signature: bool,
+}
+
+/// Creates a key and returns it with the full secret (`ck_<id>_<secret>`), which is
+/// not stored anywhere
+pub async fn create(
+    pool: &PgPool,
+    user_id: Uuid,
+    name: &str,
+    scopes: &[String],
+    ip_allowlist: &[String],
+    require_signature: bool,
+) -> anyhow::Result<(ApiKey, String)> {
+    let key_id = format!("ck_{}", random_string(10).to_lowercase());
+    let secret = format!("{}_{}", key_id, random_string(40));
+
+    let key = sqlx::query_as::<_, ApiKey>(
+        "INSERT INTO api_keys (id, user_id, name, key_id, key_hash, scopes, ip_allowlist, require_signature)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+         RETURNING id, name, key_id, scopes, ip_allowlist, require_signature, created_at, last_used_at, revoked_at",
+    )
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(name)
+    .bind(&key_id)
+    .bind(hash_key(&secret))
+    .bind(scopes)
+    .bind(ip_allowlist)
+    .bind(require_signature)
+    .fetch_one(pool)
+    .await?;
+
+    Ok((key, secret))
+}
+
+pub async fn list(pool: &PgPool, user_id: Uuid) -> anyhow::Result<Vec<ApiKey>> {
+    Ok(sqlx::query_as::<_, ApiKey>(
+        "SELECT id, name, key_id, scopes, ip_allowlist, require_signature, created_at, last_used_at, revoked_at
+         FROM api_keys WHERE user_id = $1 ORDER BY created_at DESC",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?)
+}
+
+pub async fn count_active(pool: &PgPool, user_id: Uuid) -> anyhow::Result<i64> {
+    Ok(sqlx::query_scalar(
+        "SELECT COUNT(*) FROM api_keys WHERE user_id = $1 AND revoked_at IS NULL",
+    )
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?)
+}
+
+/// Returns false when the key doesn't exist, isn't the user's or was already revoked
+pub async fn revoke(pool: &PgPool, user_id: Uuid, id: Uuid) -> anyhow::Result<bool> {
+    let result = sqlx::query(
+        "UPDATE api_keys SET revoked_at = NOW() WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL",
+    )
+    .bind(id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn find_active(pool: &PgPool, key_id: &str) -> anyhow::Result<Option<KeyCredential>> {
+    Ok(sqlx::query_as::<_, KeyCredential>(
+        "SELECT id, user_id, key_id, key_hash, scopes, ip_allowlist, require_signature
+         FROM api_keys WHERE key_id = $1 AND revoked_at IS NULL",
+    )
+    .bind(key_id)
+    .fetch_optional(pool)
+    .await?)
+}
+
+pub fn touch(pool: &PgPool, id: Uuid) {
+    let pool = pool.clone();
+    tokio::spawn(async move {
+        if let Err(e) = sqlx::query("UPDATE api_keys SET last_used_at = NOW() WHERE id = $1")
+            .bind(id)
+            .execute(&pool)
+            .await
+        {
+            warn!("⚠️ Failed to update last_used_at for API key {}: {}", id, e);
+        }
+    });
+}
+
+/// SHA-256 of the full key, hex encoded. This is all that is stored, and it only
+/// authenticates requests that present the full key.
+pub fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+/// Per-key HMAC secret for signed requests: `hex(HMAC-SHA256(pepper, key_id))`. Derived on
+/// demand from the server-side pepper, so a copy of the database alone can't sign requests.
+pub fn signing_secret(pepper: &str, key_id: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(pepper.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(key_id.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Checks `hex(HMAC-SHA256(signing_secret, timestamp + METHOD + path_and_query + body))`
+pub fn verify_signature(
+    signing_secret: &str,
+    timestamp: &str,
+    method: &str,
+    path_and_query: &str,
+    body: &[u8],
+    signature: &str,
+) -> bool {
+    let Ok(expected) = hex::decode(signature.trim()) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(signing_secret.as_bytes()) else {
+        return false;
+    };
+    mac.update(timestamp.as_bytes());
+    mac.update(method.as_bytes());
+    mac.update(path_and_query.as_bytes());
+    mac.update(body);
+    mac.verify_slice(&expected).is_ok()
+}
+
+/// Constant-time comparison of a presented key's hash with the stored one
+pub fn hash_matches(stored_hash: &str, presented_key: &str) -> bool {
+    constant_time_eq(stored_hash.as_bytes(), hash_key(presented_key).as_bytes())
+}
+
+/// Byte comparison that doesn't stop at the first difference
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// `entry` is a plain IP or a CIDR block such as `10.0.0.0/8`
+pub fn ip_allowed(allowlist: &[String], ip: IpAddr) -> bool {
+    allowlist.is_empty() || allowlist.iter().any(|entry| ip_matches(entry, ip))
+}
+
+pub fn valid_allowlist_entry(entry: &str) -> bool {
+    parse_cidr(entry).is_some()
+}
+
+fn ip_matches(entry: &str, ip: IpAddr) -> bool {
+    let Some((network, prefix)) = parse_cidr(entry) else {
+        return false;
+    };
+    match (network, ip) {
+        (IpAddr::V4(net), IpAddr::V4(ip)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            u32::from(net) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(net), IpAddr::V6(ip)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            u128::from(net) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
+
+fn parse_cidr(entry: &str) -> Option<(IpAddr, u32)> {
+    let (addr, prefix) = match entry.trim().split_once('/') {
+        Some((addr, prefix)) => (addr, Some(prefix.parse::<u32>().ok()?)),
+        None => (entry.trim(), None),
+    };
+    let addr: IpAddr = addr.parse().ok()?;
+    let max = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix = prefix.unwrap_or(max);
+    (prefix <= max).then_some((addr, prefix))
+}
+
+pub fn random_string(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+/// `ck_<id>` from either the bare key id or a full key
+pub fn key_id_of(presented: &str) -> &str {
+    match presented.match_indices('_').nth(1) {
+        Some((i, _)) => &presented[..i],
+        None => presented,
+    }
+}
@@ -1,15 +1,25 @@
+use crate::models::Candle;
+use crate::services::binance::BinanceClient;
+use crate::services::candles::Interval;
+use crate::services::depth::DepthView;
+use crate::services::indicators;
 use crate::services::matching_engine::MatchingEngine;
+use crate::services::realtime::{Channel, RealtimeHub};
 use chrono::{DateTime, Utc};
 use futures::stream::{self, StreamExt};
-use reqwest::Client;
-use rust_decimal::{Decimal, MathematicalOps};
-use serde::Deserialize;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 use sqlx::PgPool;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use tokio::sync::Mutex;
 use tokio::time::{sleep, Duration};
 use tracing::{error, info, warn};
-use crate::services::execution::execute_order;
+use crate::services::events::{append_to_outbox, next_event, DomainEvent, EventBus};
+use crate::services::execution::{execute_order, trading_fee};
+use crate::services::sizing::{position_notional, SizingInputs, SizingMode};
 use uuid::Uuid;
 use std::str::FromStr;
 
@@ -29,12 +39,107 @@ struct Strategy {
     current_coin_id: Option<String>,
     current_order_id: Option<Uuid>,
     entry_price: Option<Decimal>,
-    high_water_mark: Option<Decimal>,
-    // Dynamic profit taking tracking
-    profit_target_1_sold: Option<bool>, // 25% sold at +2%
-    profit_target_2_sold: Option<bool>, // 25% sold at +4%
-    profit_target_3_sold: Option<bool>, // 25% sold at +6%
-    break_even_activated: Option<bool>, // Stop moved to break-even
+    mode: String, // "live" (simulated wallet) or "paper" (shadow fills only)
+    // Portfolio-level risk caps
+    max_positions: i32,
+    max_coin_exposure: Option<Decimal>,   // Defaults to one `amount` per coin
+    max_total_exposure: Option<Decimal>,  // Defaults to `amount * max_positions`
+    max_correlated_positions: i32,        // Open positions allowed per correlation group
+    // Position sizing (see services::sizing)
+    sizing_mode: String,
+    risk_percent: Decimal,
+    target_volatility: Decimal,
+    kelly_fraction: Decimal,
+    // Scaled exits (NULL = the default ladder, see `exit_ladder`)
+    take_profit_ladder: Option<sqlx::types::Json<Vec<TakeProfitLevel>>>,
+    // Optimizer recommendation applied to this strategy (NULL = StrategyParams::default)
+    tuned_params: Option<sqlx::types::Json<StrategyParams>>,
+}
+
+/// One tranche of the scaled exit ladder: sell `fraction` of the original quantity at `target_pct` profit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TakeProfitLevel {
+    pub target_pct: Decimal,
+    pub fraction: Decimal,
+}
+
+/// Ladder levels map onto the position's profit_target_{1,2,3}_sold flags
+pub const MAX_TAKE_PROFIT_LEVELS: usize = 3;
+
+/// One open (or closed) trade of a strategy with its own trailing-stop state
+#[derive(Debug, sqlx::FromRow)]
+#[allow(dead_code)]
+struct Position {
+    id: Uuid,
+    strategy_id: Uuid,
+    coin_id: String,
+    quantity: Decimal,
+    entry_price: Decimal,
+    cost: Decimal, // Notional at entry
+    high_water_mark: Decimal,
+    // Dynamic profit taking tracking (one flag per ladder level)
+    profit_target_1_sold: bool,
+    profit_target_2_sold: bool,
+    profit_target_3_sold: bool,
+    break_even_activated: bool, // Stop moved to break-even after the first tranche
+    realized_profit: Decimal,   // Net profit already banked by partial sells
+}
+
+impl Position {
+    fn targets_sold(&self) -> [bool; MAX_TAKE_PROFIT_LEVELS] {
+        [self.profit_target_1_sold, self.profit_target_2_sold, self.profit_target_3_sold]
+    }
+
+    /// Quantity still held after the ladder tranches already sold
+    fn remaining_quantity(&self, ladder: &[TakeProfitLevel]) -> Decimal {
+        let sold_fraction: Decimal = ladder
+            .iter()
+            .zip(self.targets_sold())
+            .filter(|(_, sold)| *sold)
+            .map(|(level, _)| level.fraction)
+            .sum();
+        self.quantity * (Decimal::ONE - sold_fraction.min(Decimal::ONE))
+    }
+}
+
+/// Coins that tend to move together; a strategy may only hold `max_correlated_positions` per group
+const CORRELATION_GROUPS: &[(&str, &[&str])] = &[
+    ("l1", &["btc", "eth", "bnb", "sol", "ada", "avax", "dot", "trx", "ton", "near", "atom", "apt", "sui", "sei", "icp", "xrp", "xlm", "ltc", "bch", "etc"]),
+    ("l2", &["arb", "op", "matic", "pol", "strk", "imx", "mnt"]),
+    ("meme", &["doge", "shib", "pepe", "floki", "bonk", "wif", "bome", "meme"]),
+    ("defi", &["uni", "aave", "link", "mkr", "crv", "ldo", "comp", "snx", "pendle", "jup", "ena"]),
+];
+
+/// Smallest entry worth placing (Binance rejects orders below ~5 USDT notional)
+const MIN_ORDER_NOTIONAL: i64 = 5;
+
+fn correlation_group(coin_id: &str) -> Option<&'static str> {
+    CORRELATION_GROUPS
+        .iter()
+        .find(|(_, coins)| coins.contains(&coin_id))
+        .map(|(group, _)| *group)
+}
+
+impl Strategy {
+    fn is_paper(&self) -> bool {
+        self.mode == "paper"
+    }
+
+    /// The configured ladder, or 25% tranches at 50%, 75% and 100% of `profit_percentage`
+    /// (the last quarter exits at the profit target)
+    fn exit_ladder(&self) -> Vec<TakeProfitLevel> {
+        if let Some(ladder) = &self.take_profit_ladder {
+            return ladder.0.clone();
+        }
+        let quarter = Decimal::from_str("0.25").unwrap();
+        ["0.5", "0.75", "1"]
+            .iter()
+            .map(|step| TakeProfitLevel {
+                target_pct: self.profit_percentage * Decimal::from_str(step).unwrap(),
+                fraction: quarter,
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug, sqlx::FromRow)]
@@ -45,12 +150,6 @@ struct OrderStatusRow {
     quantity: Decimal,
 }
 
-#[derive(Debug, Deserialize)]
-struct BinanceOrderBookResponse {
-    bids: Vec<[String; 2]>, // [price, quantity]
-    asks: Vec<[String; 2]>, // [price, quantity]
-}
-
 #[derive(Debug, Deserialize)]
 #[allow(non_snake_case)]
 struct BinanceTrade {
@@ -69,109 +168,171 @@ struct BinanceTrade {
 
 
 
-#[derive(Debug, Clone)]
-struct CoinAnalysis {
+/// Latest per-coin analysis; cached by the engine and exposed through the market screener
+#[derive(Debug, Clone, Serialize)]
+pub struct CoinAnalysis {
+    pub coin_id: String,
+    pub current_price: Decimal,
+    pub predicted_price_10m: Decimal,
+    pub price_change_percent: Decimal,
+    pub rsi: Decimal, // RSI for filtering overbought coins
+    pub macd: Decimal, // MACD line
+    pub macd_signal: Decimal, // MACD signal line
+    pub macd_histogram: Decimal, // MACD histogram
+    pub bb_middle: Decimal, // Bollinger middle band
+    pub bb_lower: Decimal, // Bollinger lower band
+    pub support_level: Decimal, // Nearest support level (used in scoring)
+    pub resistance_level: Decimal, // Nearest resistance level
+    pub vwap: Decimal, // VWAP of recent trades
+    pub vwap_bias: Decimal, // Boost below VWAP, penalty above
+    pub resistance_wall_detected: bool, // Ask wall > 5x average near the price
+    pub volume_ratio: Decimal, // Current volume / 24h average volume
+    pub entry_score: Decimal, // Combined entry confidence score (0-1)
+    pub buy_pressure: Decimal, // Total buy quantity * price
+    pub sell_pressure: Decimal, // Total sell quantity * price
+    pub analyzed_at: DateTime<Utc>,
+}
+
+/// Cached analyses older than this are dropped from the screener
+const ANALYSIS_CACHE_MAX_AGE_MINUTES: i64 = 15;
+
+/// Cycles without an entry run every few seconds; only one "skip" decision per strategy is kept per minute
+const SKIP_DECISION_INTERVAL_SECS: i64 = 60;
+
+/// Tracked orders advance on fill/cancel events; the database is only polled this often to
+/// reconcile anything an event missed (e.g. while the subscriber lagged)
+const ORDER_RECONCILE_INTERVAL_SECS: i64 = 60;
+
+/// One coin considered for entry and the filter that dropped it (if any)
+#[derive(Debug, Serialize)]
+struct CandidateDecision {
     coin_id: String,
-    current_price: Decimal,
-    predicted_price_10m: Decimal,
-    price_change_percent: Decimal,
-    rsi: Decimal, // RSI for filtering overbought coins
-    #[allow(dead_code)]
-    macd: Decimal, // MACD line
-    #[allow(dead_code)]
-    macd_signal: Decimal, // MACD signal line
-    #[allow(dead_code)]
-    macd_histogram: Decimal, // MACD histogram
-    #[allow(dead_code)]
-    support_level: Decimal, // Nearest support level (used in scoring)
-    #[allow(dead_code)]
-    resistance_level: Decimal, // Nearest resistance level
-    volume_ratio: Decimal, // Current volume / 24h average volume
-    entry_score: Decimal, // Combined entry confidence score (0-1)
-    #[allow(dead_code)]
-    buy_pressure: Decimal, // Total buy quantity * price
-    #[allow(dead_code)]
-    sell_pressure: Decimal, // Total sell quantity * price
+    stage: &'static str, // "prefilter" or "analysis"
+    rejection: Option<String>,
+    entry_score: Option<Decimal>,
+    rsi: Option<Decimal>,
+}
+
+impl CandidateDecision {
+    fn rejected(coin_id: &str, stage: &'static str, reason: String) -> Self {
+        Self {
+            coin_id: coin_id.to_string(),
+            stage,
+            rejection: Some(reason),
+            entry_score: None,
+            rsi: None,
+        }
+    }
+}
+
+/// Tunable strategy constants. Defaults match the values the engine has always traded with;
+/// the walk-forward optimizer searches over these.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StrategyParams {
+    pub profit_percentage: Decimal,
+    pub rsi_oversold: Decimal,          // Full RSI score below this
+    pub rsi_overbought: Decimal,        // Hard reject above this
+    pub atr_stop_multiplier: Decimal,   // Initial stop: entry - N * ATR
+    pub atr_trail_multiplier: Decimal,  // Trailing stop: high water mark - N * ATR
+    pub min_volume_ratio: Decimal,      // Volume confirmation floor
+}
+
+impl Default for StrategyParams {
+    fn default() -> Self {
+        Self {
+            profit_percentage: Decimal::from(2),
+            rsi_oversold: Decimal::from(30),
+            rsi_overbought: Decimal::from(70),
+            atr_stop_multiplier: Decimal::from(3),
+            atr_trail_multiplier: Decimal::from(2),
+            min_volume_ratio: Decimal::from_str("1.2").unwrap(),
+        }
+    }
+}
+
+impl StrategyParams {
+    /// Applied optimizer values when there are any; the profit target is always the
+    /// strategy's own `profit_percentage` (applying a recommendation updates both)
+    fn for_strategy(strategy: &Strategy) -> Self {
+        let base = strategy
+            .tuned_params
+            .as_ref()
+            .map(|tuned| tuned.0.clone())
+            .unwrap_or_default();
+        Self {
+            profit_percentage: strategy.profit_percentage,
+            ..base
+        }
+    }
+}
+
+/// Indicator readings that feed `AutomationEngine::score_entry`
+pub(crate) struct EntrySignals {
+    pub current_price: Decimal,
+    pub rsi: Decimal,
+    pub macd: Decimal,
+    pub macd_signal: Decimal,
+    pub macd_histogram: Decimal,
+    pub bb_middle: Decimal,
+    pub bb_lower: Decimal,
+    pub volume_ratio: Decimal,
+    pub support_level: Decimal,
 }
 
 pub struct AutomationEngine {
     pool: PgPool,
     matching_engine: MatchingEngine,
-    http_client: Client,
+    binance: Arc<BinanceClient>,
+    latest_analyses: Mutex<HashMap<String, CoinAnalysis>>, // CoinID -> last analysis
+    last_skip_recorded: Mutex<HashMap<Uuid, DateTime<Utc>>>, // StrategyID -> last "skip" decision
+    last_order_reconcile: Mutex<Option<DateTime<Utc>>>, // Last poll of tracked orders' status
+    entries_paused: AtomicBool, // Set while the price feed is unhealthy
+    hub: Arc<RealtimeHub>,      // Pushes strategy actions and decisions to /ws clients
+    events: Arc<EventBus>,      // Fills/cancellations in, strategy state changes out
+    cycle: Mutex<()>,           // Held while strategies are processed, from the loop or an event
 }
 
 impl AutomationEngine {
-    pub fn new(pool: PgPool, matching_engine: MatchingEngine) -> Self {
+    pub fn new(
+        pool: PgPool,
+        matching_engine: MatchingEngine,
+        binance: Arc<BinanceClient>,
+        hub: Arc<RealtimeHub>,
+        events: Arc<EventBus>,
+    ) -> Self {
         Self {
             pool,
             matching_engine,
-            http_client: Client::new(),
+            binance,
+            latest_analyses: Mutex::new(HashMap::new()),
+            last_skip_recorded: Mutex::new(HashMap::new()),
+            last_order_reconcile: Mutex::new(None),
+            entries_paused: AtomicBool::new(false),
+            hub,
+            events,
+            cycle: Mutex::new(()),
         }
     }
 
     pub async fn start(self: Arc<Self>) {
         info!("🤖 Starting Advanced Automation Engine...");
 
-        // Ensure Schema Migration
-        if let Err(e) =
-            sqlx::query("ALTER TABLE strategies ADD COLUMN IF NOT EXISTS current_order_id uuid")
-                .execute(&self.pool)
-                .await
-        {
-            error!("⚠️ Failed to run runtime migration for strategies: {}", e);
-        }
-        
-        // Add new fields for dynamic profit taking and break-even stop
-        let migrations = vec![
-            "ALTER TABLE strategies ADD COLUMN IF NOT EXISTS profit_target_1_sold BOOLEAN",
-            "ALTER TABLE strategies ADD COLUMN IF NOT EXISTS profit_target_2_sold BOOLEAN",
-            "ALTER TABLE strategies ADD COLUMN IF NOT EXISTS profit_target_3_sold BOOLEAN",
-            "ALTER TABLE strategies ADD COLUMN IF NOT EXISTS break_even_activated BOOLEAN",
-        ];
-        
-        for migration in migrations {
-            if let Err(e) = sqlx::query(migration).execute(&self.pool).await {
-                warn!("⚠️ Schema migration warning (may already exist): {} - {}", migration, e);
-            }
-        }
-
-        // Ensure profit_percentage is DECIMAL (not Integer)
-        if let Err(e) =
-            sqlx::query("ALTER TABLE strategies ALTER COLUMN profit_percentage TYPE NUMERIC")
-                .execute(&self.pool)
-                .await
-        {
-             // Ignore error if it's just a type conversion issue to same type, but log it
-             warn!("⚠️ Failed to enforce profit_percentage type: {}", e);
-        }
-
-        // 🛡️ CRITICAL FIX: Ensure orders table has high precision for low-value coins
-        // (Prevents 0.07224 truncating to 0.07)
-        if let Err(e) = sqlx::query("
-            ALTER TABLE orders 
-            ALTER COLUMN price_per_unit TYPE NUMERIC,
-            ALTER COLUMN quantity TYPE NUMERIC,
-            ALTER COLUMN total_amount TYPE NUMERIC
-        ").execute(&self.pool).await {
-            warn!("⚠️ Failed to enforce high precision on orders table: {}", e);
-        }
-
-        // Add high_water_mark for Trailing Stop
-        if let Err(e) =
-            sqlx::query("ALTER TABLE strategies ADD COLUMN IF NOT EXISTS high_water_mark NUMERIC")
-                .execute(&self.pool)
-                .await
-        {
-            warn!("⚠️ Failed to add high_water_mark column: {}", e);
-        }
-
+        // React to fills and cancellations of tracked orders without waiting for the next poll
+        let watcher = self.clone();
+        tokio::spawn(async move {
+            watcher.watch_order_events().await;
+        });
 
         let self_clone = self.clone();
 
         tokio::spawn(async move {
             let mut error_count = 0;
             loop {
-                match self_clone.process_strategies().await {
+                let result = {
+                    let _cycle = self_clone.cycle.lock().await;
+                    self_clone.process_strategies().await
+                };
+                match result {
                     Ok(_) => {
                         if error_count > 0 {
                             info!("✅ Automation Loop recovered.");
@@ -198,6 +359,38 @@ impl AutomationEngine {
         });
     }
 
+    async fn watch_order_events(&self) {
+        let mut events = self.events.subscribe();
+        while let Some(event) = next_event(&mut events, "Automation engine").await {
+            let order_id = match event {
+                DomainEvent::OrderFilled { order_id, .. }
+                | DomainEvent::OrderCancelled { order_id, .. } => order_id,
+                _ => continue,
+            };
+            if let Err(e) = self.on_order_update(order_id).await {
+                warn!("⚠️ Failed to handle update of order {}: {}", order_id, e);
+            }
+        }
+    }
+
+    /// Advances the strategy tracking `order_id`, if any (the reconciliation sweep in
+    /// `process_strategies` catches anything missed here)
+    async fn on_order_update(&self, order_id: Uuid) -> anyhow::Result<()> {
+        let _cycle = self.cycle.lock().await;
+        let strategy = sqlx::query_as::<_, Strategy>(
+            "SELECT * FROM strategies WHERE current_order_id = $1 AND status = 'running'",
+        )
+        .bind(order_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        if let Some(strategy) = strategy {
+            info!("⚡ Strategy {}: tracked order {} updated", strategy.id, order_id);
+            self.check_order_status(&strategy, order_id).await?;
+        }
+        Ok(())
+    }
+
     async fn process_strategies(&self) -> anyhow::Result<()> {
         // 1. Fetch running strategies
         let strategies =
@@ -208,7 +401,7 @@ impl AutomationEngine {
         if strategies.is_empty() {
             return Ok(());
         }
-
+        let reconcile_orders = self.order_reconcile_due().await;
 
 
 
@@ -249,12 +442,24 @@ impl AutomationEngine {
             }
         }
 
-        // 3. Get current prices
+        // 3. Get current prices (stale coins are left out)
         let prices = self.matching_engine.get_prices().await;
         if prices.is_empty() {
             return Ok(());
         }
 
+        // Exits keep running on whatever is fresh, but no new risk on a frozen feed
+        let feed = self.matching_engine.feed_health().await;
+        let was_paused = self.entries_paused.swap(!feed.healthy, Ordering::Relaxed);
+        if !feed.healthy && !was_paused {
+            warn!(
+                "⏸️ Price feed unhealthy (connected: {}, last batch {:?}s ago), pausing entries",
+                feed.connected, feed.seconds_since_last_batch
+            );
+        } else if feed.healthy && was_paused {
+            info!("▶️ Price feed recovered, resuming entries");
+        }
+
         // 4. Process each strategy
         for strategy in strategies {
             // Double-check status (might have been stopped above)
@@ -274,21 +479,53 @@ impl AutomationEngine {
             };
 
             if let Some(order_id) = strategy.current_order_id {
-                // Monitor Active Order (Buy or Sell)
-                self.check_order_status(&strategy, order_id).await?;
-            } else if let Some(coin_id) = &strategy.current_coin_id {
-                // Currently holding a coin, waiting for sell order
-                self.handle_active_trade(&strategy, &prices, coin_id)
-                    .await?;
-            } else {
-                // Not in a trade: Analyze ALL coins and find best opportunity
-                self.handle_entry(&strategy, &prices).await?;
+                // Active order (buy or sell): fills and cancellations arrive as events, so
+                // only a periodic sweep reads its status here
+                if reconcile_orders {
+                    self.check_order_status(&strategy, order_id).await?;
+                }
+                continue;
+            }
+
+            // Manage every open position (trailing stops / targets)
+            let positions = self.open_positions(strategy.id).await?;
+            let mut closed = 0;
+            for position in &positions {
+                if self.handle_active_trade(&strategy, &prices, position).await? {
+                    closed += 1;
+                }
+            }
+
+            // Free slot and iterations left: Analyze ALL coins and find best opportunity
+            // (exits settle first; entries resume on the next cycle)
+            let iterations_left =
+                strategy.total_iterations - strategy.iterations_completed - positions.len() as i32;
+            if closed == 0
+                && positions.len() < strategy.max_positions.max(1) as usize
+                && iterations_left > 0
+            {
+                if feed.healthy {
+                    self.handle_entry(&strategy, &prices, &positions).await?;
+                } else {
+                    self.record_skip(&strategy, "feed_unhealthy", &[], None).await;
+                }
             }
         }
 
         Ok(())
     }
 
+    /// True at most once per `ORDER_RECONCILE_INTERVAL_SECS`
+    async fn order_reconcile_due(&self) -> bool {
+        let now = Utc::now();
+        let mut last = self.last_order_reconcile.lock().await;
+        let due = last.is_none_or(|at| (now - at).num_seconds() >= ORDER_RECONCILE_INTERVAL_SECS);
+        if due {
+            *last = Some(now);
+        }
+        due
+    }
+
     async fn check_order_status(&self, strategy: &Strategy, order_id: Uuid) -> anyhow::Result<()> {
         let order = sqlx::query_as::<_, OrderStatusRow>(
             "SELECT order_status, order_type, price_per_unit, quantity FROM orders WHERE id = $1",
@@ -341,7 +578,8 @@ impl AutomationEngine {
                     // Add to matching engine for immediate matching
                     self.matching_engine
                         .add_order(
-                            sell_order_id.to_string(),
+                            sell_order_id,
+                            strategy.user_id,
                             coin_id.to_string(),
                             "sell".to_string(),
                             target_price,
@@ -380,7 +618,7 @@ impl AutomationEngine {
                     let coin_id = strategy.current_coin_id.as_deref().unwrap_or("unknown");
 
                     self.log_action(
-                        strategy.id,
+                        strategy,
                         "sell",
                         coin_id,
                         sell_price,
@@ -432,55 +670,56 @@ impl AutomationEngine {
         Ok(())
     }
 
+    /// Applies stop/target exits to one open position. Returns true if it was closed.
     async fn handle_active_trade(
         &self,
         strategy: &Strategy,
         prices: &HashMap<String, Decimal>,
-        coin_id: &str,
-    ) -> anyhow::Result<()> {
+        position: &Position,
+    ) -> anyhow::Result<bool> {
+        let coin_id = position.coin_id.as_str();
         let current_price = match prices.get(coin_id) {
             Some(p) => *p,
-            None => return Ok(()),
+            None => return Ok(false),
         };
 
-        let entry_price = strategy.entry_price.unwrap_or_default();
+        let entry_price = position.entry_price;
         if entry_price <= Decimal::ZERO {
-            return Ok(());
+            return Ok(false);
         }
 
         // --- TRAILING STOP LOGIC ---
-        let mut high_water_mark = strategy.high_water_mark.unwrap_or(entry_price);
+        let mut high_water_mark = position.high_water_mark;
         
         // Update High Water Mark if current price is higher
-        if current_price > high_water_mark {
+        let high_water_mark_raised = current_price > high_water_mark;
+        if high_water_mark_raised {
             high_water_mark = current_price;
             // Update in DB
-             sqlx::query("UPDATE strategies SET high_water_mark = $2 WHERE id = $1")
-                .bind(strategy.id)
+             sqlx::query("UPDATE strategy_positions SET high_water_mark = $2 WHERE id = $1")
+                .bind(position.id)
                 .bind(high_water_mark)
                 .execute(&self.pool)
                 .await?;
         }
 
+        let params = StrategyParams::for_strategy(strategy);
         let profit_pct = (current_price - entry_price) / entry_price * Decimal::from(100);
-        let target_pct = strategy.profit_percentage;
+        let target_pct = params.profit_percentage;
         
-        // Initialize profit target flags if not set
-        // Note: These fields may not exist in DB yet, so we use unwrap_or(false)
-        let profit_target_1_sold = strategy.profit_target_1_sold.unwrap_or(false);
-        let profit_target_2_sold = strategy.profit_target_2_sold.unwrap_or(false);
-        let profit_target_3_sold = strategy.profit_target_3_sold.unwrap_or(false);
-        let _break_even_activated = strategy.break_even_activated.unwrap_or(false);
+        let ladder = strategy.exit_ladder();
+        let ladder = ladder.as_slice();
+        let targets_sold = position.targets_sold();
         
         // --- ATR TRAILING STOP LOGIC ---
-        // Fetch Klines for ATR (15m candles context)
-        let klines_atr = self.fetch_klines(coin_id, 20).await.unwrap_or_default();
-        let atr = Self::calculate_atr(&klines_atr, 14);
+        // 1m OHLC candles for a true-range ATR
+        let candles_atr = self.fetch_candles(coin_id, 20).await.unwrap_or_default();
+        let atr = Self::calculate_atr(&candles_atr, 14);
 
         let stop_price = if profit_pct > Decimal::from_parts(5, 0, 0, false, 1) { // > 0.5% profit
              // TRAIL: HighWaterMark - 2 * ATR
              if atr > Decimal::ZERO {
-                 let dynamic_stop = high_water_mark - (atr * Decimal::from(2));
+                 let dynamic_stop = high_water_mark - (atr * params.atr_trail_multiplier);
                  // Sanity check: Don't let stop loss be ABOVE current price (impossible but good safety)
                  if dynamic_stop >= current_price {
                      current_price * Decimal::from_str("0.999").unwrap() // Tight close
@@ -491,86 +730,446 @@ impl AutomationEngine {
                  high_water_mark * Decimal::from_str("0.995").unwrap() // Fallback 0.5% trail
              }
         } else {
-             // INITIAL STOP: Entry - 3 * ATR (Give it room to breathe)
-             if atr > Decimal::ZERO {
-                 entry_price - (atr * Decimal::from(3))
-             } else {
-                 entry_price * Decimal::from_str("0.97").unwrap() // Fallback 3% hard stop
-             }
+             Self::initial_stop_price(entry_price, atr, &params)
+        };
+
+        // BREAK-EVEN: once the first tranche is banked the rest can't turn into a loss
+        let stop_price = if position.break_even_activated {
+            stop_price.max(entry_price)
+        } else {
+            stop_price
         };
 
-        let target_price = entry_price * (Decimal::ONE + (target_pct / Decimal::from(100)));
+        // With a ladder, the remainder rides until the highest tranche target at least
+        let final_target_pct = ladder.iter().map(|l| l.target_pct).fold(target_pct, Decimal::max);
+        let target_price = entry_price * (Decimal::ONE + (final_target_pct / Decimal::from(100)));
+
+        let remaining_quantity = position.remaining_quantity(ladder);
+
+        if high_water_mark_raised {
+            self.hub.publish(
+                Channel::Automation,
+                Some(strategy.user_id),
+                "trailing_stop",
+                json!({
+                    "strategy_id": strategy.id,
+                    "position_id": position.id,
+                    "coin_id": coin_id,
+                    "high_water_mark": high_water_mark,
+                    "stop_price": stop_price,
+                    "target_price": target_price,
+                }),
+            );
+        }
 
         info!("🛡️ Strategy {} Monitoring: {} @ {} (Entry: {}, High: {}, Stop: {}, Target: {})", 
             strategy.id, coin_id, current_price, entry_price, high_water_mark, stop_price, target_price);
 
-        let mut should_sell = false;
-        let mut sell_reason = "";
+        let (sell_reason, exit_kind) = if current_price <= stop_price {
+            ("Trailing Stop / Stop Loss Hit", "stop_loss")
+        } else {
+            // SCALED EXIT: sell the next unsold tranche whose target has been reached
+            // (before the final target, so every level is booked as its own tranche)
+            let next_tranche = ladder.iter().enumerate().find(|(i, level)| {
+                !targets_sold[*i]
+                    && current_price >= entry_price * (Decimal::ONE + level.target_pct / Decimal::from(100))
+            });
+            if let Some((index, level)) = next_tranche {
+                let closed = self
+                    .sell_tranche(strategy, position, index, level, current_price, remaining_quantity)
+                    .await?;
+                self.record_decision(
+                    strategy,
+                    Some(position.id),
+                    "partial_exit",
+                    Some(coin_id),
+                    json!({
+                        "reason": "take_profit_level",
+                        "level": index + 1,
+                        "target_pct": level.target_pct,
+                        "fraction": level.fraction,
+                        "price": current_price,
+                        "entry_price": entry_price,
+                        "high_water_mark": high_water_mark,
+                        "atr": atr,
+                        "stop_price": stop_price,
+                        "target_price": target_price,
+                        "closed_position": closed,
+                    }),
+                )
+                .await;
+                return Ok(closed);
+            }
+            if current_price < target_price {
+                return Ok(false);
+            }
+            ("Profit Target Hit", "take_profit")
+        };
+
+        info!(
+            "🚨 Strategy {}: Selling remaining position {} @ {} ({})",
+            strategy.id, coin_id, current_price, sell_reason
+        );
 
-        if current_price <= stop_price {
-            should_sell = true;
-            sell_reason = "Trailing Stop / Stop Loss Hit";
-        } else if current_price >= target_price {
-            should_sell = true;
-            sell_reason = "Profit Target Hit";
+        if remaining_quantity <= Decimal::ZERO {
+            return Ok(false);
         }
 
-        if should_sell {
-            info!(
-                "🚨 Strategy {}: Selling remaining position {} @ {} ({})",
-                strategy.id, coin_id, current_price, sell_reason
-            );
+        let total_amount = current_price * remaining_quantity;
 
-            // Calculate remaining quantity (after partial sells)
-            let total_quantity = strategy.amount / entry_price;
-            let sold_quantity = if profit_target_1_sold { total_quantity * Decimal::from_str("0.25").unwrap() } else { Decimal::ZERO } +
-                              if profit_target_2_sold { total_quantity * Decimal::from_str("0.25").unwrap() } else { Decimal::ZERO } +
-                              if profit_target_3_sold { total_quantity * Decimal::from_str("0.25").unwrap() } else { Decimal::ZERO };
-            let remaining_quantity = total_quantity - sold_quantity;
-            
-            if remaining_quantity > Decimal::ZERO {
-                let order_id = Uuid::new_v4();
-                let total_amount = current_price * remaining_quantity;
+        self.place_market_order(strategy, coin_id, "sell", remaining_quantity, current_price)
+            .await?;
 
-                sqlx::query(
-                    "INSERT INTO orders (id, user_id, coin_id, coin_symbol, order_type, order_mode, quantity, price_per_unit, total_amount, order_status) VALUES ($1, $2, $3, $4, 'sell', 'market', $5, $6, $7, 'completed')"
-                )
-                .bind(order_id)
+        // Per-tranche profit for this sell; the position keeps the running total
+        let profit = Self::tranche_profit(entry_price, current_price, remaining_quantity);
+
+        // Close first: a failing log insert must not leave the sold position open
+        let total_profit = position.realized_profit + profit;
+        self.close_position(position, current_price, total_profit).await?;
+        self.publish_position_closed(strategy, position, exit_kind, current_price, total_profit);
+
+        self.log_action(
+            strategy,
+            "sell",
+            coin_id,
+            current_price,
+            total_amount,
+            Some(profit),
+        )
+        .await?;
+
+        self.record_decision(
+            strategy,
+            Some(position.id),
+            "exit",
+            Some(coin_id),
+            json!({
+                "reason": sell_reason,
+                "price": current_price,
+                "entry_price": entry_price,
+                "high_water_mark": high_water_mark,
+                "profit_pct": profit_pct,
+                "atr": atr,
+                "stop_price": stop_price,
+                "target_price": target_price,
+                "break_even_activated": position.break_even_activated,
+                "quantity": remaining_quantity,
+                "profit": total_profit,
+            }),
+        )
+        .await;
+
+        info!("✅ Strategy {} Iteration Completed. Total Profit: {} (Partial sells + Final sell)", strategy.id, total_profit);
+
+        Ok(true)
+    }
+
+    /// Sells one ladder tranche, flags it and moves the stop to break-even.
+    /// Returns true if this tranche closed the whole position.
+    async fn sell_tranche(
+        &self,
+        strategy: &Strategy,
+        position: &Position,
+        index: usize,
+        level: &TakeProfitLevel,
+        current_price: Decimal,
+        remaining_quantity: Decimal,
+    ) -> anyhow::Result<bool> {
+        let coin_id = position.coin_id.as_str();
+        let quantity = (position.quantity * level.fraction).min(remaining_quantity);
+        if quantity <= Decimal::ZERO {
+            return Ok(false);
+        }
+
+        info!(
+            "🎯 Strategy {}: Take-profit {} hit for {} @ {} (+{}%). Selling {} of {}",
+            strategy.id, index + 1, coin_id, current_price, level.target_pct, quantity, remaining_quantity
+        );
+
+        // Claim the tranche before selling so a failure further down can't sell it twice
+        let profit = Self::tranche_profit(position.entry_price, current_price, quantity);
+        let flag_column = match index {
+            0 => "profit_target_1_sold",
+            1 => "profit_target_2_sold",
+            _ => "profit_target_3_sold",
+        };
+        let claimed = sqlx::query(&format!(
+            "UPDATE strategy_positions SET {0} = TRUE, break_even_activated = TRUE, realized_profit = realized_profit + $2 WHERE id = $1 AND NOT {0}",
+            flag_column
+        ))
+        .bind(position.id)
+        .bind(profit)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        if claimed == 0 {
+            return Ok(false);
+        }
+
+        if let Err(e) = self
+            .place_market_order(strategy, coin_id, "sell", quantity, current_price)
+            .await
+        {
+            sqlx::query(&format!(
+                "UPDATE strategy_positions SET {} = FALSE, break_even_activated = $3, realized_profit = realized_profit - $2 WHERE id = $1",
+                flag_column
+            ))
+            .bind(position.id)
+            .bind(profit)
+            .bind(position.break_even_activated)
+            .execute(&self.pool)
+            .await?;
+            return Err(e);
+        }
+
+        let closed = remaining_quantity - quantity <= Decimal::ZERO;
+        if closed {
+            // Ladder fractions added up to the whole position
+            let total_profit = position.realized_profit + profit;
+            self.close_position(position, current_price, total_profit).await?;
+            self.publish_position_closed(strategy, position, "take_profit", current_price, total_profit);
+            info!("✅ Strategy {} Iteration Completed via take-profit ladder", strategy.id);
+        }
+
+        self.log_action(
+            strategy,
+            "sell_partial",
+            coin_id,
+            current_price,
+            current_price * quantity,
+            Some(profit),
+        )
+        .await?;
+
+        Ok(closed)
+    }
+
+    /// Net profit of selling `quantity` bought at `entry_price`, including both fills' fees
+    fn tranche_profit(entry_price: Decimal, exit_price: Decimal, quantity: Decimal) -> Decimal {
+        (exit_price - entry_price) * quantity
+            - trading_fee(entry_price * quantity)
+            - trading_fee(exit_price * quantity)
+    }
+
+    /// Notional for a new entry according to the strategy's sizing mode (capped at `amount`)
+    async fn size_entry(&self, strategy: &Strategy, params: &StrategyParams, best: &CoinAnalysis) -> anyhow::Result<Decimal> {
+        let mode = SizingMode::from_str(&strategy.sizing_mode).unwrap_or_else(|e| {
+            warn!("⚠️ Strategy {}: {}. Falling back to fixed sizing.", strategy.id, e);
+            SizingMode::Fixed
+        });
+        if mode == SizingMode::Fixed {
+            return Ok(strategy.amount);
+        }
+
+        let candles_atr = self.fetch_candles(&best.coin_id, 20).await.unwrap_or_default();
+        let atr = Self::calculate_atr(&candles_atr, 14);
+
+        let equity = if strategy.is_paper() {
+            // Paper equity: allocated capital plus realized shadow P&L
+            let realized: Option<Decimal> = sqlx::query_scalar(
+                "SELECT SUM(profit) FROM strategy_positions WHERE strategy_id = $1 AND status = 'closed'"
+            )
+            .bind(strategy.id)
+            .fetch_one(&self.pool)
+            .await?;
+            strategy.amount * Decimal::from(strategy.max_positions.max(1)) + realized.unwrap_or_default()
+        } else {
+            sqlx::query_scalar::<_, Decimal>("SELECT balance_inr FROM profiles WHERE id = $1")
                 .bind(strategy.user_id)
-                .bind(coin_id)
-                .bind(coin_id.to_uppercase())
-                .bind(remaining_quantity)
-                .bind(current_price)
-                .bind(total_amount)
-                .execute(&self.pool).await?;
+                .fetch_optional(&self.pool)
+                .await?
+                .unwrap_or_default()
+        };
 
-                // Calculate total profit (including partial sells)
-                let total_sell_amount = total_amount + sold_quantity * current_price; // Approximate partial sell value
-                let profit = total_sell_amount - strategy.amount;
-                
-                self.log_action(
-                    strategy.id,
-                    "sell",
-                    coin_id,
-                    current_price,
-                    total_amount,
-                    Some(profit),
-                )
-                .await?;
+        // One sample per closed position (profit includes its partial exits)
+        let trade_profits: Vec<Decimal> = if mode == SizingMode::Kelly {
+            sqlx::query_scalar(
+                "SELECT profit FROM strategy_positions WHERE strategy_id = $1 AND status = 'closed' AND profit IS NOT NULL ORDER BY closed_at DESC LIMIT 100"
+            )
+            .bind(strategy.id)
+            .fetch_all(&self.pool)
+            .await?
+        } else {
+            Vec::new()
+        };
+
+        let notional = position_notional(
+            mode,
+            &SizingInputs {
+                amount: strategy.amount,
+                equity,
+                price: best.current_price,
+                atr,
+                atr_stop_multiplier: params.atr_stop_multiplier,
+                risk_percent: strategy.risk_percent,
+                target_volatility: strategy.target_volatility,
+                kelly_fraction: strategy.kelly_fraction,
+                trade_profits: &trade_profits,
+            },
+        );
+
+        info!(
+            "⚖️ Strategy {}: {} sizing for {} -> {} (equity: {}, ATR: {})",
+            strategy.id, strategy.sizing_mode, best.coin_id, notional, equity, atr
+        );
+        Ok(notional)
+    }
+
+    /// Persists an audit record; failures are logged, never fatal to the trading cycle
+    async fn record_decision(
+        &self,
+        strategy: &Strategy,
+        position_id: Option<Uuid>,
+        kind: &str,
+        coin_id: Option<&str>,
+        details: serde_json::Value,
+    ) {
+        if let Err(e) = sqlx::query(
+            "INSERT INTO strategy_decisions (strategy_id, position_id, kind, coin_id, details) VALUES ($1, $2, $3, $4, $5)"
+        )
+        .bind(strategy.id)
+        .bind(position_id)
+        .bind(kind)
+        .bind(coin_id)
+        .bind(&details)
+        .execute(&self.pool)
+        .await
+        {
+            warn!("⚠️ Failed to record {} decision for strategy {}: {}", kind, strategy.id, e);
+        }
+
+        // Live viewers get the gist; the full record stays in strategy_decisions
+        self.hub.publish(
+            Channel::Automation,
+            Some(strategy.user_id),
+            "decision",
+            json!({
+                "strategy_id": strategy.id,
+                "position_id": position_id,
+                "kind": kind,
+                "coin_id": coin_id,
+                "reason": details.get("reason"),
+            }),
+        );
+    }
 
-                // Update user balance/holdings via execution service
-                if let Err(e) = execute_order(&self.pool, order_id, current_price).await {
-                    error!("❌ Failed to execute automation sell order {}: {}", order_id, e);
+    /// Records a cycle that ended without an entry, at most once per `SKIP_DECISION_INTERVAL_SECS`
+    async fn record_skip(
+        &self,
+        strategy: &Strategy,
+        reason: &str,
+        candidates: &[CandidateDecision],
+        btc_trend: Option<Decimal>,
+    ) {
+        {
+            let mut last_skips = self.last_skip_recorded.lock().await;
+            let now = Utc::now();
+            if let Some(last) = last_skips.get(&strategy.id) {
+                if (now - *last).num_seconds() < SKIP_DECISION_INTERVAL_SECS {
+                    return;
                 }
+            }
+            last_skips.insert(strategy.id, now);
+        }
 
-                // Reset Strategy (including profit target flags)
-                sqlx::query(
-                    "UPDATE strategies SET current_coin_id = NULL, current_order_id = NULL, entry_price = NULL, high_water_mark = NULL, profit_target_1_sold = NULL, profit_target_2_sold = NULL, profit_target_3_sold = NULL, break_even_activated = NULL, iterations_completed = iterations_completed + 1 WHERE id = $1"
-                )
-                .bind(strategy.id)
-                .execute(&self.pool).await?;
+        self.record_decision(
+            strategy,
+            None,
+            "skip",
+            None,
+            json!({
+                "reason": reason,
+                "btc_trend": btc_trend,
+                "candidates": candidates,
+            }),
+        )
+        .await;
+    }
+
+    async fn cache_analyses(&self, analyses: &[CoinAnalysis]) {
+        let cutoff = Utc::now() - chrono::Duration::minutes(ANALYSIS_CACHE_MAX_AGE_MINUTES);
+        let mut cache = self.latest_analyses.lock().await;
+        cache.retain(|_, a| a.analyzed_at > cutoff);
+        for analysis in analyses {
+            cache.insert(analysis.coin_id.clone(), analysis.clone());
+        }
+    }
+
+    /// Most recent analysis per coin (at most `ANALYSIS_CACHE_MAX_AGE_MINUTES` old)
+    pub async fn latest_analyses(&self) -> Vec<CoinAnalysis> {
+        let cutoff = Utc::now() - chrono::Duration::minutes(ANALYSIS_CACHE_MAX_AGE_MINUTES);
+        let cache = self.latest_analyses.lock().await;
+        cache.values().filter(|a| a.analyzed_at > cutoff).cloned().collect()
+    }
+
+    async fn open_positions(&self, strategy_id: Uuid) -> anyhow::Result<Vec<Position>> {
+        let positions = sqlx::query_as::<_, Position>(
+            "SELECT id, strategy_id, coin_id, quantity, entry_price, cost, high_water_mark, profit_target_1_sold, profit_target_2_sold, profit_target_3_sold, break_even_activated, realized_profit FROM strategy_positions WHERE strategy_id = $1 AND status = 'open' ORDER BY opened_at"
+        )
+        .bind(strategy_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(positions)
+    }
+
+    /// Marks the position closed, counts the iteration and points `current_coin_id` at the
+    /// most recent position still open (if any)
+    async fn close_position(&self, position: &Position, exit_price: Decimal, profit: Decimal) -> anyhow::Result<()> {
+        sqlx::query(
+            "UPDATE strategy_positions SET status = 'closed', exit_price = $2, profit = $3, closed_at = NOW() WHERE id = $1"
+        )
+        .bind(position.id)
+        .bind(exit_price)
+        .bind(profit)
+        .execute(&self.pool).await?;
+
+        sqlx::query(
+            "UPDATE strategies SET current_coin_id = (SELECT coin_id FROM strategy_positions WHERE strategy_id = $1 AND status = 'open' ORDER BY opened_at DESC LIMIT 1), current_order_id = NULL, entry_price = NULL, high_water_mark = NULL, iterations_completed = iterations_completed + 1 WHERE id = $1"
+        )
+        .bind(position.strategy_id)
+        .execute(&self.pool).await?;
+        Ok(())
+    }
+
+    fn publish_position_closed(
+        &self,
+        strategy: &Strategy,
+        position: &Position,
+        reason: &str,
+        exit_price: Decimal,
+        profit: Decimal,
+    ) {
+        self.events.publish(DomainEvent::PositionClosed {
+            strategy_id: strategy.id,
+            user_id: strategy.user_id,
+            position_id: position.id,
+            coin_id: position.coin_id.clone(),
+            reason: reason.to_string(),
+            exit_price,
+            profit,
+        });
+    }
+
+    /// Checks the strategy's exposure caps for opening `notional` worth of `coin_id`
+    fn check_risk_caps(strategy: &Strategy, open: &[Position], coin_id: &str, notional: Decimal) -> Result<(), String> {
+        let coin_exposure: Decimal = open.iter().filter(|p| p.coin_id == coin_id).map(|p| p.cost).sum();
+        let max_coin_exposure = strategy.max_coin_exposure.unwrap_or(strategy.amount);
+        if coin_exposure + notional > max_coin_exposure {
+            return Err(format!("coin exposure {} + {} exceeds cap {}", coin_exposure, notional, max_coin_exposure));
+        }
+
+        let total_exposure: Decimal = open.iter().map(|p| p.cost).sum();
+        let max_total_exposure = strategy
+            .max_total_exposure
+            .unwrap_or(strategy.amount * Decimal::from(strategy.max_positions.max(1)));
+        if total_exposure + notional > max_total_exposure {
+            return Err(format!("total exposure {} + {} exceeds cap {}", total_exposure, notional, max_total_exposure));
+        }
 
-                info!("✅ Strategy {} Iteration Completed. Total Profit: {} (Partial sells + Final sell)", strategy.id, profit);
+        if let Some(group) = correlation_group(coin_id) {
+            let in_group = open.iter().filter(|p| correlation_group(&p.coin_id) == Some(group)).count();
+            if in_group >= strategy.max_correlated_positions.max(0) as usize {
+                return Err(format!("already holding {} {} coins", in_group, group));
             }
         }
 
@@ -581,6 +1180,7 @@ impl AutomationEngine {
         &self,
         strategy: &Strategy,
         _prices: &HashMap<String, Decimal>,
+        open_positions: &[Position],
     ) -> anyhow::Result<()> {
         // ANALYZE TOP LIQUID COINS (Top 30 by Volume)
         info!(
@@ -595,40 +1195,18 @@ impl AutomationEngine {
             "btcup", "btcdown", "ethup", "ethdown", "bnbup", "bnbdown", "xrpup", "xrpdown", "linkup", "linkdown", "ltcup", "ltcdown"
         ];
 
-        let filtered_coins: HashMap<String, crate::services::matching_engine::TickerData> = top_coins
-            .into_iter()
-            .filter(|(coin_id, data)| {
-                 if blacklisted_coins.contains(&coin_id.as_str()) { return false; }
-                 
-                 // PRE-FILTER 1: Liquidity Check (> 1M USDT 24h Volume)
-                 if data.volume_quote < Decimal::from(1_000_000) { return false; }
-
-                 // PRE-FILTER 2: Momentum Check (FIXED - Favor Oversold)
-                 // We want coins that are OVERSOLD (down), not overbought (up)
-                 // Oversold coins (down 1-8%) are good entry opportunities
-                 let open = data.open_price;
-                 let close = data.price;
-                 if open <= Decimal::ZERO { return false; }
-                 
-                 let change_pct = (close - open) / open * Decimal::from(100);
-                 // CRITICAL FIX: Only allow coins that are DOWN (oversold), not UP (overbought)
-                 // - Moving down moderately (-1% to -8%) - oversold opportunity (GOOD)
-                 // - Reject coins moving up (> 0%) - they're already high (BAD)
-                 // - Reject sideways coins (< 1% change) - no opportunity
-                 // - Reject extreme dumps (< -8%) - might be crashing
-                 if change_pct > Decimal::ZERO {
-                      return false; // REJECT coins going UP - they're already high!
-                 }
-                 if change_pct.abs() < Decimal::from(1) {
-                      return false; // Skip boring side-ways coins
-                 }
-                 if change_pct < Decimal::from_str("-8").unwrap() {
-                      return false; // Skip extreme dumps (might be crashing)
-                 }
-                 
-                 true
-            })
-            .collect();
+        // Every coin considered this cycle and why it was dropped (for strategy_decisions)
+        let mut candidates: Vec<CandidateDecision> = Vec::new();
+
+        let mut filtered_coins: HashMap<String, crate::services::matching_engine::TickerData> = HashMap::new();
+        for (coin_id, data) in top_coins {
+            match Self::prefilter_rejection(strategy, open_positions, &coin_id, &data, &blacklisted_coins) {
+                Some(reason) => candidates.push(CandidateDecision::rejected(&coin_id, "prefilter", reason)),
+                None => {
+                    filtered_coins.insert(coin_id, data);
+                }
+            }
+        }
 
         info!("🛡️ Strategy {}: Optimized candidate list to {} coins (from 30)", strategy.id, filtered_coins.len());
 
@@ -637,6 +1215,7 @@ impl AutomationEngine {
                 "⚠️ Strategy {}: No liquid coins found after filtering. Waiting for market data...",
                 strategy.id
             );
+            self.record_skip(strategy, "no_prefilter_candidates", &candidates, None).await;
             return Ok(());
         }
 
@@ -645,28 +1224,45 @@ impl AutomationEngine {
         if btc_trend < Decimal::from_str("-0.01").unwrap() {
             // Market Dump Warning! Abort/Cautious
             warn!("⚠️ Global Market Dump Detected (BTC Down). Pausing entries.");
+            self.record_skip(strategy, "btc_dump", &candidates, Some(btc_trend)).await;
             return Ok(());
         }
 
+        let params = StrategyParams::for_strategy(strategy);
+
         // Parallel Analysis with Concurrency Limit (10 concurrent requests)
         let analyses = stream::iter(filtered_coins)
             .map(|(coin_id, ticker_data)| {
                 let self_ref = &self;
-                async move { self_ref.analyze_coin(&coin_id, ticker_data.price, ticker_data.open_price, btc_trend).await }
+                let params_ref = &params;
+                async move { self_ref.analyze_coin(&coin_id, ticker_data.price, ticker_data.open_price, btc_trend, params_ref).await }
             })
             .buffer_unordered(10) // Limit concurrency to avoid IP bans
             .filter_map(|res| async { res.ok() })
             .collect::<Vec<_>>()
             .await;
 
+        self.cache_analyses(&analyses).await;
+
         if analyses.is_empty() {
             warn!(
                 "⚠️ Strategy {}: Analysis failed for all candidates. Skipping cycle.",
                 strategy.id
             );
+            self.record_skip(strategy, "analysis_failed", &candidates, Some(btc_trend)).await;
             return Ok(());
         }
 
+        for analysis in &analyses {
+            candidates.push(CandidateDecision {
+                coin_id: analysis.coin_id.clone(),
+                stage: "analysis",
+                rejection: Self::entry_rejection(analysis, &params).map(str::to_string),
+                entry_score: Some(analysis.entry_score),
+                rsi: Some(analysis.rsi),
+            });
+        }
+
         // ENHANCED: Multi-indicator entry system - buy LOW, sell HIGH
         // Use entry_score (0-1) to filter and rank opportunities
         let threshold_percent = strategy.profit_percentage;
@@ -674,33 +1270,7 @@ impl AutomationEngine {
         // FILTER: Require minimum entry score of 0.7 (70% confidence) AND volume confirmation
         let best_coin = analyses
             .iter()
-            .filter(|a| {
-                // CRITICAL: Don't buy overbought coins (RSI > 70) - they're at high prices
-                if a.rsi > Decimal::from(70) {
-                    return false; // Hard reject overbought
-                }
-                // CRITICAL: Only buy if price is BELOW or NEAR support level (oversold/undervalued)
-                // If current price is ABOVE support, it's not a good entry (would buy high)
-                if a.support_level > Decimal::ZERO && a.current_price > a.support_level * Decimal::from_str("1.01").unwrap() {
-                    return false; // Price is more than 1% above support - not oversold enough, would buy high
-                }
-                // Require minimum entry score (multi-indicator confirmation)
-                if a.entry_score < Decimal::from_str("0.7").unwrap() {
-                    return false; // Not enough confirmation
-                }
-                // Require volume confirmation (volume spike > 120%)
-                if a.volume_ratio < Decimal::from_str("1.2").unwrap() {
-                    return false; // Weak volume = weak signal
-                }
-                // Look for coins with positive potential (not predicted to crash)
-                if a.price_change_percent < Decimal::from_str("-5").unwrap() {
-                    return false; // Predicted to crash
-                }
-                // CRITICAL: Only buy if current price is BELOW predicted price (undervalued)
-                // This ensures we buy LOW, not HIGH
-                // If current_price >= predicted_price, we'd be buying at or above fair value (bad)
-                a.current_price < a.predicted_price_10m
-            })
+            .filter(|a| Self::entry_rejection(a, &params).is_none())
             .max_by(|a, b| {
                 // Prioritize by:
                 // 1. Lower RSI (more oversold = better entry) - MOST IMPORTANT
@@ -749,55 +1319,85 @@ impl AutomationEngine {
                 return Ok(());
             }
 
+            // Size the entry (fixed notional unless the strategy uses a risk-based mode)
+            let notional = self.size_entry(strategy, &params, best).await?;
+            if notional < Decimal::from(MIN_ORDER_NOTIONAL) {
+                info!(
+                    "📉 Strategy {}: {} sizing gave {} for {} (below minimum). Skipping entry.",
+                    strategy.id, strategy.sizing_mode, notional, best.coin_id
+                );
+                self.record_skip(strategy, "below_min_notional", &candidates, Some(btc_trend)).await;
+                return Ok(());
+            }
+            // The prefilter checked the caps against the configured amount; the sized entry may be larger
+            if let Err(reason) = Self::check_risk_caps(strategy, open_positions, &best.coin_id, notional) {
+                info!("🧱 Strategy {}: Skipping {} entry of {} ({})", strategy.id, best.coin_id, notional, reason);
+                self.record_skip(strategy, "risk_cap", &candidates, Some(btc_trend)).await;
+                return Ok(());
+            }
+
             // Place MARKET BUY order immediately
-            let quantity = strategy.amount / best.current_price;
-            let buy_order_id = Uuid::new_v4();
+            let quantity = notional / best.current_price;
 
             info!(
-                "💸 Strategy {}: Placing MARKET BUY for {} @ {} (Quantity: {})",
-                strategy.id, best.coin_id, best.current_price, quantity
+                "💸 Strategy {}: Placing {} MARKET BUY for {} @ {} (Quantity: {})",
+                strategy.id, strategy.mode, best.coin_id, best.current_price, quantity
             );
 
-            // Place market order (will execute immediately)
-            sqlx::query(
-                "INSERT INTO orders (id, user_id, coin_id, coin_symbol, order_type, order_mode, quantity, price_per_unit, total_amount, order_status) VALUES ($1, $2, $3, $4, 'buy', 'market', $5, $6, $7, 'completed')"
-            )
-            .bind(buy_order_id)
-            .bind(strategy.user_id)
-            .bind(&best.coin_id)
-            .bind(best.coin_id.to_uppercase())
-            .bind(quantity)
-            .bind(best.current_price)
-            .bind(strategy.amount)
-            .execute(&self.pool).await?;
+            self.place_market_order(strategy, &best.coin_id, "buy", quantity, best.current_price)
+                .await?;
 
             // Log the buy action
             self.log_action(
-                strategy.id,
+                strategy,
                 "buy",
                 &best.coin_id,
                 best.current_price,
-                strategy.amount,
+                notional,
                 None,
             )
             .await?;
 
-            // Update user balance and holdings (simulate immediate execution)
-            if let Err(e) = execute_order(&self.pool, buy_order_id, best.current_price).await {
-                error!("❌ Failed to execute automation buy order {}: {}", buy_order_id, e);
-                // Continue anyway, but log potential consistency issue
-            }
-
             // --- TRAILING STOP SETUP (Active Monitoring) ---
-            // Update strategy to track this active trade with NULL order_id (No fixed sell order)
-            // Initialize High Water Mark = Entry Price
-            
-            sqlx::query(
-                "UPDATE strategies SET current_coin_id = $2, current_order_id = NULL, entry_price = $3, high_water_mark = $3 WHERE id = $1"
+            // Open a position row with its own trailing state (High Water Mark = Entry Price)
+            let position_id: Uuid = sqlx::query_scalar(
+                "INSERT INTO strategy_positions (strategy_id, coin_id, quantity, entry_price, cost, high_water_mark) VALUES ($1, $2, $3, $4, $5, $4) RETURNING id"
             )
             .bind(strategy.id)
             .bind(&best.coin_id)
+            .bind(quantity)
             .bind(best.current_price)
+            .bind(notional)
+            .fetch_one(&self.pool).await?;
+
+            // Stop and target the position starts with
+            let candles_atr = self.fetch_candles(&best.coin_id, 20).await.unwrap_or_default();
+            let atr = Self::calculate_atr(&candles_atr, 14);
+            self.record_decision(
+                strategy,
+                Some(position_id),
+                "entry",
+                Some(&best.coin_id),
+                json!({
+                    "btc_trend": btc_trend,
+                    "candidates": candidates,
+                    "chosen": best,
+                    "sizing_mode": strategy.sizing_mode,
+                    "notional": notional,
+                    "quantity": quantity,
+                    "atr": atr,
+                    "stop_price": Self::initial_stop_price(best.current_price, atr, &params),
+                    "target_price": best.current_price * (Decimal::ONE + params.profit_percentage / Decimal::from(100)),
+                }),
+            )
+            .await;
+
+            // current_coin_id shows the latest entry; no fixed sell order is tracked
+            sqlx::query(
+                "UPDATE strategies SET current_coin_id = $2, current_order_id = NULL WHERE id = $1"
+            )
+            .bind(strategy.id)
+            .bind(&best.coin_id)
             .execute(&self.pool).await?;
 
             info!("✅ Strategy {} Entered Active Monitoring for {} @ {}", strategy.id, best.coin_id, best.current_price);
@@ -807,6 +1407,7 @@ impl AutomationEngine {
                 "⏳ Strategy {}: No coins meet {}% threshold. Waiting for next cycle...",
                 strategy.id, threshold_percent
             );
+            self.record_skip(strategy, "no_candidate_passed", &candidates, Some(btc_trend)).await;
         }
 
         Ok(())
@@ -818,6 +1419,7 @@ impl AutomationEngine {
         current_price: Decimal,
         open_price: Decimal,
         btc_trend_score: Decimal, // Passed from handle_entry
+        params: &StrategyParams,
     ) -> anyhow::Result<CoinAnalysis> {
         // Fetch order book data
         let order_book = self.fetch_order_book(coin_id).await?;
@@ -832,7 +1434,8 @@ impl AutomationEngine {
         };
 
         // --- NEW: K-Line & Technical Indicators Analysis ---
-        let klines = self.fetch_klines(coin_id, 30).await.unwrap_or_default();
+        // 50 closes so the MACD signal line (26 + 9 - 1) has warmed up
+        let klines = self.fetch_klines(coin_id, 50).await.unwrap_or_default();
         let rsi = Self::calculate_rsi(&klines, 14);
         
         // Calculate MACD
@@ -846,9 +1449,9 @@ impl AutomationEngine {
         
         // Filter: Don't buy if RSI > 70 (Overbought) - STRONG penalty
         // Boost: Buy if RSI < 30 (Oversold Bounce candidate) - STRONG boost
-        let rsi_bias = if rsi > Decimal::from(70) {
+        let rsi_bias = if rsi > params.rsi_overbought {
             Decimal::from_str("-0.10").unwrap() // -10% penalty (strong rejection of overbought)
-        } else if rsi < Decimal::from(30) {
+        } else if rsi < params.rsi_oversold {
              Decimal::from_str("0.05").unwrap() // +5% boost (strong preference for oversold)
         } else if rsi < Decimal::from(45) {
              Decimal::from_str("0.02").unwrap() // +2% boost for slightly oversold
@@ -898,15 +1501,11 @@ impl AutomationEngine {
         // Identify Walls (Liquidity > 5x average)
         let mut total_ask_qty = Decimal::ZERO;
         for ask in order_book.asks.iter().take(20) {
-             let qty: Decimal = ask[1].parse().unwrap_or_default();
-             total_ask_qty += qty;
+             total_ask_qty += ask[1];
         }
         let avg_ask_qty = total_ask_qty / Decimal::from(20);
 
-        for ask in order_book.asks.iter().take(10) {
-            let price: Decimal = ask[0].parse().unwrap_or_default();
-            let qty: Decimal = ask[1].parse().unwrap_or_default();
-            
+        for &[price, qty] in order_book.asks.iter().take(10) {
             // Check for Wall
             if qty > avg_ask_qty * Decimal::from(5) {
                 // Large Sell Wall near current price? Bad.
@@ -921,9 +1520,7 @@ impl AutomationEngine {
              Decimal::ZERO
         };
 
-        for bid in order_book.bids.iter().take(10) {
-            let price: Decimal = bid[0].parse().unwrap_or_default();
-            let qty: Decimal = bid[1].parse().unwrap_or_default();
+        for &[price, qty] in order_book.bids.iter().take(10) {
             buy_pressure += price * qty;
         }
 
@@ -1022,53 +1619,191 @@ impl AutomationEngine {
             Decimal::ZERO
         };
         
+        let entry_score = Self::score_entry(
+            &EntrySignals {
+                current_price,
+                rsi,
+                macd,
+                macd_signal,
+                macd_histogram,
+                bb_middle,
+                bb_lower,
+                volume_ratio,
+                support_level,
+            },
+            params,
+        );
+        
+        if price_change_percent > Decimal::from(1) || entry_score > Decimal::from_str("0.7").unwrap() {
+            info!("🔬 Analysis {}: RSI: {}, MACD: {:.4}, BB: {:.2}, Vol: {:.2}x, Support: {:.2}, Entry Score: {:.2}, Total%: {}", 
+                coin_id, rsi, macd, bb_lower, volume_ratio, support_level, entry_score, price_change_percent);
+        }
+
+        Ok(CoinAnalysis {
+            coin_id: coin_id.to_string(),
+            current_price,
+            predicted_price_10m,
+            price_change_percent,
+            rsi,
+            macd,
+            macd_signal,
+            macd_histogram,
+            bb_middle,
+            bb_lower,
+            support_level,
+            resistance_level,
+            vwap,
+            vwap_bias,
+            resistance_wall_detected,
+            volume_ratio,
+            entry_score,
+            buy_pressure,
+            sell_pressure,
+            analyzed_at: Utc::now(),
+        })
+    }
+
+    /// Why a top-volume coin is dropped before analysis, or `None` if it goes on to be analysed
+    fn prefilter_rejection(
+        strategy: &Strategy,
+        open_positions: &[Position],
+        coin_id: &str,
+        data: &crate::services::matching_engine::TickerData,
+        blacklisted_coins: &[&str],
+    ) -> Option<String> {
+        if blacklisted_coins.contains(&coin_id) {
+            return Some("blacklisted".to_string());
+        }
+
+        // RISK CAPS: per-coin, total and correlated exposure
+        if let Err(reason) = Self::check_risk_caps(strategy, open_positions, coin_id, strategy.amount) {
+            info!("🧱 Strategy {}: Skipping {} ({})", strategy.id, coin_id, reason);
+            return Some(reason);
+        }
+
+        // PRE-FILTER 1: Liquidity Check (> 1M USDT 24h Volume)
+        if data.volume_quote < Decimal::from(1_000_000) {
+            return Some("low_liquidity".to_string());
+        }
+
+        // PRE-FILTER 2: Momentum Check (FIXED - Favor Oversold)
+        // We want coins that are OVERSOLD (down), not overbought (up)
+        // Oversold coins (down 1-8%) are good entry opportunities
+        let open = data.open_price;
+        let close = data.price;
+        if open <= Decimal::ZERO {
+            return Some("no_open_price".to_string());
+        }
+
+        let change_pct = (close - open) / open * Decimal::from(100);
+        // CRITICAL FIX: Only allow coins that are DOWN (oversold), not UP (overbought)
+        // - Moving down moderately (-1% to -8%) - oversold opportunity (GOOD)
+        // - Reject coins moving up (> 0%) - they're already high (BAD)
+        // - Reject sideways coins (< 1% change) - no opportunity
+        // - Reject extreme dumps (< -8%) - might be crashing
+        if change_pct > Decimal::ZERO {
+            return Some("rising_24h".to_string()); // REJECT coins going UP - they're already high!
+        }
+        if change_pct.abs() < Decimal::from(1) {
+            return Some("sideways_24h".to_string()); // Skip boring side-ways coins
+        }
+        if change_pct < Decimal::from_str("-8").unwrap() {
+            return Some("dumping_24h".to_string()); // Skip extreme dumps (might be crashing)
+        }
+
+        None
+    }
+
+    /// Entry - 3 * ATR (give it room to breathe), or a 3% hard stop without ATR
+    fn initial_stop_price(entry_price: Decimal, atr: Decimal, params: &StrategyParams) -> Decimal {
+        if atr > Decimal::ZERO {
+            entry_price - (atr * params.atr_stop_multiplier)
+        } else {
+            entry_price * Decimal::from_str("0.97").unwrap() // Fallback 3% hard stop
+        }
+    }
+
+    /// Why `a` fails the entry filters, or `None` if it's a buy candidate
+    pub fn entry_rejection(a: &CoinAnalysis, params: &StrategyParams) -> Option<&'static str> {
+        // CRITICAL: Don't buy overbought coins (RSI > 70) - they're at high prices
+        if a.rsi > params.rsi_overbought {
+            return Some("rsi_overbought"); // Hard reject overbought
+        }
+        // CRITICAL: Only buy if price is BELOW or NEAR support level (oversold/undervalued)
+        // If current price is ABOVE support, it's not a good entry (would buy high)
+        if a.support_level > Decimal::ZERO && a.current_price > a.support_level * Decimal::from_str("1.01").unwrap() {
+            return Some("above_support"); // Price is more than 1% above support - not oversold enough, would buy high
+        }
+        // Require minimum entry score (multi-indicator confirmation)
+        if a.entry_score < Decimal::from_str("0.7").unwrap() {
+            return Some("low_entry_score"); // Not enough confirmation
+        }
+        // Require volume confirmation (volume spike > 120%)
+        if a.volume_ratio < params.min_volume_ratio {
+            return Some("weak_volume"); // Weak volume = weak signal
+        }
+        // Look for coins with positive potential (not predicted to crash)
+        if a.price_change_percent < Decimal::from_str("-5").unwrap() {
+            return Some("predicted_crash"); // Predicted to crash
+        }
+        // CRITICAL: Only buy if current price is BELOW predicted price (undervalued)
+        // This ensures we buy LOW, not HIGH
+        // If current_price >= predicted_price, we'd be buying at or above fair value (bad)
+        if a.current_price >= a.predicted_price_10m {
+            return Some("above_prediction");
+        }
+        None
+    }
+
+    /// Weighted multi-indicator entry confidence (0-1), shared with the backtester
+    pub(crate) fn score_entry(s: &EntrySignals, params: &StrategyParams) -> Decimal {
         // --- MULTI-INDICATOR ENTRY SCORING SYSTEM ---
         // Score each indicator (0-1 scale), then weight them
-        let rsi_score = if rsi < Decimal::from(30) {
+        let rsi_score = if s.rsi < params.rsi_oversold {
             Decimal::ONE // Perfect oversold
-        } else if rsi < Decimal::from(45) {
+        } else if s.rsi < Decimal::from(45) {
             Decimal::from_str("0.7").unwrap() // Good oversold
-        } else if rsi < Decimal::from(55) {
+        } else if s.rsi < Decimal::from(55) {
             Decimal::from_str("0.5").unwrap() // Neutral
-        } else if rsi < Decimal::from(70) {
+        } else if s.rsi < params.rsi_overbought {
             Decimal::from_str("0.3").unwrap() // Overbought warning
         } else {
             Decimal::ZERO // Reject overbought
         };
-        
+    
         // MACD Score (bullish crossover = good)
-        let macd_score = if macd > macd_signal && macd_histogram > Decimal::ZERO {
+        let macd_score = if s.macd > s.macd_signal && s.macd_histogram > Decimal::ZERO {
             Decimal::ONE // Bullish crossover
-        } else if macd > macd_signal {
+        } else if s.macd > s.macd_signal {
             Decimal::from_str("0.6").unwrap() // Above signal but histogram negative
         } else {
             Decimal::from_str("0.2").unwrap() // Bearish
         };
-        
+    
         // Bollinger Bands Score (price near lower band = oversold = good)
-        let bb_score = if bb_lower > Decimal::ZERO && current_price <= bb_lower * Decimal::from_str("1.01").unwrap() {
+        let bb_score = if s.bb_lower > Decimal::ZERO && s.current_price <= s.bb_lower * Decimal::from_str("1.01").unwrap() {
             Decimal::ONE // Touching lower band (oversold)
-        } else if current_price < bb_middle {
+        } else if s.current_price < s.bb_middle {
             Decimal::from_str("0.6").unwrap() // Below middle (good)
         } else {
             Decimal::from_str("0.3").unwrap() // Above middle (less ideal)
         };
-        
+    
         // Volume Score (volume spike = confirmation)
-        let volume_score = if volume_ratio > Decimal::from_str("1.5").unwrap() {
+        let volume_score = if s.volume_ratio > Decimal::from_str("1.5").unwrap() {
             Decimal::ONE // Strong volume spike (>150%)
-        } else if volume_ratio > Decimal::from_str("1.2").unwrap() {
+        } else if s.volume_ratio > Decimal::from_str("1.2").unwrap() {
             Decimal::from_str("0.7").unwrap() // Good volume (>120%)
-        } else if volume_ratio > Decimal::ONE {
+        } else if s.volume_ratio > Decimal::ONE {
             Decimal::from_str("0.5").unwrap() // Average volume
         } else {
             Decimal::from_str("0.2").unwrap() // Low volume (weak)
         };
-        
+    
         // Support Level Score (near support = good entry)
         let mut support_score = Decimal::ZERO;
-        if support_level > Decimal::ZERO {
-            let distance_to_support = ((current_price - support_level) / support_level).abs();
+        if s.support_level > Decimal::ZERO {
+            let distance_to_support = ((s.current_price - s.support_level) / s.support_level).abs();
             if distance_to_support < Decimal::from_str("0.01").unwrap() {
                 support_score = Decimal::ONE; // Within 1% of support (perfect)
             } else if distance_to_support < Decimal::from_str("0.02").unwrap() {
@@ -1079,220 +1814,76 @@ impl AutomationEngine {
                 support_score = Decimal::from_str("0.1").unwrap(); // Far from support (poor)
             }
         }
-        
+    
         // Calculate weighted entry score
-        let entry_score = (rsi_score * Decimal::from_str("0.25").unwrap()) +
-                         (macd_score * Decimal::from_str("0.20").unwrap()) +
-                         (bb_score * Decimal::from_str("0.15").unwrap()) +
-                         (volume_score * Decimal::from_str("0.20").unwrap()) +
-                         (support_score * Decimal::from_str("0.20").unwrap());
-        
-        if price_change_percent > Decimal::from(1) || entry_score > Decimal::from_str("0.7").unwrap() {
-            info!("🔬 Analysis {}: RSI: {}, MACD: {:.4}, BB: {:.2}, Vol: {:.2}x, Support: {:.2}, Entry Score: {:.2}, Total%: {}", 
-                coin_id, rsi, macd, bb_lower, volume_ratio, support_level, entry_score, price_change_percent);
-        }
-
-        Ok(CoinAnalysis {
-            coin_id: coin_id.to_string(),
-            current_price,
-            predicted_price_10m,
-            price_change_percent,
-            rsi,
-            macd,
-            macd_signal,
-            macd_histogram,
-            support_level,
-            resistance_level,
-            volume_ratio,
-            entry_score,
-            buy_pressure,
-            sell_pressure,
-        })
+        (rsi_score * Decimal::from_str("0.25").unwrap()) +
+            (macd_score * Decimal::from_str("0.20").unwrap()) +
+            (bb_score * Decimal::from_str("0.15").unwrap()) +
+            (volume_score * Decimal::from_str("0.20").unwrap()) +
+            (support_score * Decimal::from_str("0.20").unwrap())
     }
 
-    async fn fetch_order_book(&self, coin_id: &str) -> anyhow::Result<BinanceOrderBookResponse> {
-        let symbol = format!("{}USDT", coin_id.to_uppercase());
-        let url = format!(
-            "https://api.binance.com/api/v3/depth?symbol={}&limit=20",
-            symbol
-        );
-
-        let response = self.http_client.get(&url).send().await?;
-
-        if !response.status().is_success() {
-            return Err(anyhow::anyhow!(
-                "Failed to fetch order book for {}",
-                coin_id
-            ));
+    /// Top 20 levels from the local depth cache, falling back to REST while it syncs
+    async fn fetch_order_book(&self, coin_id: &str) -> anyhow::Result<DepthView> {
+        if let Some(book) = self.matching_engine.depth().book(coin_id, 20).await {
+            return Ok(book);
         }
 
-        let order_book: BinanceOrderBookResponse = response.json().await?;
-        Ok(order_book)
+        let snapshot = self.binance.depth(coin_id, 20).await?;
+        Ok(DepthView {
+            coin_id: coin_id.to_string(),
+            last_update_id: snapshot.last_update_id,
+            bids: snapshot.bids,
+            asks: snapshot.asks,
+        })
     }
 
     async fn fetch_recent_trades(&self, coin_id: &str) -> anyhow::Result<Vec<BinanceTrade>> {
-        let symbol = format!("{}USDT", coin_id.to_uppercase());
-        let url = format!(
-            "https://api.binance.com/api/v3/trades?symbol={}&limit=50",
-            symbol
-        );
-
-        let response = self.http_client.get(&url).send().await?;
-
-        if !response.status().is_success() {
-            return Err(anyhow::anyhow!(
-                "Failed to fetch trades for {}",
-                coin_id
-            ));
-        }
-
-        let trades: Vec<BinanceTrade> = response.json().await?;
-        Ok(trades)
+        self.binance.recent_trades(coin_id, 50).await
     }
 
-    async fn fetch_klines(&self, coin_id: &str, limit: usize) -> anyhow::Result<Vec<Decimal>> {
-        let symbol = format!("{}USDT", coin_id.to_uppercase());
-        // Fetch 1m candles
-        let url = format!(
-            "https://api.binance.com/api/v3/klines?symbol={}&interval=1m&limit={}",
-            symbol, limit
-        );
-
-        let response = self.http_client.get(&url).send().await?;
-        
-        if !response.status().is_success() {
-             return Ok(Vec::new()); // Fail gracefully
+    /// 1m OHLCV candles (oldest first) from the live candle aggregator, falling back to
+    /// Binance REST while the in-memory buffer is still warming up
+    async fn fetch_candles(&self, coin_id: &str, limit: usize) -> anyhow::Result<Vec<Candle>> {
+        let candles = self
+            .matching_engine
+            .candles()
+            .recent(coin_id, Interval::M1, limit, true)
+            .await;
+        if candles.len() >= limit {
+            return Ok(candles);
         }
 
-        // Binance returns array of arrays. We need to parse custom logic or use generic Value
-        let raw_klines: Vec<serde_json::Value> = response.json().await?;
-        
-        let closes: Vec<Decimal> = raw_klines.iter().filter_map(|k| {
-            // Index 4 is Close Price
-            k.get(4).and_then(|v| v.as_str()).and_then(|s| s.parse::<Decimal>().ok())
-        }).collect();
-
-        Ok(closes)
+        self.binance.klines(coin_id, "1m", limit).await
     }
 
-    fn calculate_rsi(prices: &[Decimal], period: usize) -> Decimal {
-        if prices.len() <= period {
-            return Decimal::from(50); // Not enough data, return neutral
-        }
-
-        let mut gains = Decimal::ZERO;
-        let mut losses = Decimal::ZERO;
-
-        // Calculate initial Average Gain/Loss
-        for i in 1..=period {
-            let change = prices[i] - prices[i-1];
-            if change > Decimal::ZERO {
-                gains += change;
-            } else {
-                losses += change.abs();
-            }
-        }
-
-        let mut avg_gain = gains / Decimal::from(period);
-        let mut avg_loss = losses / Decimal::from(period);
-
-        // Calculate smoothed averages for remainder
-        for i in (period + 1)..prices.len() {
-             let change = prices[i] - prices[i-1];
-             let (gain, loss) = if change > Decimal::ZERO {
-                 (change, Decimal::ZERO)
-             } else {
-                 (Decimal::ZERO, change.abs())
-             };
-             
-             avg_gain = (avg_gain * Decimal::from(period - 1) + gain) / Decimal::from(period);
-             avg_loss = (avg_loss * Decimal::from(period - 1) + loss) / Decimal::from(period);
-        }
-
-        if avg_loss == Decimal::ZERO {
-            return Decimal::from(100);
-        }
-
-        let rs = avg_gain / avg_loss;
-        let rsi = Decimal::from(100) - (Decimal::from(100) / (Decimal::ONE + rs));
-        
-        rsi
+    async fn fetch_klines(&self, coin_id: &str, limit: usize) -> anyhow::Result<Vec<Decimal>> {
+        let candles = self.fetch_candles(coin_id, limit).await?;
+        Ok(candles.into_iter().map(|c| c.close).collect())
     }
 
-    fn calculate_atr(prices: &[Decimal], period: usize) -> Decimal {
-        if prices.len() <= period {
-            return Decimal::ZERO;
-        }
-
-        let mut tr_sum = Decimal::ZERO;
-
-        // Simple ATR (Average True Range) approximation using just High-Low (since we only have Close here really, but let's approximate with Close volatility)
-        // Wait, fetch_klines only returns CLOSES. 
-        // True Range needs High/Low/Close.
-        // As a fallback for "Close-only" data: We used Absolute Change.
-        
-        for i in 1..prices.len() {
-             let change = (prices[i] - prices[i-1]).abs();
-             tr_sum += change;
-        }
-        
-        let avg_tr = tr_sum / Decimal::from(prices.len() - 1);
-        avg_tr
+    /// Wilder-smoothed RSI (neutral 50 until there is enough data)
+    pub(crate) fn calculate_rsi(prices: &[Decimal], period: usize) -> Decimal {
+        indicators::latest(&indicators::rsi(prices, period)).unwrap_or(Decimal::from(50))
     }
 
-    // Calculate EMA (Exponential Moving Average)
-    fn calculate_ema(prices: &[Decimal], period: usize) -> Decimal {
-        if prices.is_empty() {
-            return Decimal::ZERO;
-        }
-        if prices.len() == 1 {
-            return prices[0];
-        }
-
-        let multiplier = Decimal::from(2) / Decimal::from(period + 1);
-        let mut ema = prices[0];
-
-        for i in 1..prices.len() {
-            ema = (prices[i] - ema) * multiplier + ema;
-        }
-
-        ema
+    /// Wilder ATR from OHLC candles
+    pub(crate) fn calculate_atr(candles: &[Candle], period: usize) -> Decimal {
+        indicators::latest(&indicators::atr(candles, period)).unwrap_or(Decimal::ZERO)
     }
 
-    // Calculate MACD (Moving Average Convergence Divergence)
-    fn calculate_macd(prices: &[Decimal]) -> (Decimal, Decimal, Decimal) {
-        // MACD = EMA(12) - EMA(26)
-        // Signal = EMA(9) of MACD
-        // Histogram = MACD - Signal
-        
-        if prices.len() < 26 {
-            return (Decimal::ZERO, Decimal::ZERO, Decimal::ZERO);
-        }
-
-        // Calculate EMA(12) and EMA(26)
-        let ema12 = Self::calculate_ema(prices, 12);
-        let ema26 = Self::calculate_ema(prices, 26);
-        let macd_line = ema12 - ema26;
-
-        // For signal line, we need MACD values over time, but we'll approximate
-        // by using a shorter period EMA of recent price changes
-        let recent_prices: Vec<Decimal> = prices.iter().rev().take(9).cloned().collect();
-        let _signal_line = Self::calculate_ema(&recent_prices, 9);
-        
-        // Approximate signal as EMA of MACD by using price momentum
-        let signal_approx = if macd_line > Decimal::ZERO {
-            macd_line * Decimal::from_str("0.7").unwrap() // Approximate
-        } else {
-            macd_line * Decimal::from_str("0.7").unwrap()
-        };
-
-        let histogram = macd_line - signal_approx;
-
-        (macd_line, signal_approx, histogram)
+    // Calculate MACD (12, 26, 9): line, signal and histogram
+    pub(crate) fn calculate_macd(prices: &[Decimal]) -> (Decimal, Decimal, Decimal) {
+        let macd = indicators::macd(prices, 12, 26, 9);
+        (
+            indicators::latest(&macd.line).unwrap_or(Decimal::ZERO),
+            indicators::latest(&macd.signal).unwrap_or(Decimal::ZERO),
+            indicators::latest(&macd.histogram).unwrap_or(Decimal::ZERO),
+        )
     }
 
     // Calculate Bollinger Bands
-    fn calculate_bollinger_bands(prices: &[Decimal], period: usize, std_dev: Decimal) -> (Decimal, Decimal, Decimal) {
+    pub(crate) fn calculate_bollinger_bands(prices: &[Decimal], period: usize, std_dev: Decimal) -> (Decimal, Decimal, Decimal) {
         if prices.len() < period {
             let avg = if prices.is_empty() { Decimal::ZERO } else {
                 prices.iter().sum::<Decimal>() / Decimal::from(prices.len())
@@ -1300,26 +1891,19 @@ impl AutomationEngine {
             return (avg, avg, avg);
         }
 
-        let recent: Vec<Decimal> = prices.iter().rev().take(period).cloned().collect();
-        let sma = recent.iter().sum::<Decimal>() / Decimal::from(period);
-
-        // Calculate standard deviation
-        let variance = recent.iter()
-            .map(|p| {
-                let diff = *p - sma;
-                diff * diff
-            })
-            .sum::<Decimal>() / Decimal::from(period);
-        
-        let std = variance.sqrt().unwrap_or(Decimal::ZERO);
-        let upper_band = sma + (std * std_dev);
-        let lower_band = sma - (std * std_dev);
-
-        (upper_band, sma, lower_band)
+        let bands = indicators::bollinger(prices, period, std_dev);
+        match (
+            indicators::latest(&bands.upper),
+            indicators::latest(&bands.middle),
+            indicators::latest(&bands.lower),
+        ) {
+            (Some(upper), Some(middle), Some(lower)) => (upper, middle, lower),
+            _ => (Decimal::ZERO, Decimal::ZERO, Decimal::ZERO),
+        }
     }
 
     // Detect support and resistance levels
-    fn detect_support_resistance(prices: &[Decimal], lookback: usize) -> (Decimal, Decimal) {
+    pub(crate) fn detect_support_resistance(prices: &[Decimal], lookback: usize) -> (Decimal, Decimal) {
         if prices.len() < lookback {
             let current = prices.last().copied().unwrap_or(Decimal::ZERO);
             return (current * Decimal::from_str("0.98").unwrap(), current * Decimal::from_str("1.02").unwrap());
@@ -1349,9 +1933,81 @@ impl AutomationEngine {
          Ok(trend)
     }
 
+    /// Fills a market order at `price` according to the strategy's mode.
+    /// Live strategies settle through the simulated wallet (`orders` + `execute_order`);
+    /// paper strategies only record the hypothetical fill in `paper_orders`.
+    async fn place_market_order(
+        &self,
+        strategy: &Strategy,
+        coin_id: &str,
+        order_type: &str,
+        quantity: Decimal,
+        price: Decimal,
+    ) -> anyhow::Result<Uuid> {
+        let order_id = Uuid::new_v4();
+        let total_amount = price * quantity;
+
+        if strategy.is_paper() {
+            sqlx::query(
+                "INSERT INTO paper_orders (id, strategy_id, user_id, coin_id, coin_symbol, order_type, quantity, price_per_unit, total_amount, fee) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"
+            )
+            .bind(order_id)
+            .bind(strategy.id)
+            .bind(strategy.user_id)
+            .bind(coin_id)
+            .bind(coin_id.to_uppercase())
+            .bind(order_type)
+            .bind(quantity)
+            .bind(price)
+            .bind(total_amount)
+            .bind(trading_fee(total_amount))
+            .execute(&self.pool).await?;
+
+            info!("📝 Strategy {} PAPER {} recorded: {} {} @ {}", strategy.id, order_type, quantity, coin_id, price);
+            return Ok(order_id);
+        }
+
+        // Place market order (will execute immediately)
+        sqlx::query(
+            "INSERT INTO orders (id, user_id, coin_id, coin_symbol, order_type, order_mode, quantity, price_per_unit, total_amount, order_status) VALUES ($1, $2, $3, $4, $5, 'market', $6, $7, $8, 'completed')"
+        )
+        .bind(order_id)
+        .bind(strategy.user_id)
+        .bind(coin_id)
+        .bind(coin_id.to_uppercase())
+        .bind(order_type)
+        .bind(quantity)
+        .bind(price)
+        .bind(total_amount)
+        .execute(&self.pool).await?;
+
+        self.events.publish(DomainEvent::OrderPlaced {
+            order_id,
+            user_id: strategy.user_id,
+            coin_id: coin_id.to_string(),
+            order_type: order_type.to_string(),
+            order_mode: "market".to_string(),
+            quantity,
+            price,
+        });
+
+        // Update user balance/holdings via execution service. On failure the wallet is
+        // unchanged, so the caller must not open (or close) a position for this order
+        if let Err(e) = execute_order(&self.pool, &self.events, order_id, price).await {
+            error!("❌ Failed to execute automation {} order {}: {}", order_type, order_id, e);
+            sqlx::query("UPDATE orders SET order_status = 'cancelled' WHERE id = $1")
+                .bind(order_id)
+                .execute(&self.pool)
+                .await?;
+            return Err(e);
+        }
+
+        Ok(order_id)
+    }
+
     async fn log_action(
         &self,
-        strategy_id: Uuid,
+        strategy: &Strategy,
         action: &str,
         coin_id: &str,
         price: Decimal,
@@ -1362,7 +2018,7 @@ impl AutomationEngine {
         sqlx::query(
             "INSERT INTO strategy_logs (strategy_id, action, coin_id, coin_symbol, price, quantity, amount, profit) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"
         )
-        .bind(strategy_id)
+        .bind(strategy.id)
         .bind(action)
         .bind(coin_id)
         .bind(coin_id.to_uppercase())
@@ -1371,11 +2027,27 @@ impl AutomationEngine {
         .bind(amount)
         .bind(profit)
         .execute(&self.pool).await?;
+
+        self.hub.publish(
+            Channel::Automation,
+            Some(strategy.user_id),
+            "action",
+            json!({
+                "strategy_id": strategy.id,
+                "action": action,
+                "coin_id": coin_id,
+                "price": price,
+                "quantity": quantity,
+                "amount": amount,
+                "profit": profit,
+            }),
+        );
         Ok(())
     }
 
     pub async fn force_exit_strategy(&self, id: Uuid) -> anyhow::Result<()> {
         info!("🚨 FORCE EXIT requested for strategy {}", id);
+        let _cycle = self.cycle.lock().await;
 
         // 1. Fetch Strategy
         let strategy = sqlx::query_as::<_, Strategy>("SELECT * FROM strategies WHERE id = $1")
@@ -1398,59 +2070,105 @@ impl AutomationEngine {
                 .bind(order_id)
                 .execute(&self.pool)
                 .await?;
+            self.events.publish(DomainEvent::OrderCancelled {
+                order_id,
+                user_id: strategy.user_id,
+                reason: "force_exit".to_string(),
+            });
         }
 
-        // 3. Sell Active Position if exists
-        if let Some(coin_id) = &strategy.current_coin_id {
-            if let Some(entry_price) = strategy.entry_price {
-                 if entry_price > Decimal::ZERO {
-                    // Fetch current price to estimate return (optional, matching engine handles execution price)
-                    // Just place Market Sell
-                    let prices = self.matching_engine.get_prices().await;
-                    let current_price = prices.get(coin_id).cloned().unwrap_or(entry_price);
+        // 3. Sell every open position
+        let positions = self.open_positions(strategy.id).await?;
+        let prices = if positions.is_empty() { HashMap::new() } else { self.matching_engine.get_prices().await };
+        for position in positions {
+            let coin_id = position.coin_id.as_str();
+            // Fetch current price to estimate return (optional, matching engine handles execution price)
+            // Just place Market Sell
+            let current_price = prices.get(coin_id).cloned().unwrap_or(position.entry_price);
 
-                    let quantity = strategy.amount / entry_price;
-                    let sell_order_id = Uuid::new_v4();
-                    let total_amount = current_price * quantity;
+            let quantity = position.remaining_quantity(&strategy.exit_ladder());
+            let total_amount = current_price * quantity;
 
-                    info!("Placing FORCE MARKET SELL for {} {}", quantity, coin_id);
-                    
-                    sqlx::query(
-                        "INSERT INTO orders (id, user_id, coin_id, coin_symbol, order_type, order_mode, quantity, price_per_unit, total_amount, order_status) VALUES ($1, $2, $3, $4, 'sell', 'market', $5, $6, $7, 'pending')"
-                    )
-                    .bind(sell_order_id)
-                    .bind(strategy.user_id)
-                    .bind(coin_id)
-                    .bind(coin_id.to_uppercase())
-                    .bind(quantity)
-                    .bind(current_price)
-                    .bind(total_amount)
-                    .execute(&self.pool).await?;
+            info!("Placing FORCE MARKET SELL for {} {}", quantity, coin_id);
 
-                    // Add to matching engine
-                    self.matching_engine
-                        .add_order(
-                            sell_order_id.to_string(),
-                            coin_id.to_string(),
-                            "sell".to_string(),
-                            current_price, // For market order, this might be treated as limit in current simple engine, but let's hope it executes against current price
-                            quantity,
-                        )
-                        .await;
-                    
-                    // Log the Panic Sell
-                    self.log_action(
-                        strategy.id,
-                        "sell_force",
-                        coin_id,
-                        current_price,
-                        total_amount,
-                        Some(total_amount - strategy.amount), // approx profit/loss
-                    ).await?;
-                 }
+            if strategy.is_paper() {
+                // Nothing to liquidate in the wallet, just close the shadow position
+                self.place_market_order(&strategy, coin_id, "sell", quantity, current_price)
+                    .await?;
+            } else {
+                let sell_order_id = Uuid::new_v4();
+                sqlx::query(
+                    "INSERT INTO orders (id, user_id, coin_id, coin_symbol, order_type, order_mode, quantity, price_per_unit, total_amount, order_status) VALUES ($1, $2, $3, $4, 'sell', 'market', $5, $6, $7, 'pending')"
+                )
+                .bind(sell_order_id)
+                .bind(strategy.user_id)
+                .bind(coin_id)
+                .bind(coin_id.to_uppercase())
+                .bind(quantity)
+                .bind(current_price)
+                .bind(total_amount)
+                .execute(&self.pool).await?;
+
+                // Add to matching engine
+                self.matching_engine
+                    .add_order(
+                        sell_order_id,
+                        strategy.user_id,
+                        coin_id.to_string(),
+                        "sell".to_string(),
+                        current_price, // For market order, this might be treated as limit in current simple engine, but let's hope it executes against current price
+                        quantity,
+                    )
+                    .await;
             }
+
+            let profit = Self::tranche_profit(position.entry_price, current_price, quantity);
+            sqlx::query(
+                "UPDATE strategy_positions SET status = 'closed', exit_price = $2, profit = $3, closed_at = NOW() WHERE id = $1"
+            )
+            .bind(position.id)
+            .bind(current_price)
+            .bind(position.realized_profit + profit)
+            .execute(&self.pool).await?;
+            self.publish_position_closed(
+                &strategy,
+                &position,
+                "force_exit",
+                current_price,
+                position.realized_profit + profit,
+            );
+
+            // Log the Panic Sell
+            self.log_action(
+                &strategy,
+                "sell_force",
+                coin_id,
+                current_price,
+                total_amount,
+                Some(profit),
+            ).await?;
+
+            self.record_decision(
+                &strategy,
+                Some(position.id),
+                "exit",
+                Some(coin_id),
+                json!({
+                    "reason": "Force Exit",
+                    "price": current_price,
+                    "entry_price": position.entry_price,
+                    "quantity": quantity,
+                    "profit": position.realized_profit + profit,
+                }),
+            )
+            .await;
         }
 
+        sqlx::query("UPDATE strategies SET current_coin_id = NULL, entry_price = NULL WHERE id = $1")
+            .bind(strategy.id)
+            .execute(&self.pool)
+            .await?;
+
         // 4. Stop Strategy
         self.stop_strategy(id, "force_stopped").await?;
 
@@ -1458,12 +2176,27 @@ impl AutomationEngine {
     }
 
     async fn stop_strategy(&self, id: Uuid, reason: &str) -> anyhow::Result<()> {
-        sqlx::query("UPDATE strategies SET status = $2 WHERE id = $1")
-            .bind(id)
-            .bind(reason)
-            .execute(&self.pool)
-            .await?;
+        let mut tx = self.pool.begin().await?;
+        let user_id: Option<Uuid> =
+            sqlx::query_scalar("UPDATE strategies SET status = $2 WHERE id = $1 RETURNING user_id")
+                .bind(id)
+                .bind(reason)
+                .fetch_optional(&mut *tx)
+                .await?;
+        let stopped = user_id.map(|user_id| DomainEvent::StrategyStateChanged {
+            strategy_id: id,
+            user_id,
+            status: reason.to_string(),
+        });
+        if let Some(event) = &stopped {
+            append_to_outbox(&mut *tx, event).await?;
+        }
+        tx.commit().await?;
+
         info!("🛑 Strategy {} stopped: {}", id, reason);
+        if let Some(event) = stopped {
+            self.events.publish_committed(event);
+        }
         Ok(())
     }
 }
@@ -0,0 +1,182 @@
+use crate::models::Candle;
+use crate::services::automation::{AutomationEngine, EntrySignals, StrategyParams};
+use crate::services::execution::trading_fee;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::ops::Range;
+use std::str::FromStr;
+
+// The live engine analyses the last 50 closes (RSI/MACD/BB) and 20 candles for ATR
+const ANALYSIS_WINDOW: usize = 50;
+const ATR_WINDOW: usize = 20;
+
+/// Indicator readings at one candle. These don't depend on the strategy parameters,
+/// so they are computed once per series and shared by every parameter candidate.
+#[derive(Debug, Clone)]
+pub struct CandleFeatures {
+    rsi: Decimal,
+    macd: Decimal,
+    macd_signal: Decimal,
+    macd_histogram: Decimal,
+    bb_middle: Decimal,
+    bb_lower: Decimal,
+    support_level: Decimal,
+    volume_ratio: Decimal,
+    atr: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BacktestReport {
+    pub trades: usize,
+    pub wins: usize,
+    pub win_rate: Decimal,
+    pub total_return_pct: Decimal,
+    pub max_drawdown_pct: Decimal,
+}
+
+/// Computes features for every candle using the same indicator helpers as the live engine.
+/// Entries are `None` until enough history exists.
+pub fn compute_features(candles: &[Candle]) -> Vec<Option<CandleFeatures>> {
+    let closes: Vec<Decimal> = candles.iter().map(|c| c.close).collect();
+
+    (0..candles.len())
+        .map(|i| {
+            if i + 1 < ANALYSIS_WINDOW {
+                return None;
+            }
+            let window = &closes[i + 1 - ANALYSIS_WINDOW..=i];
+            let (macd, macd_signal, macd_histogram) = AutomationEngine::calculate_macd(window);
+            let (_, bb_middle, bb_lower) =
+                AutomationEngine::calculate_bollinger_bands(window, 20, Decimal::from(2));
+            let (support_level, _) = AutomationEngine::detect_support_resistance(window, 20);
+            let atr = AutomationEngine::calculate_atr(&candles[i + 1 - ATR_WINDOW..=i], 14);
+
+            // Live analysis derives volume from recent trades; candles give us the real ratio
+            let recent = &candles[i + 1 - 20..=i];
+            let avg_volume = recent.iter().map(|c| c.volume).sum::<Decimal>() / Decimal::from(20);
+            let volume_ratio = if avg_volume > Decimal::ZERO {
+                candles[i].volume / avg_volume
+            } else {
+                Decimal::ONE
+            };
+
+            Some(CandleFeatures {
+                rsi: AutomationEngine::calculate_rsi(window, 14),
+                macd,
+                macd_signal,
+                macd_histogram,
+                bb_middle,
+                bb_lower,
+                support_level,
+                volume_ratio,
+                atr,
+            })
+        })
+        .collect()
+}
+
+/// Replays the entry filters and ATR stop/target exits over `range`, one position at a time.
+/// Order-book, trade-flow and BTC-trend inputs aren't available from candles, so only the
+/// indicator-based filters are applied.
+pub fn run(
+    candles: &[Candle],
+    features: &[Option<CandleFeatures>],
+    range: Range<usize>,
+    params: &StrategyParams,
+) -> BacktestReport {
+    let min_entry_score = Decimal::from_str("0.7").unwrap();
+    let support_margin = Decimal::from_str("1.01").unwrap();
+    let trail_activation_pct = Decimal::from_str("0.5").unwrap();
+
+    let mut equity = Decimal::ONE;
+    let mut peak = Decimal::ONE;
+    let mut max_drawdown = Decimal::ZERO;
+    let mut trades = 0;
+    let mut wins = 0;
+
+    // (entry_price, high_water_mark)
+    let mut position: Option<(Decimal, Decimal)> = None;
+    let last = range.end.saturating_sub(1);
+
+    for i in range {
+        let price = candles[i].close;
+        let Some(f) = &features[i] else { continue };
+
+        if let Some((entry_price, high_water_mark)) = position.as_mut() {
+            if price > *high_water_mark {
+                *high_water_mark = price;
+            }
+            let profit_pct = (price - *entry_price) / *entry_price * Decimal::from(100);
+            let stop_price = if profit_pct > trail_activation_pct {
+                *high_water_mark - f.atr * params.atr_trail_multiplier
+            } else {
+                *entry_price - f.atr * params.atr_stop_multiplier
+            };
+            let target_price =
+                *entry_price * (Decimal::ONE + params.profit_percentage / Decimal::from(100));
+
+            if price <= stop_price || price >= target_price || i == last {
+                let gross = price / *entry_price;
+                let net = gross - trading_fee(Decimal::ONE) - trading_fee(gross);
+                equity *= net;
+                trades += 1;
+                if net > Decimal::ONE {
+                    wins += 1;
+                }
+                if equity > peak {
+                    peak = equity;
+                }
+                let drawdown = (peak - equity) / peak;
+                if drawdown > max_drawdown {
+                    max_drawdown = drawdown;
+                }
+                position = None;
+            }
+            continue;
+        }
+
+        if i == last {
+            break;
+        }
+        if f.rsi > params.rsi_overbought {
+            continue;
+        }
+        if f.support_level > Decimal::ZERO && price > f.support_level * support_margin {
+            continue;
+        }
+        if f.volume_ratio < params.min_volume_ratio {
+            continue;
+        }
+        let entry_score = AutomationEngine::score_entry(
+            &EntrySignals {
+                current_price: price,
+                rsi: f.rsi,
+                macd: f.macd,
+                macd_signal: f.macd_signal,
+                macd_histogram: f.macd_histogram,
+                bb_middle: f.bb_middle,
+                bb_lower: f.bb_lower,
+                volume_ratio: f.volume_ratio,
+                support_level: f.support_level,
+            },
+            params,
+        );
+        if entry_score < min_entry_score {
+            continue;
+        }
+
+        position = Some((price, price));
+    }
+
+    BacktestReport {
+        trades,
+        wins,
+        win_rate: if trades > 0 {
+            Decimal::from(wins) / Decimal::from(trades)
+        } else {
+            Decimal::ZERO
+        },
+        total_return_pct: (equity - Decimal::ONE) * Decimal::from(100),
+        max_drawdown_pct: max_drawdown * Decimal::from(100),
+    }
+}
@@ -0,0 +1,361 @@
+use crate::models::Candle;
+use crate::services::depth::DepthSnapshot;
+use reqwest::{Client, StatusCode};
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{error, warn};
+
+/// Binance allows 6000 request weight per minute per IP; stay below it so other
+/// processes sharing the IP (and clock skew at the window edge) don't tip us over
+const WEIGHT_BUDGET_PER_MINUTE: u64 = 4800;
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+/// Callers wait at most this long for the weight window or a backoff to clear
+const MAX_QUEUE_WAIT_SECS: u64 = 65;
+/// Retries after a 429, each after waiting out `Retry-After`
+const MAX_RATE_LIMIT_RETRIES: u32 = 2;
+/// Used when a 429/418 response carries no `Retry-After`
+const DEFAULT_RETRY_AFTER_SECS: u64 = 60;
+
+const KLINES_WEIGHT: u64 = 2;
+const TRADES_WEIGHT: u64 = 25;
+
+/// Request weight of `GET /api/v3/depth` for a given `limit`
+fn depth_weight(limit: usize) -> u64 {
+    match limit {
+        0..=100 => 5,
+        101..=500 => 25,
+        501..=1000 => 50,
+        _ => 250,
+    }
+}
+
+/// Weight used in the current one-minute window, mirroring Binance's own accounting
+struct WeightWindow {
+    minute: i64, // Unix minute the counter belongs to
+    used: u64,
+    blocked_until: Option<Instant>, // Set from Retry-After on 429/418
+}
+
+#[derive(Default)]
+struct Counters {
+    requests: AtomicU64,
+    failures: AtomicU64,
+    timeouts: AtomicU64,
+    rate_limited: AtomicU64, // HTTP 429
+    banned: AtomicU64,       // HTTP 418
+    retries: AtomicU64,
+    delayed: AtomicU64,
+    delay_ms: AtomicU64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BinanceMetrics {
+    pub base_url: String,
+    pub requests: u64,
+    pub failures: u64,
+    pub timeouts: u64,
+    pub rate_limited: u64,
+    pub banned: u64,
+    pub retries: u64,
+    pub delayed_requests: u64,
+    pub total_delay_ms: u64,
+    pub used_weight: u64,
+    pub weight_budget: u64,
+    pub blocked_for_secs: Option<u64>,
+}
+
+/// Shared client for Binance's public REST API.
+///
+/// Every call reserves its request weight before it is sent. When the minute's budget is
+/// spent, or Binance asked us to back off, callers queue (in arrival order) until the
+/// window rolls over instead of adding to the ban.
+pub struct BinanceClient {
+    http_client: Client,
+    base_url: String,
+    window: Mutex<WeightWindow>,
+    queue: tokio::sync::Mutex<()>, // Held while waiting for weight so callers go in order
+    counters: Counters,
+}
+
+impl BinanceClient {
+    pub fn new(base_url: &str) -> Self {
+        let http_client = Client::builder()
+            .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+            .build()
+            .unwrap_or_else(|_| Client::new());
+
+        Self {
+            http_client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            window: Mutex::new(WeightWindow {
+                minute: current_minute(),
+                used: 0,
+                blocked_until: None,
+            }),
+            queue: tokio::sync::Mutex::new(()),
+            counters: Counters::default(),
+        }
+    }
+
+    /// `GET /api/v3/depth`
+    pub async fn depth(&self, coin_id: &str, limit: usize) -> anyhow::Result<DepthSnapshot> {
+        self.get(
+            "/api/v3/depth",
+            &[("symbol", symbol(coin_id)), ("limit", limit.to_string())],
+            depth_weight(limit),
+        )
+        .await
+    }
+
+    /// `GET /api/v3/trades`
+    pub async fn recent_trades<T: DeserializeOwned>(
+        &self,
+        coin_id: &str,
+        limit: u32,
+    ) -> anyhow::Result<Vec<T>> {
+        self.get(
+            "/api/v3/trades",
+            &[("symbol", symbol(coin_id)), ("limit", limit.to_string())],
+            TRADES_WEIGHT,
+        )
+        .await
+    }
+
+    /// `GET /api/v3/klines` parsed into OHLCV candles (oldest first)
+    pub async fn klines(
+        &self,
+        coin_id: &str,
+        interval: &str,
+        limit: usize,
+    ) -> anyhow::Result<Vec<Candle>> {
+        // [open_time, open, high, low, close, volume, ...]
+        let raw_klines: Vec<serde_json::Value> = self
+            .get(
+                "/api/v3/klines",
+                &[
+                    ("symbol", symbol(coin_id)),
+                    ("interval", interval.to_string()),
+                    ("limit", limit.to_string()),
+                ],
+                KLINES_WEIGHT,
+            )
+            .await?;
+
+        let parse = |k: &serde_json::Value, i: usize| {
+            k.get(i)
+                .and_then(|v| v.as_str())
+                .and_then(|s| s.parse::<Decimal>().ok())
+        };
+
+        let candles = raw_klines
+            .iter()
+            .filter_map(|k| {
+                Some(Candle {
+                    open_time: k.get(0)?.as_i64()?,
+                    open: parse(k, 1)?,
+                    high: parse(k, 2)?,
+                    low: parse(k, 3)?,
+                    close: parse(k, 4)?,
+                    volume: parse(k, 5)?,
+                })
+            })
+            .collect();
+
+        Ok(candles)
+    }
+
+    /// Sends a weighted GET, retrying 429s after the server's `Retry-After`
+    pub async fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+        weight: u64,
+    ) -> anyhow::Result<T> {
+        let url = format!("{}{}", self.base_url, path);
+        let mut attempt = 0;
+
+        loop {
+            self.acquire(weight).await?;
+            self.counters.requests.fetch_add(1, Ordering::Relaxed);
+
+            let response = match self.http_client.get(&url).query(query).send().await {
+                Ok(response) => response,
+                Err(e) => {
+                    if e.is_timeout() {
+                        self.counters.timeouts.fetch_add(1, Ordering::Relaxed);
+                    }
+                    self.counters.failures.fetch_add(1, Ordering::Relaxed);
+                    return Err(anyhow::anyhow!("Binance request {} failed: {}", path, e));
+                }
+            };
+
+            self.record_used_weight(&response);
+
+            let status = response.status();
+            if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::IM_A_TEAPOT {
+                let retry_after = retry_after(&response);
+                self.back_off(retry_after);
+
+                if status == StatusCode::IM_A_TEAPOT {
+                    self.counters.banned.fetch_add(1, Ordering::Relaxed);
+                    self.counters.failures.fetch_add(1, Ordering::Relaxed);
+                    error!(
+                        "🚫 Binance banned this IP (418), pausing REST calls for {}s",
+                        retry_after.as_secs()
+                    );
+                    return Err(anyhow::anyhow!("Binance IP ban on {}", path));
+                }
+
+                self.counters.rate_limited.fetch_add(1, Ordering::Relaxed);
+                warn!(
+                    "⚠️ Binance rate limit hit on {} (429), backing off {}s",
+                    path,
+                    retry_after.as_secs()
+                );
+                if attempt < MAX_RATE_LIMIT_RETRIES {
+                    attempt += 1;
+                    self.counters.retries.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+                self.counters.failures.fetch_add(1, Ordering::Relaxed);
+                return Err(anyhow::anyhow!("Binance rate limit exceeded on {}", path));
+            }
+
+            if !status.is_success() {
+                self.counters.failures.fetch_add(1, Ordering::Relaxed);
+                return Err(anyhow::anyhow!("Binance request {} failed: {}", path, status));
+            }
+
+            return response.json::<T>().await.map_err(|e| {
+                self.counters.failures.fetch_add(1, Ordering::Relaxed);
+                anyhow::anyhow!("Invalid Binance response from {}: {}", path, e)
+            });
+        }
+    }
+
+    pub fn metrics(&self) -> BinanceMetrics {
+        let (used_weight, blocked_for_secs) = {
+            let window = self.window.lock().unwrap();
+            let used = if window.minute == current_minute() { window.used } else { 0 };
+            let blocked = window
+                .blocked_until
+                .and_then(|until| until.checked_duration_since(Instant::now()))
+                .map(|left| left.as_secs());
+            (used, blocked)
+        };
+
+        BinanceMetrics {
+            base_url: self.base_url.clone(),
+            requests: self.counters.requests.load(Ordering::Relaxed),
+            failures: self.counters.failures.load(Ordering::Relaxed),
+            timeouts: self.counters.timeouts.load(Ordering::Relaxed),
+            rate_limited: self.counters.rate_limited.load(Ordering::Relaxed),
+            banned: self.counters.banned.load(Ordering::Relaxed),
+            retries: self.counters.retries.load(Ordering::Relaxed),
+            delayed_requests: self.counters.delayed.load(Ordering::Relaxed),
+            total_delay_ms: self.counters.delay_ms.load(Ordering::Relaxed),
+            used_weight,
+            weight_budget: WEIGHT_BUDGET_PER_MINUTE,
+            blocked_for_secs,
+        }
+    }
+
+    /// Waits until `weight` fits in the current window and no backoff is active, then
+    /// reserves it. Fails instead of waiting longer than `MAX_QUEUE_WAIT_SECS`.
+    async fn acquire(&self, weight: u64) -> anyhow::Result<()> {
+        let _turn = self.queue.lock().await;
+        let started = Instant::now();
+        let mut delayed = false;
+
+        loop {
+            let wait = {
+                let mut window = self.window.lock().unwrap();
+                let now = Instant::now();
+                let minute = current_minute();
+                if window.minute != minute {
+                    window.minute = minute;
+                    window.used = 0;
+                }
+
+                match window.blocked_until.filter(|until| *until > now) {
+                    Some(until) => until - now,
+                    None if window.used + weight <= WEIGHT_BUDGET_PER_MINUTE => {
+                        window.used += weight;
+                        break;
+                    }
+                    None => until_next_minute(),
+                }
+            };
+
+            if started.elapsed() + wait > Duration::from_secs(MAX_QUEUE_WAIT_SECS) {
+                self.counters.failures.fetch_add(1, Ordering::Relaxed);
+                return Err(anyhow::anyhow!(
+                    "Binance request budget exhausted, next slot in {}s",
+                    wait.as_secs()
+                ));
+            }
+            delayed = true;
+            tokio::time::sleep(wait).await;
+        }
+
+        if delayed {
+            self.counters.delayed.fetch_add(1, Ordering::Relaxed);
+            self.counters
+                .delay_ms
+                .fetch_add(started.elapsed().as_millis() as u64, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// Trusts the server's count when it is ahead of ours (other clients on the same IP)
+    fn record_used_weight(&self, response: &reqwest::Response) {
+        let Some(used) = response
+            .headers()
+            .get("x-mbx-used-weight-1m")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok())
+        else {
+            return;
+        };
+
+        let mut window = self.window.lock().unwrap();
+        if window.minute == current_minute() {
+            window.used = window.used.max(used);
+        }
+    }
+
+    fn back_off(&self, retry_after: Duration) {
+        let until = Instant::now() + retry_after;
+        let mut window = self.window.lock().unwrap();
+        if window.blocked_until.is_none_or(|current| current < until) {
+            window.blocked_until = Some(until);
+        }
+    }
+}
+
+fn symbol(coin_id: &str) -> String {
+    format!("{}USDT", coin_id.to_uppercase())
+}
+
+fn retry_after(response: &reqwest::Response) -> Duration {
+    let secs = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_RETRY_AFTER_SECS);
+    Duration::from_secs(secs)
+}
+
+fn current_minute() -> i64 {
+    chrono::Utc::now().timestamp().div_euclid(60)
+}
+
+fn until_next_minute() -> Duration {
+    let millis = chrono::Utc::now().timestamp_millis().rem_euclid(60_000) as u64;
+    Duration::from_millis(60_000 - millis)
+}
@@ -0,0 +1,304 @@
+use crate::models::Candle;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use std::collections::{HashMap, VecDeque};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+/// Closed candles kept in memory per coin and interval
+const RING_CAPACITY: usize = 500;
+/// How often closed candles are written to the `candles` table
+const FLUSH_INTERVAL_SECS: u64 = 15;
+/// Retention runs once every this many flushes (~1h)
+const PRUNE_EVERY_FLUSHES: u64 = 240;
+/// Closed candles kept for retry while the database is unreachable (a few hours of market data)
+const MAX_PENDING_CANDLES: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum Interval {
+    #[serde(rename = "1m")]
+    M1,
+    #[serde(rename = "5m")]
+    M5,
+    #[serde(rename = "15m")]
+    M15,
+    #[serde(rename = "1h")]
+    H1,
+}
+
+impl Interval {
+    pub const ALL: [Interval; 4] = [Interval::M1, Interval::M5, Interval::M15, Interval::H1];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Interval::M1 => "1m",
+            Interval::M5 => "5m",
+            Interval::M15 => "15m",
+            Interval::H1 => "1h",
+        }
+    }
+
+    pub fn millis(&self) -> i64 {
+        match self {
+            Interval::M1 => 60_000,
+            Interval::M5 => 5 * 60_000,
+            Interval::M15 => 15 * 60_000,
+            Interval::H1 => 60 * 60_000,
+        }
+    }
+
+    /// How long persisted candles of this interval are kept
+    fn retention_days(&self) -> i64 {
+        match self {
+            Interval::M1 => 3,
+            _ => 30,
+        }
+    }
+}
+
+impl FromStr for Interval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Interval::ALL
+            .into_iter()
+            .find(|i| i.as_str() == s)
+            .ok_or_else(|| format!("Unsupported interval '{}', expected 1m, 5m, 15m or 1h", s))
+    }
+}
+
+/// One ticker observation fed into the aggregator
+pub struct Tick {
+    pub coin_id: String,
+    pub price: Decimal,
+    pub volume_24h: Decimal, // Rolling 24h base volume ('v' from the mini ticker)
+}
+
+#[derive(Default)]
+struct Series {
+    closed: VecDeque<Candle>,
+    current: Option<Candle>,
+}
+
+type SeriesKey = (String, Interval);
+
+/// Builds OHLCV candles from the live ticker stream.
+///
+/// The mini ticker only carries a rolling 24h volume, so candle volume is the increase of
+/// that counter between ticks (clamped at zero when old trades roll out of the window).
+#[derive(Clone)]
+pub struct CandleAggregator {
+    pool: PgPool,
+    series: Arc<Mutex<HashMap<SeriesKey, Series>>>,
+    last_volume: Arc<Mutex<HashMap<String, Decimal>>>, // CoinID -> last seen 24h volume
+    pending: Arc<Mutex<Vec<(SeriesKey, Candle)>>>,     // Closed candles awaiting persistence
+}
+
+impl CandleAggregator {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            series: Arc::new(Mutex::new(HashMap::new())),
+            last_volume: Arc::new(Mutex::new(HashMap::new())),
+            pending: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub async fn start(&self) {
+        let aggregator = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(FLUSH_INTERVAL_SECS));
+            let mut flushes: u64 = 0;
+            loop {
+                interval.tick().await;
+                if let Err(e) = aggregator.flush().await {
+                    error!("❌ Failed to persist candles: {}", e);
+                }
+                flushes += 1;
+                if flushes.is_multiple_of(PRUNE_EVERY_FLUSHES) {
+                    if let Err(e) = aggregator.prune().await {
+                        warn!("⚠️ Failed to prune old candles: {}", e);
+                    }
+                }
+            }
+        });
+        info!("🕯️ Candle aggregator started ({} intervals)", Interval::ALL.len());
+    }
+
+    /// Applies one ticker batch (all coins share the event time in ms)
+    pub async fn ingest(&self, event_time: i64, ticks: &[Tick]) {
+        let mut volume_deltas = Vec::with_capacity(ticks.len());
+        {
+            let mut last_volume = self.last_volume.lock().await;
+            for tick in ticks {
+                let delta = match last_volume.insert(tick.coin_id.clone(), tick.volume_24h) {
+                    Some(previous) => (tick.volume_24h - previous).max(Decimal::ZERO),
+                    None => Decimal::ZERO,
+                };
+                volume_deltas.push(delta);
+            }
+        }
+
+        let mut closed = Vec::new();
+        {
+            let mut series = self.series.lock().await;
+            for (tick, volume) in ticks.iter().zip(volume_deltas) {
+                for interval in Interval::ALL {
+                    let open_time = event_time - event_time.rem_euclid(interval.millis());
+                    let entry = series.entry((tick.coin_id.clone(), interval)).or_default();
+
+                    match entry.current.as_mut() {
+                        Some(candle) if candle.open_time == open_time => {
+                            candle.high = candle.high.max(tick.price);
+                            candle.low = candle.low.min(tick.price);
+                            candle.close = tick.price;
+                            candle.volume += volume;
+                        }
+                        // Late tick for an already closed bucket
+                        Some(candle) if candle.open_time > open_time => {}
+                        _ => {
+                            let next = Candle {
+                                open_time,
+                                open: tick.price,
+                                high: tick.price,
+                                low: tick.price,
+                                close: tick.price,
+                                volume,
+                            };
+                            if let Some(done) = entry.current.replace(next) {
+                                if entry.closed.len() == RING_CAPACITY {
+                                    entry.closed.pop_front();
+                                }
+                                entry.closed.push_back(done.clone());
+                                closed.push(((tick.coin_id.clone(), interval), done));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if !closed.is_empty() {
+            self.pending.lock().await.extend(closed);
+        }
+    }
+
+    /// Most recent in-memory candles (oldest first). With `include_current` the still-open
+    /// candle is appended.
+    pub async fn recent(
+        &self,
+        coin_id: &str,
+        interval: Interval,
+        limit: usize,
+        include_current: bool,
+    ) -> Vec<Candle> {
+        let series = self.series.lock().await;
+        let Some(entry) = series.get(&(coin_id.to_lowercase(), interval)) else {
+            return Vec::new();
+        };
+
+        let current = entry.current.iter().filter(|_| include_current);
+        let all: Vec<Candle> = entry.closed.iter().chain(current).cloned().collect();
+        all[all.len().saturating_sub(limit)..].to_vec()
+    }
+
+    /// Recent candles from memory, topped up from the `candles` table when the ring buffer
+    /// doesn't reach back far enough (e.g. right after a restart)
+    pub async fn history(
+        &self,
+        coin_id: &str,
+        interval: Interval,
+        limit: usize,
+    ) -> anyhow::Result<Vec<Candle>> {
+        let coin_id = coin_id.to_lowercase();
+        let mut candles = self.recent(&coin_id, interval, limit, true).await;
+        if candles.len() >= limit {
+            return Ok(candles);
+        }
+
+        let before = candles.first().map_or(i64::MAX, |c| c.open_time);
+        let missing = (limit - candles.len()) as i64;
+        let mut stored = sqlx::query_as::<_, (i64, Decimal, Decimal, Decimal, Decimal, Decimal)>(
+            "SELECT open_time, open, high, low, close, volume FROM candles WHERE coin_id = $1 AND interval = $2 AND open_time < $3 ORDER BY open_time DESC LIMIT $4",
+        )
+        .bind(&coin_id)
+        .bind(interval.as_str())
+        .bind(before)
+        .bind(missing)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|(open_time, open, high, low, close, volume)| Candle {
+            open_time,
+            open,
+            high,
+            low,
+            close,
+            volume,
+        })
+        .collect::<Vec<_>>();
+
+        stored.reverse();
+        stored.append(&mut candles);
+        Ok(stored)
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        let batch = std::mem::take(&mut *self.pending.lock().await);
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        // Postgres caps bind parameters at 65535 (8 per row)
+        for (index, chunk) in batch.chunks(5000).enumerate() {
+            let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(
+                "INSERT INTO candles (coin_id, interval, open_time, open, high, low, close, volume) ",
+            );
+            builder.push_values(chunk, |mut row, ((coin_id, interval), candle)| {
+                row.push_bind(coin_id)
+                    .push_bind(interval.as_str())
+                    .push_bind(candle.open_time)
+                    .push_bind(candle.open)
+                    .push_bind(candle.high)
+                    .push_bind(candle.low)
+                    .push_bind(candle.close)
+                    .push_bind(candle.volume);
+            });
+            builder.push(
+                " ON CONFLICT (coin_id, interval, open_time) DO UPDATE SET high = EXCLUDED.high, low = EXCLUDED.low, close = EXCLUDED.close, volume = EXCLUDED.volume",
+            );
+            if let Err(e) = builder.build().execute(&self.pool).await {
+                // Requeue this chunk and the rest ahead of newer candles; the upsert makes retries safe
+                let mut pending = self.pending.lock().await;
+                let newer = std::mem::take(&mut *pending);
+                pending.extend(batch.into_iter().skip(index * 5000));
+                pending.extend(newer);
+                let excess = pending.len().saturating_sub(MAX_PENDING_CANDLES);
+                if excess > 0 {
+                    warn!("⚠️ Dropping {} unsaved candles (retry queue full)", excess);
+                    pending.drain(..excess);
+                }
+                return Err(e.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn prune(&self) -> anyhow::Result<()> {
+        let now = chrono::Utc::now().timestamp_millis();
+        for interval in Interval::ALL {
+            let cutoff = now - interval.retention_days() * 24 * 60 * 60 * 1000;
+            sqlx::query("DELETE FROM candles WHERE interval = $1 AND open_time < $2")
+                .bind(interval.as_str())
+                .bind(cutoff)
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(())
+    }
+}
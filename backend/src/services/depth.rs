@@ -0,0 +1,537 @@
+use crate::services::binance::BinanceClient;
+use futures::future::BoxFuture;
+use futures::stream::{self, BoxStream};
+use futures::{FutureExt, StreamExt};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
+use tracing::{info, warn};
+
+/// Books nobody asked for in this long stop streaming
+const IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+/// Upper bound on concurrently maintained books (one WebSocket each)
+const MAX_TRACKED_BOOKS: usize = 40;
+/// Levels requested for the REST snapshot the diff stream is applied on top of
+const SNAPSHOT_LIMIT: usize = 1000;
+/// How long to wait for the first diff and for the snapshot before resyncing
+const SYNC_TIMEOUT: Duration = Duration::from_secs(30);
+/// How often a syncing or synced book checks whether anyone still reads it
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// One depth-diff message ('U'/'u' are the first/final update ids it covers)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DepthEvent {
+    #[serde(rename = "U")]
+    pub first_update_id: u64,
+    #[serde(rename = "u")]
+    pub final_update_id: u64,
+    #[serde(rename = "b")]
+    pub bids: Vec<[Decimal; 2]>, // [price, quantity]; quantity 0 removes the level
+    #[serde(rename = "a")]
+    pub asks: Vec<[Decimal; 2]>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DepthSnapshot {
+    #[serde(rename = "lastUpdateId")]
+    pub last_update_id: u64,
+    pub bids: Vec<[Decimal; 2]>,
+    pub asks: Vec<[Decimal; 2]>,
+}
+
+/// Where depth data comes from: the live exchange or a recorded file
+pub trait DepthSource: Send + Sync {
+    /// Diff events for `coin_id`, in exchange order
+    fn subscribe(&self, coin_id: &str) -> BoxStream<'static, anyhow::Result<DepthEvent>>;
+    /// Full book the diffs are applied on top of
+    fn snapshot(&self, coin_id: &str) -> BoxFuture<'static, anyhow::Result<DepthSnapshot>>;
+}
+
+pub struct BinanceDepthSource {
+    binance: Arc<BinanceClient>,
+}
+
+impl BinanceDepthSource {
+    pub fn new(binance: Arc<BinanceClient>) -> Self {
+        Self { binance }
+    }
+}
+
+impl DepthSource for BinanceDepthSource {
+    fn subscribe(&self, coin_id: &str) -> BoxStream<'static, anyhow::Result<DepthEvent>> {
+        let url = format!(
+            "wss://stream.binance.com:9443/ws/{}usdt@depth@100ms",
+            coin_id.to_lowercase()
+        );
+
+        async move {
+            let (ws_stream, _) = connect_async(url.as_str()).await?;
+            let (_, read) = ws_stream.split();
+            Ok::<_, anyhow::Error>(read.filter_map(|message| async move {
+                match message {
+                    Ok(Message::Text(text)) => {
+                        Some(serde_json::from_str::<DepthEvent>(&text).map_err(anyhow::Error::from))
+                    }
+                    Ok(_) => None,
+                    Err(e) => Some(Err(e.into())),
+                }
+            }))
+        }
+        .map(|connected| match connected {
+            Ok(events) => events.boxed(),
+            Err(e) => stream::once(async move { Err(e) }).boxed(),
+        })
+        .flatten_stream()
+        .boxed()
+    }
+
+    fn snapshot(&self, coin_id: &str) -> BoxFuture<'static, anyhow::Result<DepthSnapshot>> {
+        let binance = self.binance.clone();
+        let coin_id = coin_id.to_string();
+        async move { binance.depth(&coin_id, SNAPSHOT_LIMIT).await }.boxed()
+    }
+}
+
+/// One line of a depth recording: `{"coin_id": "btc", "kind": "snapshot" | "diff", "data": {...}}`
+/// where `data` is the exchange's raw message
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DepthRecord {
+    pub coin_id: String,
+    pub kind: String,
+    pub data: serde_json::Value,
+}
+
+/// Replays a newline-delimited JSON recording instead of connecting to the exchange
+pub struct FileReplaySource {
+    snapshots: HashMap<String, DepthSnapshot>,
+    events: HashMap<String, Vec<DepthEvent>>,
+}
+
+impl FileReplaySource {
+    pub fn open(path: &str) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let mut snapshots = HashMap::new();
+        let mut events: HashMap<String, Vec<DepthEvent>> = HashMap::new();
+
+        for (line_no, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: DepthRecord = serde_json::from_str(line)
+                .map_err(|e| anyhow::anyhow!("{}:{}: {}", path, line_no + 1, e))?;
+            let coin_id = record.coin_id.to_lowercase();
+            match record.kind.as_str() {
+                // First snapshot per coin wins, matching how a live session starts
+                "snapshot" => {
+                    if let Entry::Vacant(slot) = snapshots.entry(coin_id) {
+                        slot.insert(serde_json::from_value(record.data)?);
+                    }
+                }
+                "diff" => events
+                    .entry(coin_id)
+                    .or_default()
+                    .push(serde_json::from_value(record.data)?),
+                other => warn!("⚠️ {}:{}: unknown depth record kind '{}'", path, line_no + 1, other),
+            }
+        }
+
+        info!(
+            "📼 Loaded depth replay from {} ({} books)",
+            path,
+            snapshots.len()
+        );
+        Ok(Self { snapshots, events })
+    }
+}
+
+impl DepthSource for FileReplaySource {
+    fn subscribe(&self, coin_id: &str) -> BoxStream<'static, anyhow::Result<DepthEvent>> {
+        let Some(events) = self.events.get(coin_id).cloned() else {
+            let error = anyhow::anyhow!("No recorded depth diffs for {}", coin_id);
+            return stream::once(async move { Err(error) }).boxed();
+        };
+        // Keep the stream open after the recording ends so the book stays readable
+        stream::iter(events.into_iter().map(Ok))
+            .chain(stream::pending())
+            .boxed()
+    }
+
+    fn snapshot(&self, coin_id: &str) -> BoxFuture<'static, anyhow::Result<DepthSnapshot>> {
+        let snapshot = self
+            .snapshots
+            .get(coin_id)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("No recorded snapshot for {}", coin_id));
+        async move { snapshot }.boxed()
+    }
+}
+
+/// Local L2 book kept in sync with the diff stream
+#[derive(Debug, Default)]
+pub struct OrderBook {
+    last_update_id: u64,
+    bids: BTreeMap<Decimal, Decimal>,
+    asks: BTreeMap<Decimal, Decimal>,
+}
+
+impl OrderBook {
+    fn from_snapshot(snapshot: DepthSnapshot) -> Self {
+        let mut book = Self {
+            last_update_id: snapshot.last_update_id,
+            ..Default::default()
+        };
+        Self::apply_levels(&mut book.bids, &snapshot.bids);
+        Self::apply_levels(&mut book.asks, &snapshot.asks);
+        book
+    }
+
+    fn apply_levels(side: &mut BTreeMap<Decimal, Decimal>, levels: &[[Decimal; 2]]) {
+        for [price, quantity] in levels {
+            if quantity.is_zero() {
+                side.remove(price);
+            } else {
+                side.insert(*price, *quantity);
+            }
+        }
+    }
+
+    /// Applies a diff. `Ok(false)` means the event predates the book and was skipped;
+    /// `Err` means updates were missed and the book must be rebuilt from a new snapshot.
+    fn apply(&mut self, event: &DepthEvent) -> Result<bool, String> {
+        if event.final_update_id <= self.last_update_id {
+            return Ok(false);
+        }
+        if event.first_update_id > self.last_update_id + 1 {
+            return Err(format!(
+                "gap: book at {}, event starts at {}",
+                self.last_update_id, event.first_update_id
+            ));
+        }
+        Self::apply_levels(&mut self.bids, &event.bids);
+        Self::apply_levels(&mut self.asks, &event.asks);
+        self.last_update_id = event.final_update_id;
+        Ok(true)
+    }
+
+    fn view(&self, coin_id: &str, levels: usize) -> DepthView {
+        DepthView {
+            coin_id: coin_id.to_string(),
+            last_update_id: self.last_update_id,
+            bids: self
+                .bids
+                .iter()
+                .rev()
+                .take(levels)
+                .map(|(p, q)| [*p, *q])
+                .collect(),
+            asks: self
+                .asks
+                .iter()
+                .take(levels)
+                .map(|(p, q)| [*p, *q])
+                .collect(),
+        }
+    }
+}
+
+/// Top of a book: bids best (highest) first, asks best (lowest) first
+#[derive(Debug, Clone, Serialize)]
+pub struct DepthView {
+    pub coin_id: String,
+    pub last_update_id: u64,
+    pub bids: Vec<[Decimal; 2]>,
+    pub asks: Vec<[Decimal; 2]>,
+}
+
+struct TrackedBook {
+    book: Option<OrderBook>, // None while (re)syncing
+    last_access: Instant,
+}
+
+/// Maintains local books for the coins that are asked for, one stream per coin
+#[derive(Clone)]
+pub struct DepthCache {
+    source: Arc<dyn DepthSource>,
+    books: Arc<Mutex<HashMap<String, TrackedBook>>>,
+}
+
+impl DepthCache {
+    pub fn new(source: Arc<dyn DepthSource>) -> Self {
+        Self {
+            source,
+            books: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Top `levels` of the local book. Starts tracking the coin on first use and returns
+    /// `None` until the book is synced (or when the tracking limit is reached).
+    pub async fn book(&self, coin_id: &str, levels: usize) -> Option<DepthView> {
+        let coin_id = coin_id.trim().to_lowercase();
+        let mut books = self.books.lock().await;
+
+        if let Some(tracked) = books.get_mut(&coin_id) {
+            tracked.last_access = Instant::now();
+            return tracked.book.as_ref().map(|b| b.view(&coin_id, levels));
+        }
+
+        if books.len() >= MAX_TRACKED_BOOKS {
+            return None;
+        }
+        books.insert(
+            coin_id.clone(),
+            TrackedBook {
+                book: None,
+                last_access: Instant::now(),
+            },
+        );
+        drop(books);
+
+        let cache = self.clone();
+        tokio::spawn(async move { cache.maintain(coin_id).await });
+        None
+    }
+
+    /// Snapshot + diff sync loop for one coin; exits once the book goes idle
+    async fn maintain(&self, coin_id: String) {
+        info!("📖 Tracking order book for {}", coin_id);
+        loop {
+            if self.is_idle(&coin_id).await {
+                self.books.lock().await.remove(&coin_id);
+                info!("📕 Stopped tracking idle order book for {}", coin_id);
+                return;
+            }
+
+            if let Err(e) = self.sync(&coin_id).await {
+                warn!("⚠️ Order book {} out of sync: {}. Resyncing in 1s...", coin_id, e);
+            }
+            self.set_book(&coin_id, None).await;
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    /// Runs until the stream breaks, a gap is detected or the book goes idle
+    async fn sync(&self, coin_id: &str) -> anyhow::Result<()> {
+        // Checked from the start, so a coin that never produces data still goes idle
+        let mut idle_check = tokio::time::interval(IDLE_CHECK_INTERVAL);
+        let mut events = self.source.subscribe(coin_id);
+
+        // Buffer diffs from the first one on, so nothing between the snapshot and the
+        // stream is lost
+        let first = tokio::select! {
+            event = tokio::time::timeout(SYNC_TIMEOUT, events.next()) => event
+                .map_err(|_| anyhow::anyhow!("no depth diff within {}s", SYNC_TIMEOUT.as_secs()))?
+                .ok_or_else(|| anyhow::anyhow!("depth stream closed"))??,
+            _ = self.wait_until_idle(coin_id, &mut idle_check) => return Ok(()),
+        };
+        let mut buffered = vec![first];
+
+        let snapshot = {
+            let snapshot = tokio::time::timeout(SYNC_TIMEOUT, self.source.snapshot(coin_id));
+            tokio::pin!(snapshot);
+            loop {
+                tokio::select! {
+                    result = &mut snapshot => break result.map_err(|_| {
+                        anyhow::anyhow!("no depth snapshot within {}s", SYNC_TIMEOUT.as_secs())
+                    })??,
+                    event = events.next() => {
+                        buffered.push(event.ok_or_else(|| anyhow::anyhow!("depth stream closed"))??);
+                    }
+                    _ = self.wait_until_idle(coin_id, &mut idle_check) => return Ok(()),
+                }
+            }
+        };
+
+        // The snapshot must not be older than the first buffered diff
+        if snapshot.last_update_id + 1 < buffered[0].first_update_id {
+            anyhow::bail!(
+                "snapshot {} older than first diff {}",
+                snapshot.last_update_id,
+                buffered[0].first_update_id
+            );
+        }
+
+        let mut book = OrderBook::from_snapshot(snapshot);
+        for event in &buffered {
+            book.apply(event).map_err(anyhow::Error::msg)?;
+        }
+        self.set_book(coin_id, Some(book)).await;
+
+        loop {
+            tokio::select! {
+                event = events.next() => {
+                    let event = event.ok_or_else(|| anyhow::anyhow!("depth stream closed"))??;
+                    let mut books = self.books.lock().await;
+                    let Some(book) = books.get_mut(coin_id).and_then(|t| t.book.as_mut()) else {
+                        return Ok(());
+                    };
+                    book.apply(&event).map_err(anyhow::Error::msg)?;
+                }
+                _ = self.wait_until_idle(coin_id, &mut idle_check) => return Ok(()),
+            }
+        }
+    }
+
+    /// Resolves once the book has gone idle, checking on every tick of `interval`
+    async fn wait_until_idle(&self, coin_id: &str, interval: &mut tokio::time::Interval) {
+        loop {
+            interval.tick().await;
+            if self.is_idle(coin_id).await {
+                return;
+            }
+        }
+    }
+
+    async fn set_book(&self, coin_id: &str, book: Option<OrderBook>) {
+        if let Some(tracked) = self.books.lock().await.get_mut(coin_id) {
+            tracked.book = book;
+        }
+    }
+
+    async fn is_idle(&self, coin_id: &str) -> bool {
+        self.books
+            .lock()
+            .await
+            .get(coin_id)
+            .is_none_or(|t| t.last_access.elapsed() > IDLE_TIMEOUT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    const RECORDING: &str = r#"{"coin_id": "BTC", "kind": "snapshot", "data": {"lastUpdateId": 100, "bids": [["99", "1"], ["98", "2"]], "asks": [["101", "1"], ["102", "3"]]}}
+
+{"coin_id": "btc", "kind": "snapshot", "data": {"lastUpdateId": 500, "bids": [], "asks": []}}
+{"coin_id": "btc", "kind": "diff", "data": {"U": 99, "u": 101, "b": [["99", "0"], ["97", "4"]], "a": []}}
+{"coin_id": "btc", "kind": "trade", "data": {}}
+{"coin_id": "btc", "kind": "diff", "data": {"U": 102, "u": 103, "b": [], "a": [["100.5", "2"]]}}
+"#;
+
+    fn write_recording(name: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(format!("depth-{}-{}.jsonl", name, std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn event(first_update_id: u64, final_update_id: u64, bids: Vec<[Decimal; 2]>) -> DepthEvent {
+        DepthEvent {
+            first_update_id,
+            final_update_id,
+            bids,
+            asks: vec![],
+        }
+    }
+
+    fn book_at(last_update_id: u64) -> OrderBook {
+        OrderBook::from_snapshot(DepthSnapshot {
+            last_update_id,
+            bids: vec![[dec!(99), dec!(1)], [dec!(98), dec!(2)]],
+            asks: vec![[dec!(101), dec!(1)]],
+        })
+    }
+
+    #[tokio::test]
+    async fn replay_keeps_the_first_snapshot_and_all_diffs() {
+        let path = write_recording("replay", RECORDING);
+        let source = FileReplaySource::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(source.snapshot("btc").await.unwrap().last_update_id, 100);
+
+        let mut events = source.subscribe("btc");
+        let first = events.next().await.unwrap().unwrap();
+        let second = events.next().await.unwrap().unwrap();
+        assert_eq!((first.first_update_id, first.final_update_id), (99, 101));
+        assert_eq!((second.first_update_id, second.final_update_id), (102, 103));
+
+        // The stream stays open after the recording ends
+        let after = tokio::time::timeout(Duration::from_millis(50), events.next()).await;
+        assert!(after.is_err());
+    }
+
+    #[tokio::test]
+    async fn replay_rejects_coins_without_a_recording() {
+        let path = write_recording("unknown", RECORDING);
+        let source = FileReplaySource::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(source.snapshot("eth").await.is_err());
+        assert!(matches!(source.subscribe("eth").next().await, Some(Err(_))));
+    }
+
+    #[test]
+    fn replay_reports_the_malformed_line() {
+        let first_line = RECORDING.lines().next().unwrap();
+        let path = write_recording("malformed", &format!("{}\n{{not json\n", first_line));
+        let error = FileReplaySource::open(&path).err().unwrap().to_string();
+        std::fs::remove_file(&path).unwrap();
+        assert!(error.contains(":2:"), "{}", error);
+    }
+
+    #[test]
+    fn apply_skips_events_the_snapshot_already_covers() {
+        let mut book = book_at(100);
+        assert_eq!(book.apply(&event(90, 100, vec![[dec!(99), dec!(0)]])), Ok(false));
+        assert_eq!(book.last_update_id, 100);
+        assert_eq!(book.bids.get(&dec!(99)), Some(&dec!(1)));
+    }
+
+    #[test]
+    fn apply_accepts_an_event_straddling_the_snapshot() {
+        let mut book = book_at(100);
+        assert_eq!(book.apply(&event(95, 105, vec![[dec!(99), dec!(0)], [dec!(97), dec!(4)]])), Ok(true));
+        assert_eq!(book.last_update_id, 105);
+        assert_eq!(book.bids.get(&dec!(99)), None); // Zero quantity removes the level
+        assert_eq!(book.bids.get(&dec!(97)), Some(&dec!(4)));
+
+        // Contiguous follow-up
+        assert_eq!(book.apply(&event(106, 110, vec![])), Ok(true));
+        assert_eq!(book.last_update_id, 110);
+    }
+
+    #[test]
+    fn apply_detects_a_gap() {
+        let mut book = book_at(100);
+        assert!(book.apply(&event(102, 104, vec![[dec!(50), dec!(1)]])).is_err());
+        // The book is left untouched so it can be rebuilt from a fresh snapshot
+        assert_eq!(book.last_update_id, 100);
+        assert_eq!(book.bids.get(&dec!(50)), None);
+    }
+
+    #[test]
+    fn view_lists_the_best_levels_first() {
+        let view = book_at(100).view("btc", 1);
+        assert_eq!(view.bids, vec![[dec!(99), dec!(1)]]);
+        assert_eq!(view.asks, vec![[dec!(101), dec!(1)]]);
+    }
+
+    #[tokio::test]
+    async fn cache_syncs_a_book_from_a_replay() {
+        let path = write_recording("cache", RECORDING);
+        let source = FileReplaySource::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let cache = DepthCache::new(Arc::new(source));
+
+        assert!(cache.book("BTC", 5).await.is_none()); // Starts syncing
+        let mut view = None;
+        for _ in 0..100 {
+            view = cache.book("btc", 5).await;
+            if view.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let view = view.expect("book never synced");
+        assert_eq!(view.last_update_id, 103);
+        assert_eq!(view.bids, vec![[dec!(98), dec!(2)], [dec!(97), dec!(4)]]);
+        assert_eq!(view.asks, vec![[dec!(100.5), dec!(2)], [dec!(101), dec!(1)], [dec!(102), dec!(3)]]);
+    }
+}
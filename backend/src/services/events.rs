@@ -0,0 +1,212 @@
+use crate::services::alerts::AlertCondition;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sqlx::{PgExecutor, PgPool};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::{broadcast, mpsc};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Events buffered per subscriber before the slowest one starts missing them
+const BUS_CAPACITY: usize = 4096;
+
+/// Something that happened in one component that others may react to
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DomainEvent {
+    OrderPlaced {
+        order_id: Uuid,
+        user_id: Uuid,
+        coin_id: String,
+        order_type: String, // buy / sell
+        order_mode: String, // limit / market
+        quantity: Decimal,
+        price: Decimal, // Limit price, or the price a market order was placed at
+    },
+    OrderFilled {
+        order_id: Uuid,
+        user_id: Uuid,
+        coin_id: String,
+        coin_symbol: String,
+        order_type: String,
+        quantity: Decimal,
+        price: Decimal,
+        total_amount: Decimal,
+        fee: Decimal,
+        balance: Decimal, // Wallet balance after settlement
+    },
+    OrderCancelled {
+        order_id: Uuid,
+        user_id: Uuid,
+        reason: String,
+    },
+    PriceTick {
+        event_time: i64, // Binance event time (ms) of the batch
+        prices: Arc<Vec<PriceUpdate>>,
+    },
+    StrategyStateChanged {
+        strategy_id: Uuid,
+        user_id: Uuid,
+        status: String, // running, stopped, completed, force_stopped
+    },
+    PositionClosed {
+        strategy_id: Uuid,
+        user_id: Uuid,
+        position_id: Uuid,
+        coin_id: String,
+        reason: String, // stop_loss, take_profit, force_exit
+        exit_price: Decimal,
+        profit: Decimal, // Realized over the whole position, partial sells included
+    },
+    PriceAlertTriggered {
+        alert_id: Uuid,
+        user_id: Uuid,
+        coin_id: String,
+        condition: AlertCondition,
+        price: Decimal,
+        value: Decimal, // What was observed: price, percent move, volume ratio or RSI
+        message: String,
+        note: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PriceUpdate {
+    pub coin_id: String,
+    pub price: Decimal,
+    pub open_price: Decimal,
+    pub volume_quote: Decimal,
+}
+
+impl DomainEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DomainEvent::OrderPlaced { .. } => "order_placed",
+            DomainEvent::OrderFilled { .. } => "order_filled",
+            DomainEvent::OrderCancelled { .. } => "order_cancelled",
+            DomainEvent::PriceTick { .. } => "price_tick",
+            DomainEvent::StrategyStateChanged { .. } => "strategy_state_changed",
+            DomainEvent::PositionClosed { .. } => "position_closed",
+            DomainEvent::PriceAlertTriggered { .. } => "price_alert_triggered",
+        }
+    }
+
+    pub fn user_id(&self) -> Option<Uuid> {
+        match self {
+            DomainEvent::OrderPlaced { user_id, .. }
+            | DomainEvent::OrderFilled { user_id, .. }
+            | DomainEvent::OrderCancelled { user_id, .. }
+            | DomainEvent::StrategyStateChanged { user_id, .. }
+            | DomainEvent::PositionClosed { user_id, .. }
+            | DomainEvent::PriceAlertTriggered { user_id, .. } => Some(*user_id),
+            DomainEvent::PriceTick { .. } => None,
+        }
+    }
+
+    /// Price ticks are too frequent (and too quickly stale) to be worth persisting
+    fn is_durable(&self) -> bool {
+        !matches!(self, DomainEvent::PriceTick { .. })
+    }
+}
+
+/// In-process pub/sub between the matching engine, settlement, automation and the
+/// realtime hub. With an outbox, every durable event is also appended to
+/// `event_outbox` (in publish order, on a background writer), where webhook delivery
+/// picks it up.
+pub struct EventBus {
+    tx: broadcast::Sender<DomainEvent>,
+    outbox: Option<mpsc::UnboundedSender<DomainEvent>>,
+    published: AtomicU64,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(BUS_CAPACITY);
+        Self {
+            tx,
+            outbox: None,
+            published: AtomicU64::new(0),
+        }
+    }
+
+    pub fn with_outbox(pool: PgPool) -> Self {
+        let (outbox_tx, mut outbox_rx) = mpsc::unbounded_channel::<DomainEvent>();
+        tokio::spawn(async move {
+            while let Some(event) = outbox_rx.recv().await {
+                if let Err(e) = append_to_outbox(&pool, &event).await {
+                    error!("❌ Failed to write {} to the event outbox: {}", event.name(), e);
+                }
+            }
+        });
+        info!("📮 Event outbox enabled");
+
+        Self {
+            outbox: Some(outbox_tx),
+            ..Self::new()
+        }
+    }
+
+    /// For events whose outbox row was already written in the caller's transaction
+    /// (see `append_to_outbox`), so a rolled-back change never announces itself
+    pub fn publish_committed(&self, event: DomainEvent) {
+        let _ = self.tx.send(event);
+        self.published.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn publish(&self, event: DomainEvent) {
+        if let Some(outbox) = &self.outbox {
+            if event.is_durable() {
+                let _ = outbox.send(event.clone());
+            }
+        }
+        // No subscribers yet (e.g. during startup) is fine
+        let _ = self.tx.send(event);
+        self.published.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<DomainEvent> {
+        self.tx.subscribe()
+    }
+
+    pub fn published(&self) -> u64 {
+        self.published.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Receives the next event, logging (rather than failing on) events a slow subscriber
+/// missed. `None` once the bus is gone.
+pub async fn next_event(
+    events: &mut broadcast::Receiver<DomainEvent>,
+    subscriber: &str,
+) -> Option<DomainEvent> {
+    loop {
+        match events.recv().await {
+            Ok(event) => return Some(event),
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                warn!("⚠️ {} fell behind the event bus, {} events missed", subscriber, missed);
+            }
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
+/// Writes `event` to the outbox on `executor`, typically the transaction making the change
+pub async fn append_to_outbox<'e, E: PgExecutor<'e>>(
+    executor: E,
+    event: &DomainEvent,
+) -> anyhow::Result<()> {
+    sqlx::query("INSERT INTO event_outbox (event_type, user_id, payload) VALUES ($1, $2, $3)")
+        .bind(event.name())
+        .bind(event.user_id())
+        .bind(serde_json::to_value(event)?)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
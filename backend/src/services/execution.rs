@@ -1,3 +1,4 @@
+use crate::services::events::{append_to_outbox, DomainEvent, EventBus};
 use rust_decimal::Decimal;
 use sqlx::{PgPool, Row};
 use tracing::{error, info};
@@ -7,7 +8,19 @@ const TRADING_FEE_RATE_NUM: i64 = 1;
 const TRADING_FEE_RATE_SCALE: u32 = 3; 
 // 0.001
 
-pub async fn execute_order(pool: &PgPool, order_id: Uuid, execution_price: Decimal) -> anyhow::Result<()> {
+/// Fee charged on a fill of `total_amount` (shared with paper fills so both modes net out the same)
+pub fn trading_fee(total_amount: Decimal) -> Decimal {
+    total_amount * Decimal::new(TRADING_FEE_RATE_NUM, TRADING_FEE_RATE_SCALE)
+}
+
+/// Settles a filled order against the user's wallet and announces it as `OrderFilled`
+/// (outbox row written in the same transaction)
+pub async fn execute_order(
+    pool: &PgPool,
+    events: &EventBus,
+    order_id: Uuid,
+    execution_price: Decimal,
+) -> anyhow::Result<()> {
     let mut tx = pool.begin().await?;
 
     // 1. Fetch Order (Runtime Query)
@@ -37,8 +50,7 @@ pub async fn execute_order(pool: &PgPool, order_id: Uuid, execution_price: Decim
     let total_amount = execution_price * quantity;
     
     // Fee Calculation
-    let fee_rate = Decimal::new(TRADING_FEE_RATE_NUM, TRADING_FEE_RATE_SCALE);
-    let trading_fee = total_amount * fee_rate; 
+    let trading_fee = trading_fee(total_amount);
 
     // Ensure Profile Exists
     let profile_row = sqlx::query(
@@ -184,16 +196,33 @@ pub async fn execute_order(pool: &PgPool, order_id: Uuid, execution_price: Decim
     )
     .bind(user_id)
     .bind(order_id)
-    .bind(order_type)
-    .bind(coin_id)
-    .bind(coin_symbol)
+    .bind(&order_type)
+    .bind(&coin_id)
+    .bind(&coin_symbol)
     .bind(quantity)
     .bind(execution_price)
     .bind(total_amount)
     .execute(&mut *tx)
     .await?;
 
+    let filled = DomainEvent::OrderFilled {
+        order_id,
+        user_id,
+        coin_id,
+        coin_symbol,
+        order_type,
+        quantity,
+        price: execution_price,
+        total_amount,
+        fee: trading_fee,
+        balance,
+    };
+    // Recorded with the settlement itself, so webhooks see exactly the fills that happened
+    append_to_outbox(&mut *tx, &filled).await?;
+
     tx.commit().await?;
 
+    events.publish_committed(filled);
+
     Ok(())
 }
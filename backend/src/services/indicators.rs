@@ -0,0 +1,641 @@
+// Technical indicators shared by the `/api/indicators` handlers, the automation engine
+// and the backtester. Every function returns a series aligned with its input: index `i`
+// holds the reading at input `i`, or `None` while there isn't enough history yet.
+
+use crate::models::{Candle, CandleIndicatorResponse};
+use crate::services::candles::Interval;
+use rust_decimal::{Decimal, MathematicalOps};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// Cached results are served for this long; the live candle changes every tick anyway
+const CACHE_TTL: Duration = Duration::from_secs(5);
+/// Expired entries are swept once the cache grows past this
+const CACHE_SWEEP_SIZE: usize = 1000;
+
+pub type Series = Vec<Option<Decimal>>;
+
+/// Last reading of a series, if it has warmed up
+pub fn latest(series: &[Option<Decimal>]) -> Option<Decimal> {
+    series.last().copied().flatten()
+}
+
+pub fn sma(values: &[Decimal], period: usize) -> Series {
+    let mut out = vec![None; values.len()];
+    if period == 0 || values.len() < period {
+        return out;
+    }
+
+    let divisor = Decimal::from(period);
+    let mut sum: Decimal = values[..period].iter().sum();
+    out[period - 1] = Some(sum / divisor);
+    for i in period..values.len() {
+        sum += values[i] - values[i - period];
+        out[i] = Some(sum / divisor);
+    }
+    out
+}
+
+/// EMA seeded with the SMA of the first `period` values
+pub fn ema(values: &[Decimal], period: usize) -> Series {
+    let wrapped: Series = values.iter().copied().map(Some).collect();
+    ema_of(&wrapped, period)
+}
+
+/// EMA over a series that may start with `None` (e.g. the MACD line)
+fn ema_of(series: &[Option<Decimal>], period: usize) -> Series {
+    let mut out = vec![None; series.len()];
+    let Some(start) = series.iter().position(Option::is_some) else {
+        return out;
+    };
+    if period == 0 || series.len() - start < period {
+        return out;
+    }
+
+    let multiplier = Decimal::from(2) / Decimal::from(period + 1);
+    let seed_end = start + period - 1;
+    let mut value = series[start..=seed_end].iter().flatten().sum::<Decimal>() / Decimal::from(period);
+    out[seed_end] = Some(value);
+    for i in seed_end + 1..series.len() {
+        if let Some(x) = series[i] {
+            value = (x - value) * multiplier + value;
+        }
+        out[i] = Some(value);
+    }
+    out
+}
+
+/// Linearly weighted moving average (newest value has weight `period`)
+pub fn wma(values: &[Decimal], period: usize) -> Series {
+    let mut out = vec![None; values.len()];
+    if period == 0 || values.len() < period {
+        return out;
+    }
+
+    let weight_sum = Decimal::from(period * (period + 1) / 2);
+    for i in period - 1..values.len() {
+        let window = &values[i + 1 - period..=i];
+        let weighted: Decimal = window
+            .iter()
+            .enumerate()
+            .map(|(w, v)| *v * Decimal::from(w + 1))
+            .sum();
+        out[i] = Some(weighted / weight_sum);
+    }
+    out
+}
+
+/// RSI with Wilder smoothing; the first reading is at index `period`
+pub fn rsi(values: &[Decimal], period: usize) -> Series {
+    let mut out = vec![None; values.len()];
+    if period == 0 || values.len() <= period {
+        return out;
+    }
+
+    let p = Decimal::from(period);
+    let split = |change: Decimal| {
+        if change > Decimal::ZERO {
+            (change, Decimal::ZERO)
+        } else {
+            (Decimal::ZERO, change.abs())
+        }
+    };
+    let to_rsi = |avg_gain: Decimal, avg_loss: Decimal| {
+        if avg_loss == Decimal::ZERO {
+            Decimal::from(100)
+        } else {
+            Decimal::from(100) - Decimal::from(100) / (Decimal::ONE + avg_gain / avg_loss)
+        }
+    };
+
+    let (mut avg_gain, mut avg_loss) = (Decimal::ZERO, Decimal::ZERO);
+    for i in 1..=period {
+        let (gain, loss) = split(values[i] - values[i - 1]);
+        avg_gain += gain;
+        avg_loss += loss;
+    }
+    avg_gain /= p;
+    avg_loss /= p;
+    out[period] = Some(to_rsi(avg_gain, avg_loss));
+
+    for i in period + 1..values.len() {
+        let (gain, loss) = split(values[i] - values[i - 1]);
+        avg_gain = (avg_gain * (p - Decimal::ONE) + gain) / p;
+        avg_loss = (avg_loss * (p - Decimal::ONE) + loss) / p;
+        out[i] = Some(to_rsi(avg_gain, avg_loss));
+    }
+    out
+}
+
+pub struct Macd {
+    pub line: Series,
+    pub signal: Series,
+    pub histogram: Series,
+}
+
+pub fn macd(values: &[Decimal], fast: usize, slow: usize, signal: usize) -> Macd {
+    let fast_ema = ema(values, fast);
+    let slow_ema = ema(values, slow);
+    let line: Series = fast_ema
+        .iter()
+        .zip(&slow_ema)
+        .map(|(f, s)| Some((*f)? - (*s)?))
+        .collect();
+    let signal = ema_of(&line, signal);
+    let histogram = line
+        .iter()
+        .zip(&signal)
+        .map(|(l, s)| Some((*l)? - (*s)?))
+        .collect();
+
+    Macd {
+        line,
+        signal,
+        histogram,
+    }
+}
+
+pub struct Bands {
+    pub upper: Series,
+    pub middle: Series,
+    pub lower: Series,
+}
+
+/// Bollinger Bands: SMA ± `std_dev` population standard deviations
+pub fn bollinger(values: &[Decimal], period: usize, std_dev: Decimal) -> Bands {
+    let middle = sma(values, period);
+    let mut upper = vec![None; values.len()];
+    let mut lower = vec![None; values.len()];
+
+    for (i, mean) in middle.iter().enumerate() {
+        let Some(mean) = *mean else { continue };
+        let variance = values[i + 1 - period..=i]
+            .iter()
+            .map(|v| (*v - mean) * (*v - mean))
+            .sum::<Decimal>()
+            / Decimal::from(period);
+        let width = variance.sqrt().unwrap_or(Decimal::ZERO) * std_dev;
+        upper[i] = Some(mean + width);
+        lower[i] = Some(mean - width);
+    }
+
+    Bands {
+        upper,
+        middle,
+        lower,
+    }
+}
+
+/// True range per candle; the first candle has no previous close, so it's `None`
+fn true_range(candles: &[Candle]) -> Series {
+    std::iter::once(None)
+        .chain(candles.windows(2).map(|w| {
+            let prev_close = w[0].close;
+            let c = &w[1];
+            Some(
+                (c.high - c.low)
+                    .max((c.high - prev_close).abs())
+                    .max((c.low - prev_close).abs()),
+            )
+        }))
+        .take(candles.len())
+        .collect()
+}
+
+/// Wilder's running average over a series starting with `None`s
+fn wilder(series: &[Option<Decimal>], period: usize) -> Series {
+    let mut out = vec![None; series.len()];
+    let Some(start) = series.iter().position(Option::is_some) else {
+        return out;
+    };
+    if period == 0 || series.len() - start < period {
+        return out;
+    }
+
+    let p = Decimal::from(period);
+    let seed_end = start + period - 1;
+    let mut value = series[start..=seed_end].iter().flatten().sum::<Decimal>() / p;
+    out[seed_end] = Some(value);
+    for i in seed_end + 1..series.len() {
+        if let Some(x) = series[i] {
+            value = (value * (p - Decimal::ONE) + x) / p;
+        }
+        out[i] = Some(value);
+    }
+    out
+}
+
+/// Average True Range (Wilder); the first reading is at index `period`
+pub fn atr(candles: &[Candle], period: usize) -> Series {
+    wilder(&true_range(candles), period)
+}
+
+pub struct Stochastic {
+    pub k: Series,
+    pub d: Series,
+}
+
+/// %K over `k_period` highs/lows, %D as the SMA of %K
+pub fn stochastic(candles: &[Candle], k_period: usize, d_period: usize) -> Stochastic {
+    let mut k = vec![None; candles.len()];
+    if k_period > 0 && candles.len() >= k_period {
+        for i in k_period - 1..candles.len() {
+            let window = &candles[i + 1 - k_period..=i];
+            let highest = window.iter().map(|c| c.high).max().unwrap_or_default();
+            let lowest = window.iter().map(|c| c.low).min().unwrap_or_default();
+            k[i] = Some(if highest > lowest {
+                (candles[i].close - lowest) / (highest - lowest) * Decimal::from(100)
+            } else {
+                Decimal::from(50)
+            });
+        }
+    }
+
+    let mut d = vec![None; candles.len()];
+    if d_period > 0 {
+        for i in 0..candles.len() {
+            if i + 1 < d_period {
+                continue;
+            }
+            let window: Option<Vec<Decimal>> = k[i + 1 - d_period..=i].iter().copied().collect();
+            if let Some(window) = window {
+                d[i] = Some(window.iter().sum::<Decimal>() / Decimal::from(d_period));
+            }
+        }
+    }
+
+    Stochastic { k, d }
+}
+
+pub struct Adx {
+    pub adx: Series,
+    pub plus_di: Series,
+    pub minus_di: Series,
+}
+
+/// Average Directional Index with Wilder smoothing. DI readings start at index `period`,
+/// ADX at `2 * period - 1`.
+pub fn adx(candles: &[Candle], period: usize) -> Adx {
+    let len = candles.len();
+    let mut plus_dm = vec![None; len];
+    let mut minus_dm = vec![None; len];
+    for i in 1..len {
+        let up = candles[i].high - candles[i - 1].high;
+        let down = candles[i - 1].low - candles[i].low;
+        plus_dm[i] = Some(if up > down && up > Decimal::ZERO { up } else { Decimal::ZERO });
+        minus_dm[i] = Some(if down > up && down > Decimal::ZERO { down } else { Decimal::ZERO });
+    }
+
+    // Ratios of Wilder averages equal ratios of Wilder sums, so averages are fine here
+    let tr = wilder(&true_range(candles), period);
+    let plus = wilder(&plus_dm, period);
+    let minus = wilder(&minus_dm, period);
+
+    let di = |dm: &Series| -> Series {
+        dm.iter()
+            .zip(&tr)
+            .map(|(dm, tr)| {
+                let (dm, tr) = ((*dm)?, (*tr)?);
+                Some(if tr > Decimal::ZERO { dm / tr * Decimal::from(100) } else { Decimal::ZERO })
+            })
+            .collect()
+    };
+    let plus_di = di(&plus);
+    let minus_di = di(&minus);
+
+    let dx: Series = plus_di
+        .iter()
+        .zip(&minus_di)
+        .map(|(p, m)| {
+            let (p, m) = ((*p)?, (*m)?);
+            Some(if p + m > Decimal::ZERO {
+                (p - m).abs() / (p + m) * Decimal::from(100)
+            } else {
+                Decimal::ZERO
+            })
+        })
+        .collect();
+
+    Adx {
+        adx: wilder(&dx, period),
+        plus_di,
+        minus_di,
+    }
+}
+
+/// On-Balance Volume, starting at zero
+pub fn obv(candles: &[Candle]) -> Series {
+    let mut out = Vec::with_capacity(candles.len());
+    let mut total = Decimal::ZERO;
+    for (i, c) in candles.iter().enumerate() {
+        if i > 0 {
+            let prev_close = candles[i - 1].close;
+            if c.close > prev_close {
+                total += c.volume;
+            } else if c.close < prev_close {
+                total -= c.volume;
+            }
+        }
+        out.push(Some(total));
+    }
+    out
+}
+
+/// Cumulative VWAP of the typical price over the whole input
+pub fn vwap(candles: &[Candle]) -> Series {
+    let mut cumulative_pv = Decimal::ZERO;
+    let mut cumulative_volume = Decimal::ZERO;
+    candles
+        .iter()
+        .map(|c| {
+            let typical = (c.high + c.low + c.close) / Decimal::from(3);
+            cumulative_pv += typical * c.volume;
+            cumulative_volume += c.volume;
+            (cumulative_volume > Decimal::ZERO).then(|| cumulative_pv / cumulative_volume)
+        })
+        .collect()
+}
+
+pub struct Ichimoku {
+    pub tenkan: Series,
+    pub kijun: Series,
+    pub senkou_a: Series,
+    pub senkou_b: Series,
+    pub chikou: Series,
+}
+
+/// Ichimoku cloud. The spans are displaced `kijun_period` candles forward and the lagging
+/// span `kijun_period` back, both clipped to the input range.
+pub fn ichimoku(
+    candles: &[Candle],
+    tenkan_period: usize,
+    kijun_period: usize,
+    senkou_b_period: usize,
+) -> Ichimoku {
+    let midpoint = |period: usize| -> Series {
+        (0..candles.len())
+            .map(|i| {
+                if period == 0 || i + 1 < period {
+                    return None;
+                }
+                let window = &candles[i + 1 - period..=i];
+                let high = window.iter().map(|c| c.high).max()?;
+                let low = window.iter().map(|c| c.low).min()?;
+                Some((high + low) / Decimal::from(2))
+            })
+            .collect()
+    };
+
+    let tenkan = midpoint(tenkan_period);
+    let kijun = midpoint(kijun_period);
+    let base_b = midpoint(senkou_b_period);
+
+    let shift = kijun_period;
+    let senkou_a = (0..candles.len())
+        .map(|i| {
+            let j = i.checked_sub(shift)?;
+            Some((tenkan[j]? + kijun[j]?) / Decimal::from(2))
+        })
+        .collect();
+    let senkou_b = (0..candles.len())
+        .map(|i| base_b[i.checked_sub(shift)?])
+        .collect();
+    let chikou = (0..candles.len())
+        .map(|i| candles.get(i + shift).map(|c| c.close))
+        .collect();
+
+    Ichimoku {
+        tenkan,
+        kijun,
+        senkou_a,
+        senkou_b,
+        chikou,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IndicatorKey {
+    pub name: String,
+    pub coin_id: String,
+    pub interval: Interval,
+    pub period: Option<usize>,
+    pub limit: usize,
+}
+
+/// Short-lived cache of `GET /api/indicators/:name` results per (indicator, coin, interval, params)
+#[derive(Default)]
+pub struct IndicatorCache {
+    entries: Mutex<HashMap<IndicatorKey, (Instant, Arc<CandleIndicatorResponse>)>>,
+}
+
+impl IndicatorCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn get(&self, key: &IndicatorKey) -> Option<Arc<CandleIndicatorResponse>> {
+        let entries = self.entries.lock().await;
+        entries
+            .get(key)
+            .filter(|(at, _)| at.elapsed() < CACHE_TTL)
+            .map(|(_, response)| response.clone())
+    }
+
+    pub async fn insert(&self, key: IndicatorKey, response: Arc<CandleIndicatorResponse>) {
+        let mut entries = self.entries.lock().await;
+        if entries.len() >= CACHE_SWEEP_SIZE {
+            entries.retain(|_, (at, _)| at.elapsed() < CACHE_TTL);
+        }
+        entries.insert(key, (Instant::now(), response));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn values(raw: &[i64]) -> Vec<Decimal> {
+        raw.iter().copied().map(Decimal::from).collect()
+    }
+
+    fn candle(high: Decimal, low: Decimal, close: Decimal, volume: Decimal) -> Candle {
+        Candle {
+            open_time: 0,
+            open: close,
+            high,
+            low,
+            close,
+            volume,
+        }
+    }
+
+    /// Five candles with a range expansion and a gap up at the end
+    fn sample_candles() -> Vec<Candle> {
+        vec![
+            candle(dec!(10), dec!(8), dec!(9), dec!(1)),
+            candle(dec!(11), dec!(9), dec!(10), dec!(2)),
+            candle(dec!(13), dec!(10), dec!(12), dec!(3)),
+            candle(dec!(12), dec!(8), dec!(9), dec!(4)),
+            candle(dec!(15), dec!(14), dec!(15), dec!(5)),
+        ]
+    }
+
+    /// Steady uptrend: every candle is one higher than the last
+    fn uptrend(len: usize) -> Vec<Candle> {
+        (0..len)
+            .map(|i| {
+                let i = Decimal::from(i);
+                candle(dec!(10) + i, dec!(8) + i, dec!(9) + i, dec!(1))
+            })
+            .collect()
+    }
+
+    fn assert_close(actual: Option<Decimal>, expected: Decimal) {
+        let actual = actual.expect("indicator has no reading");
+        assert!(
+            (actual - expected).abs() < dec!(0.000000001),
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn sma_averages_the_trailing_window() {
+        assert_eq!(
+            sma(&values(&[1, 2, 3, 4, 5]), 3),
+            vec![None, None, Some(dec!(2)), Some(dec!(3)), Some(dec!(4))]
+        );
+        assert_eq!(sma(&values(&[1, 2]), 3), vec![None, None]);
+        assert_eq!(sma(&values(&[1, 2]), 0), vec![None, None]);
+    }
+
+    #[test]
+    fn ema_is_seeded_with_the_sma() {
+        assert_eq!(
+            ema(&values(&[2, 4, 6, 8, 12]), 3),
+            vec![None, None, Some(dec!(4)), Some(dec!(6)), Some(dec!(9))]
+        );
+    }
+
+    #[test]
+    fn wma_weights_the_newest_value_most() {
+        assert_eq!(
+            wma(&values(&[6, 3, 9, 12]), 3),
+            vec![None, None, Some(dec!(6.5)), Some(dec!(9.5))]
+        );
+    }
+
+    #[test]
+    fn rsi_uses_wilder_smoothing() {
+        let series = rsi(&values(&[1, 2, 3, 2, 3]), 2);
+        assert_eq!(series[..2], [None, None]);
+        assert_eq!(series[2], Some(dec!(100))); // No losses yet
+        assert_close(series[3], dec!(50));
+        assert_close(series[4], dec!(75));
+    }
+
+    #[test]
+    fn macd_signal_starts_once_the_line_has_warmed_up() {
+        let result = macd(&values(&[2, 4, 6, 8, 12]), 2, 3, 2);
+        assert_eq!(result.line[..2], [None, None]);
+        assert_close(result.line[2], dec!(1));
+        assert_close(result.line[3], dec!(1));
+        assert_close(result.line[4], dec!(4) / dec!(3));
+
+        assert_eq!(result.signal[2], None);
+        assert_close(result.signal[3], dec!(1));
+        assert_close(result.signal[4], dec!(11) / dec!(9));
+        assert_close(result.histogram[4], dec!(1) / dec!(9));
+    }
+
+    #[test]
+    fn bollinger_uses_population_standard_deviation() {
+        let bands = bollinger(&values(&[2, 4, 4, 4, 5, 5, 7, 9]), 8, dec!(2));
+        assert_close(bands.middle[7], dec!(5));
+        assert_close(bands.upper[7], dec!(9));
+        assert_close(bands.lower[7], dec!(1));
+        assert_eq!(bands.upper[6], None);
+    }
+
+    #[test]
+    fn atr_includes_gaps_from_the_previous_close() {
+        // True ranges: -, 2, 3, 4, 6 (the last candle gaps up from 9 to a 14-15 range)
+        let series = atr(&sample_candles(), 2);
+        assert_eq!(series[..2], [None, None]);
+        assert_eq!(series[2], Some(dec!(2.5)));
+        assert_eq!(series[3], Some(dec!(3.25)));
+        assert_eq!(series[4], Some(dec!(4.625)));
+    }
+
+    #[test]
+    fn stochastic_k_and_d() {
+        let result = stochastic(&sample_candles(), 3, 2);
+        assert_eq!(result.k, vec![None, None, Some(dec!(80)), Some(dec!(20)), Some(dec!(100))]);
+        assert_eq!(result.d, vec![None, None, None, Some(dec!(50)), Some(dec!(60))]);
+    }
+
+    #[test]
+    fn stochastic_of_a_flat_range_is_fifty() {
+        let flat = vec![candle(dec!(5), dec!(5), dec!(5), dec!(1)); 3];
+        assert_eq!(stochastic(&flat, 2, 1).k[2], Some(dec!(50)));
+    }
+
+    #[test]
+    fn adx_of_a_steady_uptrend() {
+        let result = adx(&uptrend(7), 3);
+        assert_eq!(result.plus_di[2], None);
+        assert_eq!(result.plus_di[3], Some(dec!(50)));
+        assert_eq!(result.minus_di[3], Some(dec!(0)));
+        assert_eq!(result.adx[4], None);
+        assert_eq!(result.adx[5], Some(dec!(100)));
+        assert_eq!(result.adx[6], Some(dec!(100)));
+    }
+
+    #[test]
+    fn obv_adds_up_volume_on_up_closes_and_subtracts_on_down_closes() {
+        let mut candles = sample_candles();
+        candles.push(candle(dec!(16), dec!(14), dec!(15), dec!(7))); // Unchanged close
+        let expected: Series = values(&[0, 2, 5, 1, 6, 6]).into_iter().map(Some).collect();
+        assert_eq!(obv(&candles), expected);
+    }
+
+    #[test]
+    fn vwap_weights_the_typical_price_by_volume() {
+        let candles = vec![
+            candle(dec!(10), dec!(10), dec!(10), dec!(0)),
+            candle(dec!(12), dec!(6), dec!(9), dec!(1)),
+            candle(dec!(15), dec!(9), dec!(12), dec!(2)),
+        ];
+        assert_eq!(vwap(&candles), vec![None, Some(dec!(9)), Some(dec!(11))]);
+    }
+
+    #[test]
+    fn ichimoku_displaces_the_spans() {
+        let result = ichimoku(&uptrend(8), 2, 3, 4);
+        assert_eq!(result.tenkan[0], None);
+        assert_eq!(result.tenkan[1], Some(dec!(9.5)));
+        assert_eq!(result.kijun[2], Some(dec!(10)));
+
+        // Spans are shifted forward by the kijun period (3)
+        assert_eq!(result.senkou_a[4], None);
+        assert_eq!(result.senkou_a[5], Some(dec!(10.25)));
+        assert_eq!(result.senkou_b[5], None);
+        assert_eq!(result.senkou_b[6], Some(dec!(10.5)));
+
+        // The lagging span is the close shifted back, clipped at the end
+        assert_eq!(result.chikou[0], Some(dec!(12)));
+        assert_eq!(result.chikou[4], Some(dec!(16)));
+        assert_eq!(result.chikou[5], None);
+    }
+
+    #[test]
+    fn latest_is_the_last_reading() {
+        assert_eq!(latest(&[None, Some(dec!(1)), Some(dec!(2))]), Some(dec!(2)));
+        assert_eq!(latest(&[Some(dec!(1)), None]), None);
+        assert_eq!(latest(&[]), None);
+    }
+}
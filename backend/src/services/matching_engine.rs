@@ -1,22 +1,75 @@
+use crate::services::alerts::{AlertBook, PriceMove};
+use crate::services::candles::{CandleAggregator, Tick};
+use crate::services::depth::{DepthCache, DepthSource};
+use crate::services::events::{next_event, DomainEvent, EventBus, PriceUpdate};
+use crate::services::ticker_feed::{
+    BinanceTicker, TickerEvent, TickerRecorder, TickerSource, FEED_STALE_SECS,
+};
+use chrono::{DateTime, Utc};
 use futures::StreamExt;
 use rust_decimal::Decimal;
-use serde::Deserialize;
+use serde::Serialize;
 use sqlx::PgPool;
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
-use tokio::sync::Mutex;
-use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
-use tracing::{error, info};
-use url::Url;
+use std::time::Instant;
+use tokio::sync::{mpsc, Mutex};
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
+/// A coin's price is stale when it hasn't ticked for this long (quiet pairs drop out of
+/// the mini-ticker stream, so this is looser than the feed check)
+const STALE_PRICE_SECS: i64 = 120;
+
 #[derive(Clone)]
 pub struct MatchingEngine {
     pool: PgPool,
     orders: Arc<Mutex<HashMap<String, Vec<LimitOrder>>>>, // CoinID -> Orders
-    prices: Arc<Mutex<HashMap<String, Decimal>>>,         // CoinID -> Latest Price
+    prices: Arc<Mutex<HashMap<String, PriceEntry>>>,      // CoinID -> Latest Price
     ticker_data: Arc<Mutex<HashMap<String, TickerData>>>, // CoinID -> Volume & Price Data
+    candles: CandleAggregator,                             // OHLCV built from the ticker stream
+    depth: DepthCache,                                     // Local L2 books from the diff-depth stream
+    alerts: AlertBook,                                     // Users' price alerts, checked every batch
+    ticker_source: Arc<dyn TickerSource>,                  // Live Binance stream or a recording
+    recorder: Option<Arc<TickerRecorder>>,                 // Writes every batch to disk when enabled
+    feed: Arc<Mutex<FeedState>>,                           // Ticker connection bookkeeping
+    events: Arc<EventBus>,                                 // Price ticks, placed orders and fills
+    settlements: mpsc::UnboundedSender<Settlement>,       // Matched orders, settled in order
+    settlement_rx: Arc<Mutex<Option<mpsc::UnboundedReceiver<Settlement>>>>, // Taken by start()
+}
+
+/// A matched limit order and the price it crossed at
+type Settlement = (LimitOrder, Decimal);
+
+#[derive(Debug, Clone, Copy)]
+struct PriceEntry {
+    price: Decimal,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default)]
+struct FeedState {
+    connected: bool,
+    connected_since: Option<DateTime<Utc>>,
+    last_batch_at: Option<DateTime<Utc>>,
+    connects: u64,
+    batches: u64,
+    last_error: Option<String>,
+}
+
+/// Liveness of the ticker feed, as reported by `/health`
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedHealth {
+    pub healthy: bool, // Connected and a batch arrived within FEED_STALE_SECS
+    pub connected: bool,
+    pub connected_since: Option<DateTime<Utc>>,
+    pub last_batch_at: Option<DateTime<Utc>>,
+    pub seconds_since_last_batch: Option<i64>,
+    pub reconnects: u64,
+    pub batches: u64,
+    pub last_error: Option<String>,
+    pub tracked_prices: usize,
+    pub stale_prices: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -24,6 +77,7 @@ pub struct TickerData {
     pub price: Decimal,
     pub volume_quote: Decimal, // 'q' from Binance (USDT volume)
     pub open_price: Decimal,
+    pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone)]
@@ -38,21 +92,29 @@ struct LimitOrder {
     price: Decimal,
 }
 
-#[derive(Debug, Deserialize)]
-struct BinanceTicker {
-    s: String, // Symbol
-    c: String, // Close price
-    q: String, // Quote Asset Volume
-    o: String, // Open price
-}
-
 impl MatchingEngine {
-    pub fn new(pool: PgPool) -> Self {
+    pub fn new(
+        pool: PgPool,
+        depth_source: Arc<dyn DepthSource>,
+        ticker_source: Arc<dyn TickerSource>,
+        recorder: Option<Arc<TickerRecorder>>,
+        events: Arc<EventBus>,
+    ) -> Self {
+        let (settlements, settlement_rx) = mpsc::unbounded_channel();
         Self {
-            pool,
+            pool: pool.clone(),
             orders: Arc::new(Mutex::new(HashMap::new())),
             prices: Arc::new(Mutex::new(HashMap::new())),
             ticker_data: Arc::new(Mutex::new(HashMap::new())),
+            candles: CandleAggregator::new(pool.clone()),
+            depth: DepthCache::new(depth_source),
+            alerts: AlertBook::new(pool.clone(), events.clone()),
+            ticker_source,
+            recorder,
+            feed: Arc::new(Mutex::new(FeedState::default())),
+            events,
+            settlements,
+            settlement_rx: Arc::new(Mutex::new(Some(settlement_rx))),
         }
     }
 
@@ -65,121 +127,194 @@ impl MatchingEngine {
             return;
         }
 
-        // 2. Connect to Binance WebSocket
-        let orders_clone = self.orders.clone();
-        let prices_clone = self.prices.clone(); // Clone for WebSocket task
-        let ticker_data_clone = self.ticker_data.clone();
-        let pool_clone = self.pool.clone();
+        if let Err(e) = self.alerts.load().await {
+            error!("Failed to load price alerts: {}", e);
+        }
+
+        self.candles.start().await;
+
+        // Matched orders are settled one at a time, in match order
+        if let Some(mut settlement_rx) = self.settlement_rx.lock().await.take() {
+            let pool = self.pool.clone();
+            let events = self.events.clone();
+            tokio::spawn(async move {
+                while let Some((order, execution_price)) = settlement_rx.recv().await {
+                    Self::execute_order(&pool, &events, order, execution_price).await;
+                }
+            });
+        }
+
+        // Cancelled orders leave the book so they can't fill afterwards
+        let engine = self.clone();
+        let mut bus_events = self.events.subscribe();
+        tokio::spawn(async move {
+            while let Some(event) = next_event(&mut bus_events, "Matching engine").await {
+                if let DomainEvent::OrderCancelled { order_id, .. } = event {
+                    engine.remove_order(&order_id.to_string()).await;
+                }
+            }
+        });
 
+        // 2. Consume the ticker feed (live Binance stream or a recording)
+        let engine = self.clone();
         tokio::spawn(async move {
-            loop {
-                // Binance Mini Ticker Stream for ALL symbols
-                let url = Url::parse("wss://stream.binance.com:9443/ws/!miniTicker@arr").unwrap();
-
-                info!("Connecting to Binance WebSocket...");
-                match connect_async(url).await {
-                    Ok((ws_stream, _)) => {
-                        info!("✅ Connected to Binance WebSocket. Listening for price updates...");
-                        let (_, mut read) = ws_stream.split();
-
-                        while let Some(message) = read.next().await {
-                            if let Ok(Message::Text(text)) = message {
-                                let start = Instant::now();
-                                if let Ok(tickers) =
-                                    serde_json::from_str::<Vec<BinanceTicker>>(&text)
-                                {
-                                    let mut orders = orders_clone.lock().await;
-
-                                    // Batch update ticker data for efficiency
-                                    let mut new_ticker_data = Vec::with_capacity(tickers.len());
-
-                                    for ticker in tickers {
-                                        let symbol = ticker.s.to_lowercase();
-                                        // Only care about USDT pairs
-                                        if !symbol.ends_with("usdt") {
-                                            continue;
-                                        }
-
-                                        let coin_id = symbol.replace("usdt", "");
-
-                                        if let (Ok(current_price), Ok(volume_quote), Ok(open_price)) = (
-                                            ticker.c.parse::<Decimal>(),
-                                            ticker.q.parse::<Decimal>(),
-                                            ticker.o.parse::<Decimal>(),
-                                        ) {
-                                            // Store data for analysis
-                                            new_ticker_data.push((
-                                                coin_id.clone(),
-                                                TickerData {
-                                                    price: current_price,
-                                                    volume_quote,
-                                                    open_price,
-                                                },
-                                            ));
-
-                                            // Update Price Store (Legacy support)
-                                            {
-                                                let mut prices_map = prices_clone.lock().await;
-                                                prices_map.insert(coin_id.clone(), current_price);
-                                            }
-
-                                            if let Some(coin_orders) = orders.get_mut(&coin_id) {
-                                                // ⚡ CRITICAL SECTION: MATCHING LOGIC
-                                                let mut executed_indices = Vec::new();
-
-                                                for (i, order) in coin_orders.iter().enumerate() {
-                                                    let is_match = match order.order_type.as_str() {
-                                                        "buy" => current_price <= order.price,
-                                                        "sell" => current_price >= order.price,
-                                                        _ => false,
-                                                    };
-
-                                                    if is_match {
-                                                        info!("⚡ MATCHED: Order {} {} @ {} (Market: {}) in {:?}", 
-                                                            order.id, order.order_type, order.price, current_price, start.elapsed());
-
-                                                        // Execute async (fire and forget from matching loop perspective)
-                                                        let p_clone = pool_clone.clone();
-                                                        let o_clone = order.clone();
-                                                        let exec_price = current_price;
-
-                                                        tokio::spawn(async move {
-                                                            Self::execute_order(
-                                                                p_clone, o_clone, exec_price,
-                                                            )
-                                                            .await;
-                                                        });
-
-                                                        executed_indices.push(i);
-                                                    }
-                                                }
-
-                                                // Remove executed orders (reverse to safely remove)
-                                                for &i in executed_indices.iter().rev() {
-                                                    coin_orders.remove(i);
-                                                }
-                                            }
-                                        }
-                                    }
-
-                                    // Update Ticker Data Store
-                                    if !new_ticker_data.is_empty() {
-                                        let mut td_map = ticker_data_clone.lock().await;
-                                        for (cid, data) in new_ticker_data {
-                                            td_map.insert(cid, data);
-                                        }
-                                    }
-                                }
+            let mut events = engine.ticker_source.stream();
+            while let Some(event) = events.next().await {
+                match event {
+                    TickerEvent::Connected => {
+                        let mut feed = engine.feed.lock().await;
+                        feed.connected = true;
+                        feed.connected_since = Some(Utc::now());
+                        feed.connects += 1;
+                    }
+                    TickerEvent::Disconnected(reason) => {
+                        let mut feed = engine.feed.lock().await;
+                        feed.connected = false;
+                        feed.connected_since = None;
+                        feed.last_error = Some(reason);
+                    }
+                    TickerEvent::Batch(batch) => {
+                        if let Some(recorder) = &engine.recorder {
+                            recorder.record(&batch);
+                        }
+                        {
+                            let mut feed = engine.feed.lock().await;
+                            feed.last_batch_at = Some(Utc::now());
+                            feed.batches += 1;
+                        }
+                        engine.process_batch(batch.tickers).await;
+                    }
+                }
+            }
+            engine.feed.lock().await.connected = false;
+            warn!("⏹️ Ticker feed ended, prices will go stale");
+        });
+    }
+
+    /// Updates prices and ticker data, fills crossed limit orders, feeds the candle
+    /// aggregator and checks price alerts for one ticker batch
+    async fn process_batch(&self, tickers: Vec<BinanceTicker>) {
+        let start = Instant::now();
+        let now = Utc::now();
+        let mut orders = self.orders.lock().await;
+
+        // Batch update ticker data for efficiency
+        let mut new_ticker_data = Vec::with_capacity(tickers.len());
+        let mut ticks = Vec::with_capacity(tickers.len());
+        let mut moves = Vec::with_capacity(tickers.len());
+        let event_time = tickers.iter().map(|t| t.E).max().unwrap_or_default();
+
+        for ticker in tickers {
+            let symbol = ticker.s.to_lowercase();
+            // Only care about USDT pairs
+            if !symbol.ends_with("usdt") {
+                continue;
+            }
+
+            let coin_id = symbol.replace("usdt", "");
+
+            if let (Ok(current_price), Ok(volume_quote), Ok(open_price)) = (
+                ticker.c.parse::<Decimal>(),
+                ticker.q.parse::<Decimal>(),
+                ticker.o.parse::<Decimal>(),
+            ) {
+                // Store data for analysis
+                new_ticker_data.push((
+                    coin_id.clone(),
+                    TickerData {
+                        price: current_price,
+                        volume_quote,
+                        open_price,
+                        updated_at: now,
+                    },
+                ));
+
+                if let Ok(volume_24h) = ticker.v.parse::<Decimal>() {
+                    ticks.push(Tick {
+                        coin_id: coin_id.clone(),
+                        price: current_price,
+                        volume_24h,
+                    });
+                }
+
+                // Update Price Store (Legacy support)
+                {
+                    let mut prices_map = self.prices.lock().await;
+                    let previous = prices_map.insert(
+                        coin_id.clone(),
+                        PriceEntry {
+                            price: current_price,
+                            updated_at: now,
+                        },
+                    );
+                    moves.push(PriceMove {
+                        coin_id: coin_id.clone(),
+                        previous: previous.map(|entry| entry.price),
+                        price: current_price,
+                    });
+                }
+
+                if let Some(coin_orders) = orders.get_mut(&coin_id) {
+                    // ⚡ CRITICAL SECTION: MATCHING LOGIC
+                    let mut executed_indices = Vec::new();
+
+                    for (i, order) in coin_orders.iter().enumerate() {
+                        let is_match = match order.order_type.as_str() {
+                            "buy" => current_price <= order.price,
+                            "sell" => current_price >= order.price,
+                            _ => false,
+                        };
+
+                        if is_match {
+                            info!("⚡ MATCHED: Order {} {} @ {} (Market: {}) in {:?}", 
+                                order.id, order.order_type, order.price, current_price, start.elapsed());
+
+                            // Hand off to the settlement worker; the matching loop never waits on the DB
+                            if self.settlements.send((order.clone(), current_price)).is_err() {
+                                error!("❌ Settlement worker is gone, order {} not settled", order.id);
                             }
+
+                            executed_indices.push(i);
                         }
                     }
-                    Err(e) => {
-                        error!("WebSocket connection failed: {}. Retrying in 5s...", e);
-                        tokio::time::sleep(Duration::from_secs(5)).await;
+
+                    // Remove executed orders (reverse to safely remove)
+                    for &i in executed_indices.iter().rev() {
+                        coin_orders.remove(i);
                     }
                 }
             }
-        });
+        }
+
+        // Update Ticker Data Store
+        if !new_ticker_data.is_empty() {
+            let prices = new_ticker_data
+                .iter()
+                .map(|(coin_id, data)| PriceUpdate {
+                    coin_id: coin_id.clone(),
+                    price: data.price,
+                    open_price: data.open_price,
+                    volume_quote: data.volume_quote,
+                })
+                .collect();
+            self.events.publish(DomainEvent::PriceTick {
+                event_time,
+                prices: Arc::new(prices),
+            });
+
+            let mut td_map = self.ticker_data.lock().await;
+            for (cid, data) in new_ticker_data {
+                td_map.insert(cid, data);
+            }
+        }
+
+        // Release the order book before candle bookkeeping
+        drop(orders);
+        if !ticks.is_empty() {
+            self.candles.ingest(event_time, &ticks).await;
+        }
+        // After ingest, so window and RSI alerts see this batch in the current candle
+        self.alerts.evaluate(&moves, &self.candles).await;
     }
 
     async fn load_pending_orders(&self) -> anyhow::Result<()> {
@@ -242,7 +377,13 @@ impl MatchingEngine {
         Ok(())
     }
 
-    async fn execute_order(pool: PgPool, order: LimitOrder, execution_price: Decimal) {
+    /// Marks a matched limit order completed and settles it against the wallet
+    async fn execute_order(
+        pool: &PgPool,
+        events: &EventBus,
+        order: LimitOrder,
+        execution_price: Decimal,
+    ) {
         let total_amount = execution_price * order.quantity;
 
         // Parse UUID string to Uuid type for sqlx
@@ -254,6 +395,7 @@ impl MatchingEngine {
             }
         };
 
+        // Only pending orders fill; one cancelled since it was matched stays cancelled
         let result = sqlx::query(
             r#"
             UPDATE orders 
@@ -261,32 +403,28 @@ impl MatchingEngine {
                 price_per_unit = $1, 
                 total_amount = $2, 
                 completed_at = NOW()
-            WHERE id = $3
+            WHERE id = $3 AND order_status = 'pending'
             "#,
         )
         .bind(execution_price)
         .bind(total_amount)
         .bind(order_uuid)
-        .execute(&pool)
+        .execute(pool)
         .await;
 
         match result {
+            Ok(done) if done.rows_affected() == 0 => {
+                warn!("⚠️ Order {} is no longer pending, not settling", order.id);
+            }
             Ok(_) => {
                 info!("✅ Order {} executed successfully in DB", order.id);
 
                 // 🚀 Execute Financial Transaction (Direct DB)
-                // Use token::spawn to run in background, but we need a new connection/pool reference
-                 let p_clone_exec = pool.clone();
-                 let order_id = order_uuid;
-                 let e_price = execution_price;
-                 
-                 tokio::spawn(async move {
-                    if let Err(e) = crate::services::execution::execute_order(&p_clone_exec, order_id, e_price).await {
-                        error!("❌ Financial Execution Failed for order {}: {}", order_id, e);
-                    } else {
-                        info!("💸 Financial Transaction executed for Order {}", order_id);
-                    }
-                 });
+                if let Err(e) = crate::services::execution::execute_order(pool, events, order_uuid, execution_price).await {
+                    error!("❌ Financial Execution Failed for order {}: {}", order_uuid, e);
+                } else {
+                    info!("💸 Financial Transaction executed for Order {}", order_uuid);
+                }
             }
             Err(e) => error!("❌ Failed to update order {} in DB: {}", order.id, e),
         }
@@ -295,7 +433,8 @@ impl MatchingEngine {
     // Public method to add new order dynamically (called from API)
     pub async fn add_order(
         &self,
-        order_id: String,
+        order_id: Uuid,
+        user_id: Uuid,
         coin_id: String,
         order_type: String,
         price: Decimal,
@@ -309,38 +448,108 @@ impl MatchingEngine {
             );
             return;
         }
+        {
+            let mut orders = self.orders.lock().await;
+            orders
+                .entry(coin_id.trim().to_lowercase())
+                .or_insert_with(Vec::new)
+                .push(LimitOrder {
+                    id: order_id.to_string(),
+                    user_id: user_id.to_string(),
+                    coin_id: coin_id.clone(),
+                    coin_symbol: "".to_string(),
+                    order_type: order_type.clone(),
+                    quantity,
+                    price,
+                });
+        }
+
+        self.events.publish(DomainEvent::OrderPlaced {
+            order_id,
+            user_id,
+            coin_id,
+            order_type,
+            order_mode: "limit".to_string(),
+            quantity,
+            price,
+        });
+    }
+
+    async fn remove_order(&self, order_id: &str) {
         let mut orders = self.orders.lock().await;
-        orders
-            .entry(coin_id.trim().to_lowercase())
-            .or_insert_with(Vec::new)
-            .push(LimitOrder {
-                id: order_id,
-                user_id: "".to_string(), // Fetched if needed
-                coin_id,
-                coin_symbol: "".to_string(),
-                order_type,
-                quantity,
-                price,
-            });
+        for coin_orders in orders.values_mut() {
+            coin_orders.retain(|order| order.id != order_id);
+        }
+    }
+
+    pub fn candles(&self) -> &CandleAggregator {
+        &self.candles
+    }
+
+    pub fn depth(&self) -> &DepthCache {
+        &self.depth
     }
 
+    pub fn alerts(&self) -> &AlertBook {
+        &self.alerts
+    }
+
+    /// Whether the ticker feed has ever reported a USDT pair for `coin_id`
+    pub async fn is_listed(&self, coin_id: &str) -> bool {
+        self.prices.lock().await.contains_key(coin_id)
+    }
+
+    /// Latest prices, leaving out coins that haven't ticked for `STALE_PRICE_SECS`
     pub async fn get_prices(&self) -> HashMap<String, Decimal> {
+        let cutoff = Utc::now() - chrono::Duration::seconds(STALE_PRICE_SECS);
         let prices = self.prices.lock().await;
-        prices.clone()
+        prices
+            .iter()
+            .filter(|(_, entry)| entry.updated_at >= cutoff)
+            .map(|(coin_id, entry)| (coin_id.clone(), entry.price))
+            .collect()
+    }
+
+    pub async fn feed_health(&self) -> FeedHealth {
+        let now = Utc::now();
+        let cutoff = now - chrono::Duration::seconds(STALE_PRICE_SECS);
+        let (tracked_prices, stale_prices) = {
+            let prices = self.prices.lock().await;
+            let stale = prices.values().filter(|e| e.updated_at < cutoff).count();
+            (prices.len(), stale)
+        };
+
+        let feed = self.feed.lock().await;
+        let seconds_since_last_batch = feed.last_batch_at.map(|t| (now - t).num_seconds());
+        FeedHealth {
+            healthy: feed.connected
+                && seconds_since_last_batch.is_some_and(|secs| secs <= FEED_STALE_SECS),
+            connected: feed.connected,
+            connected_since: feed.connected_since,
+            last_batch_at: feed.last_batch_at,
+            seconds_since_last_batch,
+            reconnects: feed.connects.saturating_sub(1),
+            batches: feed.batches,
+            last_error: feed.last_error.clone(),
+            tracked_prices,
+            stale_prices,
+        }
     }
 
     // NEW: Get Top liquid coins for analysis
     pub async fn get_top_volume_coins(&self, limit: usize) -> Vec<(String, TickerData)> {
+        let cutoff = Utc::now() - chrono::Duration::seconds(STALE_PRICE_SECS);
         let ticker_map = self.ticker_data.lock().await;
 
         let mut coins: Vec<(String, TickerData)> = ticker_map
             .iter()
             .filter(|(_, data)| data.price > Decimal::ZERO && data.volume_quote > Decimal::ZERO) // Filter out 0 or invalid
+            .filter(|(_, data)| data.updated_at >= cutoff) // Frozen prices can't be traded on
             .map(|(k, v)| (k.clone(), v.clone()))
             .collect();
 
         // Sort by quote volume descending (highest volume first)
-        coins.sort_by(|a, b| b.1.volume_quote.cmp(&a.1.volume_quote));
+        coins.sort_by_key(|c| std::cmp::Reverse(c.1.volume_quote));
 
         coins.into_iter().take(limit).collect()
     }
@@ -1,5 +1,20 @@
+pub mod alerts;
+pub mod api_keys;
 pub mod automation;
+pub mod backtest;
+pub mod binance;
+pub mod candles;
+pub mod depth;
+pub mod events;
 pub mod matching_engine;
+pub mod notifications;
 pub mod execution;
+pub mod indicators;
+pub mod optimizer;
 pub mod orders;
+pub mod outbound;
 pub mod portfolio;
+pub mod realtime;
+pub mod sizing;
+pub mod ticker_feed;
+pub mod webhooks;
\ No newline at end of file
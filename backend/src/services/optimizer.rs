@@ -0,0 +1,622 @@
+use crate::models::Candle;
+use crate::services::automation::StrategyParams;
+use crate::services::backtest::{self, BacktestReport, CandleFeatures};
+use crate::services::binance::BinanceClient;
+use chrono::{DateTime, Utc};
+use rand::seq::SliceRandom;
+use rust_decimal::{Decimal, MathematicalOps};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{error, info};
+use uuid::Uuid;
+
+const MAX_COINS: usize = 10;
+const MAX_CANDLES: usize = 1000; // Binance klines limit per request
+const MIN_TRAIN_SIZE: usize = 60;
+const MAX_CANDIDATES: usize = 1000; // Per window, for grid and random search alike
+const JOB_RETENTION_SECS: i64 = 3600; // Finished jobs are dropped after this
+const MAX_RUNNING_JOBS_PER_USER: usize = 2; // Each job holds a blocking thread for its backtests
+
+#[derive(Debug, Deserialize)]
+pub struct OptimizeRequest {
+    pub coins: Vec<String>,
+    pub interval: Option<String>,  // Binance kline interval, default "5m"
+    pub limit: Option<usize>,      // Candles per coin, default 1000
+    pub train_size: Option<usize>, // Candles per in-sample window, default 300
+    pub test_size: Option<usize>,  // Candles per out-of-sample window, default 100
+    pub search: Option<String>,    // "grid" (default) or "random"
+    pub samples: Option<usize>,    // Candidates per window for random search, default 50
+    pub grid: Option<ParamGrid>,
+}
+
+/// Candidate values for each tunable parameter
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ParamGrid {
+    pub profit_percentage: Vec<Decimal>,
+    pub rsi_oversold: Vec<Decimal>,
+    pub rsi_overbought: Vec<Decimal>,
+    pub atr_stop_multiplier: Vec<Decimal>,
+    pub atr_trail_multiplier: Vec<Decimal>,
+    pub min_volume_ratio: Vec<Decimal>,
+}
+
+impl Default for ParamGrid {
+    fn default() -> Self {
+        let d = |values: &[&str]| values.iter().map(|v| Decimal::from_str(v).unwrap()).collect();
+        Self {
+            profit_percentage: d(&["1", "2", "3"]),
+            rsi_oversold: d(&["25", "30", "35"]),
+            rsi_overbought: d(&["65", "70", "75"]),
+            atr_stop_multiplier: d(&["2", "3", "4"]),
+            atr_trail_multiplier: d(&["1.5", "2", "2.5"]),
+            min_volume_ratio: d(&["1.0", "1.2", "1.5"]),
+        }
+    }
+}
+
+impl ParamGrid {
+    fn is_valid(&self) -> bool {
+        !self.profit_percentage.is_empty()
+            && !self.rsi_oversold.is_empty()
+            && !self.rsi_overbought.is_empty()
+            && !self.atr_stop_multiplier.is_empty()
+            && !self.atr_trail_multiplier.is_empty()
+            && !self.min_volume_ratio.is_empty()
+    }
+
+    /// Number of combinations, or None when it doesn't fit in a usize
+    fn size(&self) -> Option<usize> {
+        [
+            self.rsi_oversold.len(),
+            self.rsi_overbought.len(),
+            self.atr_stop_multiplier.len(),
+            self.atr_trail_multiplier.len(),
+            self.min_volume_ratio.len(),
+        ]
+        .iter()
+        .try_fold(self.profit_percentage.len(), |acc, &n| acc.checked_mul(n))
+    }
+
+    fn all(&self) -> Vec<StrategyParams> {
+        let mut candidates = Vec::with_capacity(self.size().unwrap_or(0));
+        for &profit_percentage in &self.profit_percentage {
+            for &rsi_oversold in &self.rsi_oversold {
+                for &rsi_overbought in &self.rsi_overbought {
+                    for &atr_stop_multiplier in &self.atr_stop_multiplier {
+                        for &atr_trail_multiplier in &self.atr_trail_multiplier {
+                            for &min_volume_ratio in &self.min_volume_ratio {
+                                candidates.push(StrategyParams {
+                                    profit_percentage,
+                                    rsi_oversold,
+                                    rsi_overbought,
+                                    atr_stop_multiplier,
+                                    atr_trail_multiplier,
+                                    min_volume_ratio,
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        candidates
+    }
+
+    fn sample(&self, count: usize) -> Vec<StrategyParams> {
+        let mut rng = rand::thread_rng();
+        let mut pick = |values: &[Decimal]| *values.choose(&mut rng).unwrap();
+        (0..count)
+            .map(|_| StrategyParams {
+                profit_percentage: pick(&self.profit_percentage),
+                rsi_oversold: pick(&self.rsi_oversold),
+                rsi_overbought: pick(&self.rsi_overbought),
+                atr_stop_multiplier: pick(&self.atr_stop_multiplier),
+                atr_trail_multiplier: pick(&self.atr_trail_multiplier),
+                min_volume_ratio: pick(&self.min_volume_ratio),
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WindowResult {
+    pub train_start: i64, // open_time (ms) of first in-sample candle
+    pub test_start: i64,
+    pub test_end: i64,
+    pub best_params: StrategyParams,
+    pub in_sample_return_pct: Decimal,
+    pub out_of_sample: BacktestReport,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OptimizeReport {
+    pub candidates_per_window: usize,
+    pub windows: Vec<WindowResult>,
+    pub out_of_sample: BacktestReport,
+    /// Coefficient of variation of each chosen parameter across windows (lower = more stable)
+    pub parameter_stability: HashMap<String, Decimal>,
+    /// Per-parameter median of the window winners
+    pub recommended: StrategyParams,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OptimizeJob {
+    pub id: Uuid,
+    #[serde(skip)]
+    pub user_id: Uuid, // Submitter; only they can poll the job
+    pub status: String, // "running", "completed" or "failed"
+    pub progress: u32,  // Percent of windows evaluated
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+    pub result: Option<OptimizeReport>,
+}
+
+/// Runs walk-forward optimizations as background jobs and keeps their results for polling
+#[derive(Clone)]
+pub struct Optimizer {
+    jobs: Arc<Mutex<HashMap<Uuid, OptimizeJob>>>,
+    binance: Arc<BinanceClient>,
+}
+
+struct JobSpec {
+    coins: Vec<String>,
+    interval: String,
+    limit: usize,
+    train_size: usize,
+    test_size: usize,
+    random_samples: Option<usize>,
+    grid: ParamGrid,
+}
+
+impl Optimizer {
+    pub fn new(binance: Arc<BinanceClient>) -> Self {
+        Self {
+            jobs: Arc::new(Mutex::new(HashMap::new())),
+            binance,
+        }
+    }
+
+    /// Validates the request and starts the job, returning its id
+    pub async fn submit(&self, user_id: Uuid, request: OptimizeRequest) -> Result<Uuid, String> {
+        let spec = Self::validate(request)?;
+        let id = Self::admit(&mut *self.jobs.lock().await, user_id, Utc::now())?;
+
+        let self_clone = self.clone();
+        tokio::spawn(async move {
+            let outcome = self_clone.run(id, spec).await;
+            let mut jobs = self_clone.jobs.lock().await;
+            if let Some(job) = jobs.get_mut(&id) {
+                job.finished_at = Some(Utc::now());
+                match outcome {
+                    Ok(report) => {
+                        job.status = "completed".to_string();
+                        job.progress = 100;
+                        job.result = Some(report);
+                    }
+                    Err(e) => {
+                        error!("❌ Optimization job {} failed: {}", id, e);
+                        job.status = "failed".to_string();
+                        job.error = Some(e.to_string());
+                    }
+                }
+            }
+        });
+
+        Ok(id)
+    }
+
+    pub async fn get(&self, id: Uuid) -> Option<OptimizeJob> {
+        self.jobs.lock().await.get(&id).cloned()
+    }
+
+    /// Prunes expired jobs and registers a new running one, unless the user already has
+    /// `MAX_RUNNING_JOBS_PER_USER` in flight
+    fn admit(jobs: &mut HashMap<Uuid, OptimizeJob>, user_id: Uuid, now: DateTime<Utc>) -> Result<Uuid, String> {
+        let cutoff = now - chrono::Duration::seconds(JOB_RETENTION_SECS);
+        jobs.retain(|_, job| job.finished_at.is_none_or(|finished| finished > cutoff));
+
+        let running = jobs
+            .values()
+            .filter(|job| job.user_id == user_id && job.finished_at.is_none())
+            .count();
+        if running >= MAX_RUNNING_JOBS_PER_USER {
+            return Err(format!(
+                "At most {} optimization jobs can run at once, wait for one to finish",
+                MAX_RUNNING_JOBS_PER_USER
+            ));
+        }
+
+        let id = Uuid::new_v4();
+        jobs.insert(
+            id,
+            OptimizeJob {
+                id,
+                user_id,
+                status: "running".to_string(),
+                progress: 0,
+                created_at: now,
+                finished_at: None,
+                error: None,
+                result: None,
+            },
+        );
+        Ok(id)
+    }
+
+    /// (train, test) candle ranges: each test window directly follows its training window,
+    /// and windows advance by `test_size` so the test windows tile the series without overlap
+    fn windows(len: usize, train_size: usize, test_size: usize) -> Vec<(Range<usize>, Range<usize>)> {
+        let count = len.saturating_sub(train_size) / test_size;
+        (0..count)
+            .map(|w| {
+                let train = w * test_size..w * test_size + train_size;
+                let test = train.end..train.end + test_size;
+                (train, test)
+            })
+            .collect()
+    }
+
+    fn validate(request: OptimizeRequest) -> Result<JobSpec, String> {
+        let coins: Vec<String> = request
+            .coins
+            .iter()
+            .map(|c| c.trim().to_lowercase())
+            .filter(|c| !c.is_empty())
+            .collect();
+        if coins.is_empty() || coins.len() > MAX_COINS {
+            return Err(format!("Provide between 1 and {} coins", MAX_COINS));
+        }
+
+        let limit = request.limit.unwrap_or(MAX_CANDLES).min(MAX_CANDLES);
+        let train_size = request.train_size.unwrap_or(300);
+        let test_size = request.test_size.unwrap_or(100);
+        if train_size < MIN_TRAIN_SIZE || test_size == 0 {
+            return Err(format!(
+                "train_size must be at least {} and test_size greater than 0",
+                MIN_TRAIN_SIZE
+            ));
+        }
+        if train_size.saturating_add(test_size) > limit {
+            return Err("train_size + test_size exceeds the candle limit".to_string());
+        }
+
+        let random_samples = match request.search.as_deref().unwrap_or("grid") {
+            "grid" => None,
+            "random" => Some(request.samples.unwrap_or(50).clamp(1, MAX_CANDIDATES)),
+            other => return Err(format!("Unknown search mode '{}'", other)),
+        };
+
+        let grid = request.grid.unwrap_or_default();
+        if !grid.is_valid() {
+            return Err("Every parameter in grid needs at least one value".to_string());
+        }
+        if random_samples.is_none() && grid.size().is_none_or(|size| size > MAX_CANDIDATES) {
+            return Err(format!(
+                "grid has more than {} combinations, use fewer values or search \"random\"",
+                MAX_CANDIDATES
+            ));
+        }
+
+        Ok(JobSpec {
+            coins,
+            interval: request.interval.unwrap_or_else(|| "5m".to_string()),
+            limit,
+            train_size,
+            test_size,
+            random_samples,
+            grid,
+        })
+    }
+
+    async fn run(&self, id: Uuid, spec: JobSpec) -> anyhow::Result<OptimizeReport> {
+        info!(
+            "🧪 Optimization job {}: {} coins, {} candles ({}), train {} / test {}",
+            id,
+            spec.coins.len(),
+            spec.limit,
+            spec.interval,
+            spec.train_size,
+            spec.test_size
+        );
+
+        let mut series = Vec::with_capacity(spec.coins.len());
+        for coin in &spec.coins {
+            let candles = self.binance.klines(coin, &spec.interval, spec.limit).await?;
+            if candles.len() < spec.train_size + spec.test_size {
+                return Err(anyhow::anyhow!(
+                    "Not enough history for {} ({} candles)",
+                    coin,
+                    candles.len()
+                ));
+            }
+            series.push(candles);
+        }
+
+        // All series must share the same window boundaries
+        let len = series.iter().map(|s| s.len()).min().unwrap_or(0);
+        for s in series.iter_mut() {
+            let excess = s.len() - len;
+            s.drain(..excess);
+        }
+
+        let jobs = self.jobs.clone();
+        let report = tokio::task::spawn_blocking(move || {
+            Self::walk_forward(&series, &spec, |progress| {
+                if let Some(job) = jobs.blocking_lock().get_mut(&id) {
+                    job.progress = progress;
+                }
+            })
+        })
+        .await??;
+
+        info!(
+            "✅ Optimization job {} finished: {} windows, OOS return {}%",
+            id,
+            report.windows.len(),
+            report.out_of_sample.total_return_pct.round_dp(2)
+        );
+        Ok(report)
+    }
+
+    fn walk_forward(
+        series: &[Vec<Candle>],
+        spec: &JobSpec,
+        mut on_progress: impl FnMut(u32),
+    ) -> anyhow::Result<OptimizeReport> {
+        let features: Vec<Vec<Option<CandleFeatures>>> =
+            series.iter().map(|s| backtest::compute_features(s)).collect();
+        let ranges = Self::windows(series[0].len(), spec.train_size, spec.test_size);
+        let window_count = ranges.len();
+
+        let mut windows = Vec::with_capacity(window_count);
+        let mut candidates_per_window = 0;
+
+        for (w, (train, test)) in ranges.into_iter().enumerate() {
+
+            let candidates = match spec.random_samples {
+                Some(count) => spec.grid.sample(count),
+                None => spec.grid.all(),
+            };
+            candidates_per_window = candidates.len();
+
+            // In-sample score: sum of returns across all coins
+            let (best_params, in_sample_return_pct) = candidates
+                .into_iter()
+                .map(|params| {
+                    let total: Decimal = series
+                        .iter()
+                        .zip(&features)
+                        .map(|(candles, f)| {
+                            backtest::run(candles, f, train.clone(), &params).total_return_pct
+                        })
+                        .sum();
+                    (params, total)
+                })
+                .max_by(|a, b| a.1.cmp(&b.1))
+                .ok_or_else(|| anyhow::anyhow!("No parameter candidates"))?;
+
+            let reports: Vec<BacktestReport> = series
+                .iter()
+                .zip(&features)
+                .map(|(candles, f)| backtest::run(candles, f, test.clone(), &best_params))
+                .collect();
+
+            windows.push(WindowResult {
+                train_start: series[0][train.start].open_time,
+                test_start: series[0][test.start].open_time,
+                test_end: series[0][test.end - 1].open_time,
+                best_params,
+                in_sample_return_pct,
+                out_of_sample: Self::combine(&reports),
+            });
+
+            on_progress(((w + 1) * 100 / window_count) as u32);
+        }
+
+        if windows.is_empty() {
+            return Err(anyhow::anyhow!("Not enough candles for a single walk-forward window"));
+        }
+
+        let oos: Vec<BacktestReport> = windows.iter().map(|w| w.out_of_sample.clone()).collect();
+        let (parameter_stability, recommended) = Self::stability(&windows);
+
+        Ok(OptimizeReport {
+            candidates_per_window,
+            out_of_sample: Self::combine(&oos),
+            parameter_stability,
+            recommended,
+            windows,
+        })
+    }
+
+    /// Aggregates reports: trades/wins are summed, returns averaged, drawdown is the worst seen
+    fn combine(reports: &[BacktestReport]) -> BacktestReport {
+        let trades: usize = reports.iter().map(|r| r.trades).sum();
+        let wins: usize = reports.iter().map(|r| r.wins).sum();
+        let total_return_pct = if reports.is_empty() {
+            Decimal::ZERO
+        } else {
+            reports.iter().map(|r| r.total_return_pct).sum::<Decimal>()
+                / Decimal::from(reports.len())
+        };
+
+        BacktestReport {
+            trades,
+            wins,
+            win_rate: if trades > 0 {
+                Decimal::from(wins) / Decimal::from(trades)
+            } else {
+                Decimal::ZERO
+            },
+            total_return_pct,
+            max_drawdown_pct: reports
+                .iter()
+                .map(|r| r.max_drawdown_pct)
+                .max()
+                .unwrap_or(Decimal::ZERO),
+        }
+    }
+
+    fn stability(windows: &[WindowResult]) -> (HashMap<String, Decimal>, StrategyParams) {
+        let column = |get: fn(&StrategyParams) -> Decimal| -> Vec<Decimal> {
+            windows.iter().map(|w| get(&w.best_params)).collect()
+        };
+        let columns: [(&str, Vec<Decimal>); 6] = [
+            ("profit_percentage", column(|p| p.profit_percentage)),
+            ("rsi_oversold", column(|p| p.rsi_oversold)),
+            ("rsi_overbought", column(|p| p.rsi_overbought)),
+            ("atr_stop_multiplier", column(|p| p.atr_stop_multiplier)),
+            ("atr_trail_multiplier", column(|p| p.atr_trail_multiplier)),
+            ("min_volume_ratio", column(|p| p.min_volume_ratio)),
+        ];
+
+        let mut stability = HashMap::new();
+        let mut medians = Vec::with_capacity(columns.len());
+        for (name, mut values) in columns {
+            let n = Decimal::from(values.len());
+            let mean = values.iter().sum::<Decimal>() / n;
+            let variance = values
+                .iter()
+                .map(|v| (*v - mean) * (*v - mean))
+                .sum::<Decimal>()
+                / n;
+            let cv = if mean > Decimal::ZERO {
+                variance.sqrt().unwrap_or(Decimal::ZERO) / mean
+            } else {
+                Decimal::ZERO
+            };
+            stability.insert(name.to_string(), cv.round_dp(4));
+
+            values.sort();
+            medians.push(values[values.len() / 2]);
+        }
+
+        let recommended = StrategyParams {
+            profit_percentage: medians[0],
+            rsi_oversold: medians[1],
+            rsi_overbought: medians[2],
+            atr_stop_multiplier: medians[3],
+            atr_trail_multiplier: medians[4],
+            min_volume_ratio: medians[5],
+        };
+
+        (stability, recommended)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn d(values: &[&str]) -> Vec<Decimal> {
+        values.iter().map(|v| Decimal::from_str(v).unwrap()).collect()
+    }
+
+    fn small_grid() -> ParamGrid {
+        ParamGrid {
+            profit_percentage: d(&["1", "2"]),
+            rsi_oversold: d(&["30"]),
+            rsi_overbought: d(&["70"]),
+            atr_stop_multiplier: d(&["3"]),
+            atr_trail_multiplier: d(&["2"]),
+            min_volume_ratio: d(&["1.0", "1.2", "1.5"]),
+        }
+    }
+
+    #[test]
+    fn grid_expands_every_combination() {
+        let grid = small_grid();
+        assert_eq!(grid.size(), Some(6));
+
+        let all = grid.all();
+        assert_eq!(all.len(), 6);
+        let mut pairs: Vec<(Decimal, Decimal)> =
+            all.iter().map(|p| (p.profit_percentage, p.min_volume_ratio)).collect();
+        pairs.sort();
+        pairs.dedup();
+        assert_eq!(pairs.len(), 6);
+        assert!(all.iter().all(|p| p.rsi_oversold == Decimal::from(30) && p.atr_stop_multiplier == Decimal::from(3)));
+
+        assert_eq!(ParamGrid::default().size(), Some(729));
+    }
+
+    #[test]
+    fn random_samples_come_from_the_grid() {
+        let grid = small_grid();
+        let samples = grid.sample(20);
+        assert_eq!(samples.len(), 20);
+        assert!(samples.iter().all(|p| grid.profit_percentage.contains(&p.profit_percentage)
+            && grid.min_volume_ratio.contains(&p.min_volume_ratio)));
+    }
+
+    #[test]
+    fn oversized_grids_need_random_search() {
+        let values = d(&["1", "2", "3", "4"]);
+        let grid = ParamGrid {
+            profit_percentage: values.clone(),
+            rsi_oversold: values.clone(),
+            rsi_overbought: values.clone(),
+            atr_stop_multiplier: values.clone(),
+            atr_trail_multiplier: values.clone(),
+            min_volume_ratio: values,
+        };
+        let request = |search: &str| OptimizeRequest {
+            coins: vec!["btc".to_string()],
+            interval: None,
+            limit: None,
+            train_size: None,
+            test_size: None,
+            search: Some(search.to_string()),
+            samples: None,
+            grid: Some(grid.clone()),
+        };
+        assert!(Optimizer::validate(request("grid")).is_err());
+        assert_eq!(Optimizer::validate(request("random")).unwrap().random_samples, Some(50));
+    }
+
+    #[test]
+    fn running_jobs_are_capped_per_user() {
+        let mut jobs = HashMap::new();
+        let now = Utc::now();
+        let user = Uuid::new_v4();
+
+        let first = Optimizer::admit(&mut jobs, user, now).unwrap();
+        Optimizer::admit(&mut jobs, user, now).unwrap();
+        assert!(Optimizer::admit(&mut jobs, user, now).is_err());
+        // Other users have their own allowance
+        assert!(Optimizer::admit(&mut jobs, Uuid::new_v4(), now).is_ok());
+
+        // A finished job frees a slot, and is pruned once past retention
+        jobs.get_mut(&first).unwrap().finished_at = Some(now);
+        assert!(Optimizer::admit(&mut jobs, user, now).is_ok());
+        let later = now + chrono::Duration::seconds(JOB_RETENTION_SECS + 1);
+        assert!(Optimizer::admit(&mut jobs, Uuid::new_v4(), later).is_ok());
+        assert!(!jobs.contains_key(&first));
+    }
+
+    #[test]
+    fn walk_forward_windows_split_train_then_test() {
+        let windows = Optimizer::windows(500, 300, 100);
+        assert_eq!(windows, vec![(0..300, 300..400), (100..400, 400..500)]);
+
+        // Test windows follow their training window and tile the rest of the series
+        let windows = Optimizer::windows(1000, 300, 100);
+        assert_eq!(windows.len(), 7);
+        for (i, (train, test)) in windows.iter().enumerate() {
+            assert_eq!(train.len(), 300);
+            assert_eq!(test.start, train.end);
+            assert_eq!(test.len(), 100);
+            if i > 0 {
+                assert_eq!(test.start, windows[i - 1].1.end);
+            }
+        }
+        assert_eq!(windows.last().unwrap().1.end, 1000);
+
+        assert!(Optimizer::windows(399, 300, 100).is_empty());
+    }
+}
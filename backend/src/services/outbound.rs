@@ -0,0 +1,174 @@
+// HTTP to user-supplied URLs (webhook endpoints, webhook notification channels). Users
+// pick the host, so requests must not reach the server's own network: loopback, private
+// ranges, link-local (cloud metadata at 169.254.169.254) and other non-public addresses
+// are refused when a URL is registered, again before every send, and by the client's DNS
+// resolver so a name can't be re-pointed at an internal address in between.
+
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::{redirect, Client};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Whether `ip` is routable on the public internet
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => is_public_v6(ip),
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0 // "This network"
+        || (a == 100 && (64..128).contains(&b)) // Carrier-grade NAT
+        || (a == 192 && b == 0 && c == 0) // IETF protocol assignments
+        || (a == 198 && (18..20).contains(&b)) // Benchmarking
+        || a >= 240) // Reserved
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    // IPv4-mapped (::ffff:a.b.c.d) and NAT64 (64:ff9b::a.b.c.d) reach the embedded address
+    if let Some(v4) = ip.to_ipv4_mapped() {
+        return is_public_v4(v4);
+    }
+    let segments = ip.segments();
+    if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+        let [_, _, _, _, _, _, hi, lo] = segments;
+        return is_public_v4(Ipv4Addr::from(((hi as u32) << 16) | lo as u32));
+    }
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        || (segments[0] & 0xfe00) == 0xfc00 // Unique local
+        || (segments[0] & 0xffc0) == 0xfe80 // Link-local
+        || (segments[0] & 0xffc0) == 0xfec0 // Site-local (deprecated)
+        || (segments[0] == 0x2001 && segments[1] == 0x0db8)) // Documentation
+}
+
+/// Checks that `url` is an absolute http(s) URL whose host only resolves to public
+/// addresses. `allow_private` (ALLOW_PRIVATE_WEBHOOK_TARGETS) skips the address check
+/// for local testing.
+pub async fn check_url(url: &str, allow_private: bool) -> Result<(), String> {
+    let parsed = url::Url::parse(url).map_err(|_| "must be an absolute http(s) URL".to_string())?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err("must be an absolute http(s) URL".to_string());
+    }
+    let port = parsed.port_or_known_default().unwrap_or(80);
+    let addrs: Vec<IpAddr> = match parsed.host() {
+        Some(url::Host::Ipv4(ip)) => vec![IpAddr::V4(ip)],
+        Some(url::Host::Ipv6(ip)) => vec![IpAddr::V6(ip)],
+        Some(url::Host::Domain(domain)) => tokio::net::lookup_host((domain, port))
+            .await
+            .map_err(|e| format!("host {} does not resolve: {}", domain, e))?
+            .map(|addr| addr.ip())
+            .collect(),
+        None => return Err("must be an absolute http(s) URL".to_string()),
+    };
+
+    if addrs.is_empty() {
+        return Err("host does not resolve".to_string());
+    }
+    if !allow_private {
+        if let Some(ip) = addrs.iter().find(|ip| !is_public(**ip)) {
+            return Err(format!("host resolves to a non-public address ({})", ip));
+        }
+    }
+    Ok(())
+}
+
+/// Resolves like the system resolver, but drops non-public addresses
+struct PublicOnlyResolver;
+
+impl Resolve for PublicOnlyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", host).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Client for user-supplied URLs: no redirects (a 3xx counts as a failed delivery) and,
+/// unless `allow_private`, a resolver that never hands out internal addresses. A build
+/// failure is returned rather than replaced with an unrestricted default client.
+pub fn client(timeout: Duration, allow_private: bool) -> reqwest::Result<Client> {
+    let mut builder = Client::builder()
+        .timeout(timeout)
+        .redirect(redirect::Policy::none());
+    if !allow_private {
+        builder = builder.dns_resolver(Arc::new(PublicOnlyResolver));
+    }
+    builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn internal_addresses_are_not_public() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "0.0.0.0",
+            "100.64.0.1",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+            "64:ff9b::a9fe:a9fe",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{} should not be public", ip);
+        }
+    }
+
+    #[test]
+    fn internet_addresses_are_public() {
+        for ip in ["1.1.1.1", "8.8.8.8", "2606:4700:4700::1111", "::ffff:8.8.8.8"] {
+            assert!(is_public(ip.parse().unwrap()), "{} should be public", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn check_url_rejects_internal_targets() {
+        for url in [
+            "http://127.0.0.1:8080/hook",
+            "http://localhost/hook",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://[::1]/hook",
+            "http://[::ffff:10.0.0.1]/hook",
+        ] {
+            assert!(check_url(url, false).await.is_err(), "{} should be rejected", url);
+        }
+        assert!(check_url("ftp://1.1.1.1/hook", false).await.is_err());
+        assert!(check_url("not a url", false).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn check_url_allows_internal_targets_when_configured() {
+        assert!(check_url("http://127.0.0.1:8080/hook", true).await.is_ok());
+        assert!(check_url("http://localhost/hook", true).await.is_ok());
+        assert!(check_url("http://1.1.1.1/hook", false).await.is_ok());
+    }
+}
@@ -0,0 +1,282 @@
+use crate::services::events::{next_event, DomainEvent, EventBus};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Events buffered per subscriber; a client that falls further behind gets a `lagged`
+/// notice and should resync over REST
+const HUB_CAPACITY: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    Tickers,    // Price batches from the matching engine (public)
+    Orders,     // The user's fills and cancellations
+    Balances,   // The user's wallet after each execution
+    Automation, // The user's strategy actions, stops and status changes
+    Alerts,     // The user's price alerts as they fire
+}
+
+impl Channel {
+    pub const ALL: [Channel; 5] = [
+        Channel::Tickers,
+        Channel::Orders,
+        Channel::Balances,
+        Channel::Automation,
+        Channel::Alerts,
+    ];
+}
+
+impl FromStr for Channel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "tickers" => Ok(Channel::Tickers),
+            "orders" => Ok(Channel::Orders),
+            "balances" => Ok(Channel::Balances),
+            "automation" => Ok(Channel::Automation),
+            "alerts" => Ok(Channel::Alerts),
+            other => Err(format!("Unknown channel '{}'", other)),
+        }
+    }
+}
+
+/// One pushed message. `seq` counts up by one per channel and user, so a client that
+/// sees a jump knows it missed something on that channel.
+#[derive(Debug, Clone, Serialize)]
+pub struct HubEvent {
+    pub channel: Channel,
+    pub seq: u64,
+    #[serde(rename = "type")]
+    pub kind: &'static str, // e.g. "prices", "fill", "buy", "status"
+    #[serde(skip)]
+    pub user_id: Option<Uuid>, // None = visible to every subscriber of the channel
+    pub data: serde_json::Value,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl HubEvent {
+    pub fn visible_to(&self, user_id: Uuid) -> bool {
+        self.user_id.is_none_or(|owner| owner == user_id)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RealtimeMetrics {
+    pub connections: usize,
+    pub published: u64,
+}
+
+/// Fan-out point for everything pushed to `/ws` clients
+pub struct RealtimeHub {
+    tx: broadcast::Sender<Arc<HubEvent>>,
+    sequences: Mutex<HashMap<(Channel, Option<Uuid>), u64>>,
+    connections: AtomicUsize,
+    published: AtomicU64,
+}
+
+impl Default for RealtimeHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RealtimeHub {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(HUB_CAPACITY);
+        Self {
+            tx,
+            sequences: Mutex::new(HashMap::new()),
+            connections: AtomicUsize::new(0),
+            published: AtomicU64::new(0),
+        }
+    }
+
+    pub fn publish(
+        &self,
+        channel: Channel,
+        user_id: Option<Uuid>,
+        kind: &'static str,
+        data: serde_json::Value,
+    ) {
+        // Numbering and sending under one lock keeps `seq` in delivery order
+        let mut sequences = self.sequences.lock().unwrap();
+        let seq = sequences.entry((channel, user_id)).or_insert(0);
+        *seq += 1;
+        let event = HubEvent {
+            channel,
+            seq: *seq,
+            kind,
+            user_id,
+            data,
+            timestamp: Utc::now(),
+        };
+        // No receivers is fine, nobody is listening right now
+        let _ = self.tx.send(Arc::new(event));
+        self.published.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Skips building payloads nobody would receive (ticker batches arrive every second)
+    pub fn has_subscribers(&self) -> bool {
+        self.tx.receiver_count() > 0
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<HubEvent>> {
+        self.tx.subscribe()
+    }
+
+    pub fn connection_opened(&self) {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn connection_closed(&self) {
+        self.connections.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Relays bus events that clients care about: prices, fills, balances, cancellations
+    /// and strategy status changes
+    pub fn forward_from(self: Arc<Self>, bus: &EventBus) {
+        let mut events = bus.subscribe();
+        tokio::spawn(async move {
+            while let Some(event) = next_event(&mut events, "Realtime hub").await {
+                self.relay(event);
+            }
+        });
+    }
+
+    fn relay(&self, event: DomainEvent) {
+        match event {
+            DomainEvent::PriceTick { event_time, prices } => {
+                if self.has_subscribers() {
+                    self.publish(
+                        Channel::Tickers,
+                        None,
+                        "prices",
+                        json!({ "event_time": event_time, "prices": prices }),
+                    );
+                }
+            }
+            DomainEvent::OrderFilled {
+                order_id,
+                user_id,
+                coin_id,
+                coin_symbol,
+                order_type,
+                quantity,
+                price,
+                total_amount,
+                fee,
+                balance,
+            } => {
+                self.publish(
+                    Channel::Orders,
+                    Some(user_id),
+                    "fill",
+                    json!({
+                        "order_id": order_id,
+                        "coin_id": coin_id,
+                        "coin_symbol": coin_symbol,
+                        "order_type": order_type,
+                        "quantity": quantity,
+                        "price": price,
+                        "total_amount": total_amount,
+                        "fee": fee,
+                    }),
+                );
+                self.publish(
+                    Channel::Balances,
+                    Some(user_id),
+                    "balance",
+                    json!({ "balance_inr": balance, "order_id": order_id }),
+                );
+            }
+            DomainEvent::OrderCancelled {
+                order_id,
+                user_id,
+                reason,
+            } => {
+                self.publish(
+                    Channel::Orders,
+                    Some(user_id),
+                    "cancelled",
+                    json!({ "order_id": order_id, "reason": reason }),
+                );
+            }
+            DomainEvent::StrategyStateChanged {
+                strategy_id,
+                user_id,
+                status,
+            } => {
+                self.publish(
+                    Channel::Automation,
+                    Some(user_id),
+                    "status",
+                    json!({ "strategy_id": strategy_id, "status": status }),
+                );
+            }
+            DomainEvent::PositionClosed {
+                strategy_id,
+                user_id,
+                position_id,
+                coin_id,
+                reason,
+                exit_price,
+                profit,
+            } => {
+                self.publish(
+                    Channel::Automation,
+                    Some(user_id),
+                    "position_closed",
+                    json!({
+                        "strategy_id": strategy_id,
+                        "position_id": position_id,
+                        "coin_id": coin_id,
+                        "reason": reason,
+                        "exit_price": exit_price,
+                        "profit": profit,
+                    }),
+                );
+            }
+            DomainEvent::PriceAlertTriggered {
+                alert_id,
+                user_id,
+                coin_id,
+                condition,
+                price,
+                value,
+                message,
+                note,
+            } => {
+                self.publish(
+                    Channel::Alerts,
+                    Some(user_id),
+                    "alert",
+                    json!({
+                        "alert_id": alert_id,
+                        "coin_id": coin_id,
+                        "condition": condition,
+                        "price": price,
+                        "value": value,
+                        "message": message,
+                        "note": note,
+                    }),
+                );
+            }
+            DomainEvent::OrderPlaced { .. } => {}
+        }
+    }
+
+    pub fn metrics(&self) -> RealtimeMetrics {
+        RealtimeMetrics {
+            connections: self.connections.load(Ordering::Relaxed),
+            published: self.published.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+/// Kelly sizing needs some history before the estimate means anything
+const MIN_KELLY_TRADES: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SizingMode {
+    Fixed,            // Always trade the full `amount`
+    FixedFractional,  // Lose at most `risk_percent` of equity if the ATR stop is hit
+    VolatilityTarget, // Scale `amount` by target / realized (ATR) volatility
+    Kelly,            // Fraction of equity from the strategy's own win rate and payoff
+}
+
+impl FromStr for SizingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fixed" => Ok(SizingMode::Fixed),
+            "fixed_fractional" => Ok(SizingMode::FixedFractional),
+            "volatility_target" => Ok(SizingMode::VolatilityTarget),
+            "kelly" => Ok(SizingMode::Kelly),
+            other => Err(format!(
+                "Unknown sizing mode '{}' (expected fixed, fixed_fractional, volatility_target or kelly)",
+                other
+            )),
+        }
+    }
+}
+
+pub struct SizingInputs<'a> {
+    pub amount: Decimal, // Per-trade ceiling
+    pub equity: Decimal,
+    pub price: Decimal,
+    pub atr: Decimal,
+    pub atr_stop_multiplier: Decimal,
+    pub risk_percent: Decimal,      // % of equity risked per trade
+    pub target_volatility: Decimal, // Target ATR as % of price
+    pub kelly_fraction: Decimal,    // Multiplier on full Kelly (0.5 = half Kelly)
+    pub trade_profits: &'a [Decimal],
+}
+
+/// Notional to buy for the next entry. Never exceeds `amount`, so every mode only
+/// ever shrinks the fixed size (e.g. when the coin is more volatile).
+pub fn position_notional(mode: SizingMode, inputs: &SizingInputs) -> Decimal {
+    let hundred = Decimal::from(100);
+
+    let notional = match mode {
+        SizingMode::Fixed => inputs.amount,
+        SizingMode::FixedFractional => {
+            // Same fallback as the engine's hard stop when ATR is unavailable (3%)
+            let stop_distance = if inputs.atr > Decimal::ZERO {
+                inputs.atr * inputs.atr_stop_multiplier
+            } else {
+                inputs.price * Decimal::from(3) / hundred
+            };
+            if stop_distance <= Decimal::ZERO {
+                return Decimal::ZERO;
+            }
+            let risk_amount = inputs.equity * inputs.risk_percent / hundred;
+            risk_amount / stop_distance * inputs.price
+        }
+        SizingMode::VolatilityTarget => {
+            if inputs.atr <= Decimal::ZERO || inputs.price <= Decimal::ZERO {
+                inputs.amount
+            } else {
+                let realized = inputs.atr / inputs.price * hundred;
+                inputs.amount * inputs.target_volatility / realized
+            }
+        }
+        SizingMode::Kelly => match kelly(inputs.trade_profits) {
+            Some(f) => inputs.equity * f * inputs.kelly_fraction,
+            None => inputs.amount,
+        },
+    };
+
+    notional.max(Decimal::ZERO).min(inputs.amount)
+}
+
+/// Full Kelly fraction f = W - (1 - W) / R from realized trade profits,
+/// or None while there isn't enough history
+fn kelly(profits: &[Decimal]) -> Option<Decimal> {
+    if profits.len() < MIN_KELLY_TRADES {
+        return None;
+    }
+
+    let wins: Vec<Decimal> = profits.iter().copied().filter(|p| *p > Decimal::ZERO).collect();
+    let losses: Vec<Decimal> = profits.iter().copied().filter(|p| *p < Decimal::ZERO).collect();
+    if wins.is_empty() {
+        return Some(Decimal::ZERO);
+    }
+    if losses.is_empty() {
+        return Some(Decimal::ONE);
+    }
+
+    let win_rate = Decimal::from(wins.len()) / Decimal::from(profits.len());
+    let avg_win = wins.iter().sum::<Decimal>() / Decimal::from(wins.len());
+    let avg_loss = losses.iter().sum::<Decimal>().abs() / Decimal::from(losses.len());
+    let payoff = avg_win / avg_loss;
+
+    Some((win_rate - (Decimal::ONE - win_rate) / payoff).clamp(Decimal::ZERO, Decimal::ONE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn inputs(trade_profits: &[Decimal]) -> SizingInputs<'_> {
+        SizingInputs {
+            amount: dec!(5000),
+            equity: dec!(10000),
+            price: dec!(1000),
+            atr: dec!(50),
+            atr_stop_multiplier: dec!(2),
+            risk_percent: dec!(1),
+            target_volatility: dec!(2),
+            kelly_fraction: dec!(0.5),
+            trade_profits,
+        }
+    }
+
+    /// 6 wins of 20 and 4 losses of 10: W = 0.6, R = 2, so f = 0.6 - 0.4 / 2 = 0.4
+    fn winning_history() -> Vec<Decimal> {
+        let mut profits = vec![dec!(20); 6];
+        profits.extend(vec![dec!(-10); 4]);
+        profits
+    }
+
+    #[test]
+    fn fixed_trades_the_full_amount() {
+        assert_eq!(position_notional(SizingMode::Fixed, &inputs(&[])), dec!(5000));
+    }
+
+    #[test]
+    fn fixed_fractional_risks_a_percent_of_equity_at_the_atr_stop() {
+        // Risk 100 over a 100 stop distance = 1 unit at 1000
+        assert_eq!(position_notional(SizingMode::FixedFractional, &inputs(&[])), dec!(1000));
+    }
+
+    #[test]
+    fn fixed_fractional_falls_back_to_a_three_percent_stop() {
+        let mut no_atr = inputs(&[]);
+        no_atr.atr = Decimal::ZERO;
+        no_atr.risk_percent = dec!(0.9);
+        // Risk 90 over a 30 stop distance = 3 units at 1000
+        assert_eq!(position_notional(SizingMode::FixedFractional, &no_atr), dec!(3000));
+    }
+
+    #[test]
+    fn volatility_target_scales_by_realized_volatility() {
+        let mut volatile = inputs(&[]);
+        volatile.atr = dec!(40); // 4% realized against a 2% target
+        assert_eq!(position_notional(SizingMode::VolatilityTarget, &volatile), dec!(2500));
+
+        volatile.atr = dec!(5); // Calmer than the target: capped at the amount
+        assert_eq!(position_notional(SizingMode::VolatilityTarget, &volatile), dec!(5000));
+    }
+
+    #[test]
+    fn kelly_fraction_from_win_rate_and_payoff() {
+        assert_eq!(kelly(&winning_history()), Some(dec!(0.4)));
+        assert_eq!(kelly(&[dec!(-1); 10]), Some(Decimal::ZERO));
+        assert_eq!(kelly(&[dec!(1); 10]), Some(Decimal::ONE));
+        assert_eq!(kelly(&[dec!(1); 9]), None);
+    }
+
+    #[test]
+    fn kelly_clamps_a_negative_edge_to_zero() {
+        // W = 0.2, R = 1: f = 0.2 - 0.8 = -0.6
+        let mut profits = vec![dec!(10); 2];
+        profits.extend(vec![dec!(-10); 8]);
+        assert_eq!(kelly(&profits), Some(Decimal::ZERO));
+    }
+
+    #[test]
+    fn kelly_sizing_uses_equity_and_the_kelly_multiplier() {
+        let history = winning_history();
+        // 10000 * 0.4 * 0.5
+        assert_eq!(position_notional(SizingMode::Kelly, &inputs(&history)), dec!(2000));
+        // Not enough history yet: fall back to the fixed amount
+        assert_eq!(position_notional(SizingMode::Kelly, &inputs(&history[..9])), dec!(5000));
+    }
+}
@@ -0,0 +1,375 @@
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use futures::stream::{self, BoxStream};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc as async_mpsc;
+use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
+use tracing::{error, info, warn};
+
+/// A new recording file is started every hour
+const ROTATE_SECS: u64 = 3600;
+/// Gzip sync-flush interval in batches (~1 per second), so a crash loses at most this many
+const FLUSH_EVERY_BATCHES: u64 = 60;
+/// Batches buffered between the matching loop and the recorder thread before dropping
+const RECORDER_QUEUE: usize = 1024;
+/// The feed counts as stale after this long without a batch; the live source reconnects
+/// when its socket has been silent this long (Binance pushes every second)
+pub const FEED_STALE_SECS: i64 = 15;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[allow(non_snake_case)]
+pub struct BinanceTicker {
+    pub E: i64,    // Event time (ms)
+    pub s: String, // Symbol
+    pub c: String, // Close price
+    pub q: String, // Quote Asset Volume
+    pub o: String, // Open price
+    pub v: String, // Base Asset Volume
+}
+
+/// One `!miniTicker@arr` message; also one line of a ticker recording
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TickerBatch {
+    pub received_at: i64, // Local receive time (ms), used to pace replays
+    pub tickers: Vec<BinanceTicker>,
+}
+
+/// What a ticker source reports: data, plus connection changes for feed health
+#[derive(Debug)]
+pub enum TickerEvent {
+    Connected,
+    Disconnected(String), // Reason
+    Batch(TickerBatch),
+}
+
+/// Where the matching engine's prices come from: the live exchange or a recording
+pub trait TickerSource: Send + Sync {
+    /// Events in arrival order. Live sources reconnect on their own, so the stream
+    /// only ends when a recording is exhausted.
+    fn stream(&self) -> BoxStream<'static, TickerEvent>;
+}
+
+pub struct BinanceTickerSource;
+
+impl TickerSource for BinanceTickerSource {
+    fn stream(&self) -> BoxStream<'static, TickerEvent> {
+        let (tx, rx) = async_mpsc::channel(64);
+
+        tokio::spawn(async move {
+            loop {
+                // Binance Mini Ticker Stream for ALL symbols
+                info!("Connecting to Binance WebSocket...");
+                match connect_async("wss://stream.binance.com:9443/ws/!miniTicker@arr").await {
+                    Ok((ws_stream, _)) => {
+                        info!("✅ Connected to Binance WebSocket. Listening for price updates...");
+                        if tx.send(TickerEvent::Connected).await.is_err() {
+                            return;
+                        }
+                        let (_, mut read) = ws_stream.split();
+                        let stale_after = Duration::from_secs(FEED_STALE_SECS as u64);
+
+                        let reason = loop {
+                            let message = match tokio::time::timeout(stale_after, read.next()).await {
+                                Ok(Some(message)) => message,
+                                Ok(None) => break "stream closed".to_string(),
+                                // Half-open connection: nothing arrives, but nothing fails either
+                                Err(_) => break format!("no data for {}s", FEED_STALE_SECS),
+                            };
+                            let Ok(Message::Text(text)) = message else {
+                                continue;
+                            };
+                            let Ok(tickers) = serde_json::from_str::<Vec<BinanceTicker>>(&text)
+                            else {
+                                continue;
+                            };
+                            let batch = TickerBatch {
+                                received_at: chrono::Utc::now().timestamp_millis(),
+                                tickers,
+                            };
+                            if tx.send(TickerEvent::Batch(batch)).await.is_err() {
+                                return;
+                            }
+                        };
+
+                        warn!("⚠️ Binance WebSocket {}, reconnecting...", reason);
+                        if tx.send(TickerEvent::Disconnected(reason)).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(e) => {
+                        error!("WebSocket connection failed: {}. Retrying in 5s...", e);
+                        if tx.send(TickerEvent::Disconnected(e.to_string())).await.is_err() {
+                            return;
+                        }
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                    }
+                }
+            }
+        });
+
+        receiver_stream(rx)
+    }
+}
+
+/// How fast a recording is fed back
+#[derive(Debug, Clone, Copy)]
+pub enum ReplaySpeed {
+    Factor(f64), // 1x = recorded pace, 10x = ten times faster
+    Max,         // No pauses between batches
+}
+
+impl FromStr for ReplaySpeed {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        if s == "max" {
+            return Ok(ReplaySpeed::Max);
+        }
+        match s.trim_end_matches('x').parse::<f64>() {
+            Ok(factor) if factor > 0.0 && factor.is_finite() => Ok(ReplaySpeed::Factor(factor)),
+            _ => Err(format!(
+                "Invalid replay speed '{}', expected e.g. 1x, 10x or max",
+                s
+            )),
+        }
+    }
+}
+
+/// Replays `.ndjson` / `.ndjson.gz` recordings written by `TickerRecorder`. A directory
+/// replays every recording in it, in file-name (= time) order.
+pub struct TickerReplaySource {
+    files: Vec<PathBuf>,
+    speed: ReplaySpeed,
+}
+
+impl TickerReplaySource {
+    pub fn open(path: &str, speed: ReplaySpeed) -> anyhow::Result<Self> {
+        let path = Path::new(path);
+        let mut files = if path.is_dir() {
+            std::fs::read_dir(path)?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|p| {
+                    let name = p.to_string_lossy();
+                    name.ends_with(".ndjson") || name.ends_with(".ndjson.gz")
+                })
+                .collect::<Vec<_>>()
+        } else {
+            vec![path.to_path_buf()]
+        };
+        files.sort();
+
+        if files.is_empty() {
+            return Err(anyhow::anyhow!(
+                "No ticker recordings found in {}",
+                path.display()
+            ));
+        }
+
+        info!(
+            "📼 Replaying {} ticker recording(s) from {} at {:?}",
+            files.len(),
+            path.display(),
+            speed
+        );
+        Ok(Self { files, speed })
+    }
+}
+
+impl TickerSource for TickerReplaySource {
+    fn stream(&self) -> BoxStream<'static, TickerEvent> {
+        let (tx, rx) = async_mpsc::channel(256);
+        let files = self.files.clone();
+        let speed = self.speed;
+
+        tokio::task::spawn_blocking(move || {
+            let mut previous: Option<i64> = None;
+            let mut replayed: u64 = 0;
+            if tx.blocking_send(TickerEvent::Connected).is_err() {
+                return;
+            }
+
+            for path in files {
+                let file = match File::open(&path) {
+                    Ok(file) => file,
+                    Err(e) => {
+                        warn!("⚠️ Skipping recording {}: {}", path.display(), e);
+                        continue;
+                    }
+                };
+                let reader: Box<dyn Read> = if path.to_string_lossy().ends_with(".gz") {
+                    Box::new(MultiGzDecoder::new(file))
+                } else {
+                    Box::new(file)
+                };
+
+                for (line_no, line) in BufReader::new(reader).lines().enumerate() {
+                    // A recording cut off by a crash ends in a partial gzip block
+                    let line = match line {
+                        Ok(line) => line,
+                        Err(e) => {
+                            warn!("⚠️ {} truncated after line {}: {}", path.display(), line_no, e);
+                            break;
+                        }
+                    };
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let batch = match serde_json::from_str::<TickerBatch>(&line) {
+                        Ok(batch) => batch,
+                        Err(e) => {
+                            warn!("⚠️ {}:{}: {}", path.display(), line_no + 1, e);
+                            continue;
+                        }
+                    };
+
+                    if let (ReplaySpeed::Factor(factor), Some(prev)) = (speed, previous) {
+                        let gap_ms = (batch.received_at - prev).max(0) as f64 / factor;
+                        std::thread::sleep(Duration::from_millis(gap_ms as u64));
+                    }
+                    previous = Some(batch.received_at);
+
+                    if tx.blocking_send(TickerEvent::Batch(batch)).is_err() {
+                        return;
+                    }
+                    replayed += 1;
+                }
+            }
+
+            info!("⏹️ Ticker replay finished ({} batches)", replayed);
+            let _ = tx.blocking_send(TickerEvent::Disconnected("replay finished".to_string()));
+        });
+
+        receiver_stream(rx)
+    }
+}
+
+/// Writes every ticker batch to hourly, gzip-compressed NDJSON files
+/// (`tickers-YYYYMMDDTHHMMSSZ.ndjson.gz`) on a background thread.
+///
+/// Recording never slows the matching loop: when the writer falls behind, batches are
+/// dropped and counted instead.
+pub struct TickerRecorder {
+    tx: SyncSender<TickerBatch>,
+    dropped: AtomicU64,
+}
+
+struct RecordingFile {
+    writer: GzEncoder<BufWriter<File>>,
+    path: PathBuf,
+    opened: Instant,
+    batches: u64,
+}
+
+impl TickerRecorder {
+    pub fn start(dir: &str) -> anyhow::Result<Self> {
+        let dir = PathBuf::from(dir);
+        std::fs::create_dir_all(&dir)?;
+
+        let (tx, rx) = mpsc::sync_channel::<TickerBatch>(RECORDER_QUEUE);
+        let thread_dir = dir.clone();
+        std::thread::Builder::new()
+            .name("ticker-recorder".to_string())
+            .spawn(move || {
+                let mut current: Option<RecordingFile> = None;
+
+                for batch in rx {
+                    if current
+                        .as_ref()
+                        .is_some_and(|f| f.opened.elapsed() >= Duration::from_secs(ROTATE_SECS))
+                    {
+                        Self::finish(current.take());
+                    }
+                    if current.is_none() {
+                        current = match Self::create(&thread_dir) {
+                            Ok(file) => Some(file),
+                            Err(e) => {
+                                error!("❌ Failed to open ticker recording: {}", e);
+                                continue;
+                            }
+                        };
+                    }
+
+                    if let Some(file) = current.as_mut() {
+                        if let Err(e) = Self::append(file, &batch) {
+                            error!("❌ Failed to write {}: {}", file.path.display(), e);
+                            current = None;
+                        }
+                    }
+                }
+
+                Self::finish(current);
+            })?;
+
+        info!("⏺️ Recording ticker stream to {}", dir.display());
+        Ok(Self {
+            tx,
+            dropped: AtomicU64::new(0),
+        })
+    }
+
+    pub fn record(&self, batch: &TickerBatch) {
+        match self.tx.try_send(batch.clone()) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                if dropped == 1 || dropped.is_multiple_of(100) {
+                    warn!("⚠️ Ticker recorder falling behind, {} batches dropped", dropped);
+                }
+            }
+            Err(TrySendError::Disconnected(_)) => {}
+        }
+    }
+
+    fn create(dir: &Path) -> std::io::Result<RecordingFile> {
+        let path = dir.join(format!(
+            "tickers-{}.ndjson.gz",
+            chrono::Utc::now().format("%Y%m%dT%H%M%SZ")
+        ));
+        let file = File::create(&path)?;
+        info!("⏺️ Opened ticker recording {}", path.display());
+        Ok(RecordingFile {
+            writer: GzEncoder::new(BufWriter::new(file), Compression::default()),
+            path,
+            opened: Instant::now(),
+            batches: 0,
+        })
+    }
+
+    fn append(file: &mut RecordingFile, batch: &TickerBatch) -> anyhow::Result<()> {
+        serde_json::to_writer(&mut file.writer, batch)?;
+        file.writer.write_all(b"\n")?;
+        file.batches += 1;
+        if file.batches.is_multiple_of(FLUSH_EVERY_BATCHES) {
+            file.writer.flush()?;
+        }
+        Ok(())
+    }
+
+    fn finish(file: Option<RecordingFile>) {
+        let Some(file) = file else {
+            return;
+        };
+        match file.writer.finish().and_then(|mut w| w.flush()) {
+            Ok(()) => info!(
+                "⏹️ Closed ticker recording {} ({} batches)",
+                file.path.display(),
+                file.batches
+            ),
+            Err(e) => error!("❌ Failed to close {}: {}", file.path.display(), e),
+        }
+    }
+}
+
+fn receiver_stream(rx: async_mpsc::Receiver<TickerEvent>) -> BoxStream<'static, TickerEvent> {
+    stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|event| (event, rx)) }).boxed()
+}
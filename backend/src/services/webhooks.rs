@@ -0,0 +1,517 @@
+use crate::services::api_keys::random_string;
+use crate::services::outbound;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::json;
+use sha2::Sha256;
+use sqlx::PgPool;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Event types an endpoint can subscribe to (an empty list subscribes to all of them)
+pub const EVENT_TYPES: [&str; 6] = [
+    "order_placed",
+    "order_filled",
+    "order_cancelled",
+    "strategy_state_changed",
+    "position_closed",
+    "price_alert_triggered",
+];
+pub const MAX_ENDPOINTS_PER_USER: i64 = 10;
+/// A delivery that has failed this many times is moved to the dead letters
+pub const MAX_ATTEMPTS: i32 = 10;
+
+const POLL_INTERVAL_SECS: u64 = 2;
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+/// A claimed delivery is retried after this long if the worker dies mid-request
+const CLAIM_LEASE_SECS: i64 = 60;
+const FAN_OUT_BATCH: i64 = 200;
+const DELIVERY_BATCH: i64 = 20;
+const BACKOFF_BASE_SECS: i64 = 10;
+const BACKOFF_MAX_SECS: i64 = 3600;
+/// Delivered rows and dispatched outbox rows older than this are pruned
+const RETENTION_DAYS: i32 = 14;
+const PRUNE_EVERY_POLLS: u64 = 1800; // About an hour
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct WebhookEndpoint {
+    pub id: Uuid,
+    pub url: String,
+    pub event_types: Vec<String>, // Empty = every event type
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub endpoint_id: Uuid,
+    pub url: String,
+    pub event_type: String,
+    pub status: String, // pending, delivered, dead
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub last_status_code: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+/// A delivery claimed by the worker, with what it needs to send it
+#[derive(Debug, sqlx::FromRow)]
+struct ClaimedDelivery {
+    id: i64,
+    event_type: String,
+    payload: serde_json::Value,
+    attempts: i32,
+    created_at: DateTime<Utc>,
+    url: String,
+    secret: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebhookMetrics {
+    pub delivered: u64,
+    pub failed_attempts: u64,
+    pub dead_lettered: u64,
+}
+
+/// Registers an endpoint and returns it with its signing secret, which is only shown once
+pub async fn create_endpoint(
+    pool: &PgPool,
+    user_id: Uuid,
+    url: &str,
+    event_types: &[String],
+) -> anyhow::Result<(WebhookEndpoint, String)> {
+    let secret = format!("whsec_{}", random_string(32));
+    let endpoint = sqlx::query_as::<_, WebhookEndpoint>(
+        "INSERT INTO webhook_endpoints (id, user_id, url, secret, event_types)
+         VALUES ($1, $2, $3, $4, $5)
+         RETURNING id, url, event_types, created_at",
+    )
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(url)
+    .bind(&secret)
+    .bind(event_types)
+    .fetch_one(pool)
+    .await?;
+
+    Ok((endpoint, secret))
+}
+
+pub async fn list_endpoints(pool: &PgPool, user_id: Uuid) -> anyhow::Result<Vec<WebhookEndpoint>> {
+    Ok(sqlx::query_as::<_, WebhookEndpoint>(
+        "SELECT id, url, event_types, created_at FROM webhook_endpoints
+         WHERE user_id = $1 ORDER BY created_at DESC",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?)
+}
+
+pub async fn count_endpoints(pool: &PgPool, user_id: Uuid) -> anyhow::Result<i64> {
+    Ok(sqlx::query_scalar("SELECT COUNT(*) FROM webhook_endpoints WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?)
+}
+
+/// Removes the endpoint and its pending deliveries. False when it isn't the user's.
+pub async fn delete_endpoint(pool: &PgPool, user_id: Uuid, id: Uuid) -> anyhow::Result<bool> {
+    let result = sqlx::query("DELETE FROM webhook_endpoints WHERE id = $1 AND user_id = $2")
+        .bind(id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn list_deliveries(
+    pool: &PgPool,
+    user_id: Uuid,
+    status: Option<&str>,
+    limit: i64,
+) -> anyhow::Result<Vec<WebhookDelivery>> {
+    Ok(sqlx::query_as::<_, WebhookDelivery>(
+        "SELECT d.id, d.endpoint_id, e.url, d.event_type, d.status, d.attempts, d.next_attempt_at,
+                d.last_error, d.last_status_code, d.created_at, d.delivered_at
+         FROM webhook_deliveries d JOIN webhook_endpoints e ON e.id = d.endpoint_id
+         WHERE e.user_id = $1 AND ($2::TEXT IS NULL OR d.status = $2)
+         ORDER BY d.id DESC LIMIT $3",
+    )
+    .bind(user_id)
+    .bind(status)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Puts a dead delivery back in the queue with a fresh set of attempts. False when it
+/// isn't the user's or isn't dead.
+pub async fn redeliver(pool: &PgPool, user_id: Uuid, id: i64) -> anyhow::Result<bool> {
+    let result = sqlx::query(
+        "UPDATE webhook_deliveries d
+         SET status = 'pending', attempts = 0, next_attempt_at = NOW(), last_error = NULL
+         FROM webhook_endpoints e
+         WHERE d.id = $1 AND e.id = d.endpoint_id AND e.user_id = $2 AND d.status = 'dead'",
+    )
+    .bind(id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// `hex(HMAC-SHA256(secret, timestamp + "." + body))`, sent as `X-Webhook-Signature`
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Seconds before retry number `attempts` (1-based): 10s, 20s, 40s, ... capped at an hour
+fn backoff_secs(attempts: i32) -> i64 {
+    let exponent = (attempts - 1).clamp(0, 20) as u32;
+    (BACKOFF_BASE_SECS << exponent).min(BACKOFF_MAX_SECS)
+}
+
+/// Turns outbox rows into per-endpoint deliveries and sends them, retrying failures
+/// with exponential backoff. Delivery is at least once; receivers dedupe on
+/// `X-Webhook-Id`.
+pub struct WebhookDispatcher {
+    pool: PgPool,
+    client: Client,
+    allow_private_targets: bool, // Local testing only: lets endpoints point at internal addresses
+    delivered: AtomicU64,
+    failed_attempts: AtomicU64,
+    dead_lettered: AtomicU64,
+}
+
+impl WebhookDispatcher {
+    pub fn new(pool: PgPool, allow_private_targets: bool) -> reqwest::Result<Self> {
+        Ok(Self {
+            pool,
+            client: outbound::client(
+                Duration::from_secs(REQUEST_TIMEOUT_SECS),
+                allow_private_targets,
+            )?,
+            allow_private_targets,
+            delivered: AtomicU64::new(0),
+            failed_attempts: AtomicU64::new(0),
+            dead_lettered: AtomicU64::new(0),
+        })
+    }
+
+    /// Checked when an endpoint is registered and again before every delivery
+    pub async fn check_target(&self, url: &str) -> Result<(), String> {
+        outbound::check_url(url, self.allow_private_targets).await
+    }
+
+    pub fn start(self: Arc<Self>) {
+        tokio::spawn(async move {
+            info!("📬 Webhook dispatcher started");
+            let mut interval = tokio::time::interval(Duration::from_secs(POLL_INTERVAL_SECS));
+            let mut polls: u64 = 0;
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.fan_out().await {
+                    error!("❌ Webhook fan-out failed: {}", e);
+                }
+                if let Err(e) = self.deliver_due().await {
+                    error!("❌ Webhook delivery failed: {}", e);
+                }
+                polls += 1;
+                if polls.is_multiple_of(PRUNE_EVERY_POLLS) {
+                    if let Err(e) = self.prune().await {
+                        warn!("⚠️ Failed to prune webhook history: {}", e);
+                    }
+                }
+            }
+        });
+    }
+
+    /// Creates a delivery for every endpoint subscribed to each new outbox row
+    async fn fan_out(&self) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let ids: Vec<i64> = sqlx::query_scalar(
+            "SELECT id FROM event_outbox WHERE dispatched_at IS NULL
+             ORDER BY id LIMIT $1 FOR UPDATE SKIP LOCKED",
+        )
+        .bind(FAN_OUT_BATCH)
+        .fetch_all(&mut *tx)
+        .await?;
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        sqlx::query(
+            "INSERT INTO webhook_deliveries (endpoint_id, outbox_id, event_type, payload)
+             SELECT e.id, o.id, o.event_type, o.payload
+             FROM event_outbox o
+             JOIN webhook_endpoints e ON e.user_id = o.user_id
+                 AND (cardinality(e.event_types) = 0 OR o.event_type = ANY(e.event_types))
+             WHERE o.id = ANY($1)
+             ORDER BY o.id
+             ON CONFLICT (endpoint_id, outbox_id) DO NOTHING",
+        )
+        .bind(&ids)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("UPDATE event_outbox SET dispatched_at = NOW() WHERE id = ANY($1)")
+            .bind(&ids)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn deliver_due(&self) -> anyhow::Result<()> {
+        // Claiming pushes next_attempt_at out by a lease, so no lock is held during the
+        // HTTP call and a crashed worker's claims come back on their own
+        let claimed = sqlx::query_as::<_, ClaimedDelivery>(
+            "UPDATE webhook_deliveries d
+             SET next_attempt_at = NOW() + make_interval(secs => $2)
+             FROM webhook_endpoints e
+             WHERE e.id = d.endpoint_id AND d.id IN (
+                 SELECT id FROM webhook_deliveries
+                 WHERE status = 'pending' AND next_attempt_at <= NOW()
+                 ORDER BY next_attempt_at, id LIMIT $1
+                 FOR UPDATE SKIP LOCKED
+             )
+             RETURNING d.id, d.event_type, d.payload, d.attempts, d.created_at, e.url, e.secret",
+        )
+        .bind(DELIVERY_BATCH)
+        .bind(CLAIM_LEASE_SECS as f64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        futures::future::join_all(claimed.into_iter().map(|delivery| self.attempt(delivery))).await;
+        Ok(())
+    }
+
+    async fn attempt(&self, delivery: ClaimedDelivery) {
+        let attempts = delivery.attempts + 1;
+        let outcome = self.send(&delivery).await;
+
+        let result = match &outcome {
+            Ok(status_code) => {
+                self.delivered.fetch_add(1, Ordering::Relaxed);
+                sqlx::query(
+                    "UPDATE webhook_deliveries
+                     SET status = 'delivered', attempts = $2, last_status_code = $3,
+                         last_error = NULL, delivered_at = NOW()
+                     WHERE id = $1",
+                )
+                .bind(delivery.id)
+                .bind(attempts)
+                .bind(*status_code as i32)
+                .execute(&self.pool)
+                .await
+            }
+            Err((status_code, message)) => {
+                self.failed_attempts.fetch_add(1, Ordering::Relaxed);
+                let dead = attempts >= MAX_ATTEMPTS;
+                if dead {
+                    self.dead_lettered.fetch_add(1, Ordering::Relaxed);
+                    warn!(
+                        "☠️ Webhook delivery {} to {} dead after {} attempts: {}",
+                        delivery.id, delivery.url, attempts, message
+                    );
+                } else {
+                    warn!(
+                        "⚠️ Webhook delivery {} to {} failed (attempt {}): {}",
+                        delivery.id, delivery.url, attempts, message
+                    );
+                }
+                sqlx::query(
+                    "UPDATE webhook_deliveries
+                     SET status = $2, attempts = $3, last_status_code = $4, last_error = $5,
+                         next_attempt_at = NOW() + make_interval(secs => $6)
+                     WHERE id = $1",
+                )
+                .bind(delivery.id)
+                .bind(if dead { "dead" } else { "pending" })
+                .bind(attempts)
+                .bind(status_code.map(|c| c as i32))
+                .bind(message)
+                .bind(if dead { 0.0 } else { backoff_secs(attempts) as f64 })
+                .execute(&self.pool)
+                .await
+            }
+        };
+
+        if let Err(e) = result {
+            // The claim lease expires and the delivery is retried
+            error!("❌ Failed to record webhook delivery {}: {}", delivery.id, e);
+        }
+    }
+
+    /// POSTs the event; `Ok` carries the 2xx status, `Err` the status (if any) and why
+    async fn send(&self, delivery: &ClaimedDelivery) -> Result<u16, (Option<u16>, String)> {
+        let body = json!({
+            "id": delivery.id,
+            "type": delivery.event_type,
+            "created_at": delivery.created_at,
+            "data": delivery.payload,
+        })
+        .to_string();
+        let timestamp = Utc::now().timestamp();
+
+        self.check_target(&delivery.url)
+            .await
+            .map_err(|e| (None, format!("Target refused: {}", e)))?;
+        let response = self
+            .client
+            .post(&delivery.url)
+            .header("Content-Type", "application/json")
+            .header("X-Webhook-Id", delivery.id.to_string())
+            .header("X-Webhook-Event", &delivery.event_type)
+            .header("X-Webhook-Timestamp", timestamp.to_string())
+            .header(
+                "X-Webhook-Signature",
+                sign(&delivery.secret, timestamp, body.as_bytes()),
+            )
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| (None, e.to_string()))?;
+
+        let status = response.status();
+        if status.is_success() {
+            Ok(status.as_u16())
+        } else {
+            Err((Some(status.as_u16()), format!("HTTP {}", status)))
+        }
+    }
+
+    async fn prune(&self) -> anyhow::Result<()> {
+        sqlx::query(
+            "DELETE FROM webhook_deliveries
+             WHERE status = 'delivered' AND delivered_at < NOW() - make_interval(days => $1)",
+        )
+        .bind(RETENTION_DAYS)
+        .execute(&self.pool)
+        .await?;
+        // Keep outbox rows that a pending or dead delivery may still be traced back to
+        sqlx::query(
+            "DELETE FROM event_outbox o
+             WHERE o.dispatched_at < NOW() - make_interval(days => $1)
+               AND NOT EXISTS (SELECT 1 FROM webhook_deliveries d WHERE d.outbox_id = o.id)",
+        )
+        .bind(RETENTION_DAYS)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub fn metrics(&self) -> WebhookMetrics {
+        WebhookMetrics {
+            delivered: self.delivered.load(Ordering::Relaxed),
+            failed_attempts: self.failed_attempts.load(Ordering::Relaxed),
+            dead_lettered: self.dead_lettered.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{header::LOCATION, HeaderMap, StatusCode};
+    use axum::{extract::State, routing::post, Router};
+    use sqlx::postgres::PgPoolOptions;
+    use tokio::sync::mpsc;
+
+    const SECRET: &str = "whsec_test";
+
+    /// Receiver on 127.0.0.1: `/hook` records what it gets, `/moved` redirects to `/hook`
+    async fn local_receiver() -> (String, mpsc::UnboundedReceiver<(HeaderMap, String)>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let app = Router::new()
+            .route(
+                "/hook",
+                post(
+                    |State(tx): State<mpsc::UnboundedSender<(HeaderMap, String)>>,
+                     headers: HeaderMap,
+                     body: String| async move {
+                        tx.send((headers, body)).unwrap();
+                        StatusCode::NO_CONTENT
+                    },
+                ),
+            )
+            .route("/moved", post(|| async { (StatusCode::FOUND, [(LOCATION, "/hook")]) }))
+            .with_state(tx);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (base, rx)
+    }
+
+    /// `send` never touches the database, so the pool doesn't need a server
+    fn dispatcher(allow_private_targets: bool) -> WebhookDispatcher {
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/unused")
+            .unwrap();
+        WebhookDispatcher::new(pool, allow_private_targets).unwrap()
+    }
+
+    fn delivery(url: String) -> ClaimedDelivery {
+        ClaimedDelivery {
+            id: 7,
+            event_type: "order_filled".to_string(),
+            payload: json!({"order_id": "abc"}),
+            attempts: 0,
+            created_at: Utc::now(),
+            url,
+            secret: SECRET.to_string(),
+        }
+    }
+
+    fn header(headers: &HeaderMap, name: &str) -> String {
+        headers.get(name).unwrap().to_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn delivers_signed_events_to_a_local_receiver() {
+        let (base, mut received) = local_receiver().await;
+        let result = dispatcher(true).send(&delivery(format!("{}/hook", base))).await;
+        assert_eq!(result, Ok(204));
+
+        let (headers, body) = received.recv().await.unwrap();
+        let timestamp: i64 = header(&headers, "x-webhook-timestamp").parse().unwrap();
+        assert_eq!(header(&headers, "x-webhook-signature"), sign(SECRET, timestamp, body.as_bytes()));
+        assert_eq!(header(&headers, "x-webhook-id"), "7");
+        assert_eq!(header(&headers, "x-webhook-event"), "order_filled");
+
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["type"], "order_filled");
+        assert_eq!(body["data"]["order_id"], "abc");
+    }
+
+    #[tokio::test]
+    async fn refuses_local_receivers_unless_allowed() {
+        let (base, mut received) = local_receiver().await;
+        let dispatcher = dispatcher(false);
+        assert!(dispatcher.check_target(&format!("{}/hook", base)).await.is_err());
+
+        let result = dispatcher.send(&delivery(format!("{}/hook", base))).await;
+        assert!(matches!(result, Err((None, _))), "{:?}", result);
+        assert!(received.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn does_not_follow_redirects() {
+        let (base, mut received) = local_receiver().await;
+        let result = dispatcher(true).send(&delivery(format!("{}/moved", base))).await;
+        assert!(matches!(result, Err((Some(302), _))), "{:?}", result);
+        assert!(received.try_recv().is_err());
+    }
+}
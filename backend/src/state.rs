@@ -8,4 +8,16 @@ pub struct AppState {
     pub pool: PgPool,
     pub matching_engine: Arc<MatchingEngine>,
     pub automation_engine: Arc<crate::services::automation::AutomationEngine>,
+    pub optimizer: Arc<crate::services::optimizer::Optimizer>,
+    pub indicator_cache: Arc<crate::services::indicators::IndicatorCache>,
+    pub binance: Arc<crate::services::binance::BinanceClient>,
+    pub auth: Arc<crate::auth::JwtVerifier>,
+    pub rate_limiter: Arc<crate::rate_limit::RateLimiter>,
+    pub hub: Arc<crate::services::realtime::RealtimeHub>, // Fan-out for /ws push events
+    pub events: Arc<crate::services::events::EventBus>,   // In-process domain events
+    pub webhooks: Arc<crate::services::webhooks::WebhookDispatcher>,
+    pub notifier: Arc<crate::services::notifications::Notifier>,
+    pub trust_forwarded_for: bool, // Take client IPs from X-Forwarded-For (behind a proxy)
+    pub api_key_pepper: Option<Arc<str>>, // Signing secrets derive from it; None disables signed requests
+    pub frontend_proxy_secret: Option<Arc<str>>, // Lets the Next.js proxies vouch for X-Client-IP
 }
@@ -1,5 +1,5 @@
 import { NextRequest, NextResponse } from 'next/server';
-import { createClient } from '@/lib/supabase/server';
+import { backendHeaders, getBackendSession, SIGN_IN_REQUIRED } from '@/lib/backend-auth';
 
 export async function POST(
     request: NextRequest,
@@ -7,12 +7,9 @@ export async function POST(
 ) {
     try {
         const { id } = await params;
-        const supabase = await createClient();
-        const { data: { user } } = await supabase.auth.getUser();
-
-        // Auth check (allow fallback)
-        if (!user && !request.cookies.has('app_access')) {
-            return NextResponse.json({ error: 'Unauthorized' }, { status: 401 });
+        const session = await getBackendSession();
+        if (!session) {
+            return NextResponse.json(SIGN_IN_REQUIRED, { status: 401 });
         }
 
         // Smart URL resolution: In development, prefer NEXT_PUBLIC_API_URL if BACKEND_URL has wrong port
@@ -29,9 +26,9 @@ export async function POST(
         }
         const res = await fetch(`${baseUrl}/api/automation/${id}/panic`, {
             method: "POST",
-            headers: {
+            headers: backendHeaders(session, {
                 "Content-Type": "application/json",
-            },
+            }),
         });
 
         const data = await res.json();
@@ -1,5 +1,5 @@
 import { NextRequest, NextResponse } from 'next/server';
-import { createClient } from '@/lib/supabase/server';
+import { backendHeaders, getBackendSession, SIGN_IN_REQUIRED } from '@/lib/backend-auth';
 
 export async function POST(
     request: NextRequest,
@@ -7,18 +7,9 @@ export async function POST(
 ) {
     try {
         const { id } = await params;
-        const supabase = await createClient();
-        const { data: { user } } = await supabase.auth.getUser();
-
-        let userId = user?.id;
-
-        if (!userId) {
-            const hasAccess = request.cookies.has('app_access');
-            if (hasAccess) {
-                userId = '00000000-0000-0000-0000-000000000000';
-            } else {
-                return NextResponse.json({ error: 'Unauthorized' }, { status: 401 });
-            }
+        const session = await getBackendSession();
+        if (!session) {
+            return NextResponse.json(SIGN_IN_REQUIRED, { status: 401 });
         }
 
         // Smart URL resolution: In development, prefer NEXT_PUBLIC_API_URL if BACKEND_URL has wrong port
@@ -35,6 +26,7 @@ export async function POST(
         }
         const res = await fetch(`${baseUrl}/api/automation/${id}/stop`, {
             method: "POST",
+            headers: backendHeaders(session),
         });
 
         const contentType = res.headers.get("content-type");
@@ -1,32 +1,20 @@
 import { NextRequest, NextResponse } from 'next/server';
-import { createClient } from '@/lib/supabase/server';
+import { backendHeaders, getBackendSession, SIGN_IN_REQUIRED } from '@/lib/backend-auth';
 
 export async function POST(request: NextRequest) {
     try {
-        const supabase = await createClient();
-        const { data: { user }, error: authError } = await supabase.auth.getUser();
-
-        let userId = user?.id;
-
-        // Fallback for custom auth: check cookie and use default ID
-        if (!userId) {
-            const hasAccess = request.cookies.has('app_access');
-            if (hasAccess) {
-                // Use known default ID for custom auth flow
-                userId = '00000000-0000-0000-0000-000000000000';
-                console.log("DEBUG Proxy Start: Using fallback DEFAULT_USER_ID due to app_access cookie");
-            } else {
-                console.log("DEBUG Proxy Start: Unauthorized - No User and no app_access cookie", authError);
-                return NextResponse.json({ error: 'Unauthorized', details: authError }, { status: 401 });
-            }
+        // The backend needs the user's JWT; the app_access cookie alone can't be forwarded
+        const session = await getBackendSession();
+        if (!session) {
+            return NextResponse.json(SIGN_IN_REQUIRED, { status: 401 });
         }
 
         const payload = await request.json();
 
-        // Force user_id to be the authenticated/fallback user
+        // Force user_id to be the authenticated user (the backend checks it against the token)
         const backendPayload = {
             ...payload,
-            user_id: userId
+            user_id: session.userId
         };
 
         // Smart URL resolution: In development, prefer NEXT_PUBLIC_API_URL if BACKEND_URL has wrong port
@@ -45,9 +33,9 @@ export async function POST(request: NextRequest) {
         }
         const res = await fetch(`${baseUrl}/api/automation/start`, {
             method: "POST",
-            headers: {
+            headers: backendHeaders(session, {
                 "Content-Type": "application/json",
-            },
+            }),
             body: JSON.stringify(backendPayload),
         });
 
@@ -1,10 +1,16 @@
 import { NextRequest, NextResponse } from 'next/server';
+import { backendHeaders, getBackendSession, SIGN_IN_REQUIRED } from '@/lib/backend-auth';
 
 export const dynamic = 'force-dynamic';
 
 export async function GET(request: NextRequest) {
     try {
-        // Ideally we should filter by user here in the future
+        // The backend lists the strategies of the user the token belongs to
+        const session = await getBackendSession();
+        if (!session) {
+            return NextResponse.json(SIGN_IN_REQUIRED, { status: 401 });
+        }
+
         // Smart URL resolution: In development, prefer NEXT_PUBLIC_API_URL if BACKEND_URL has wrong port
         const isDevelopment = process.env.NODE_ENV === 'development';
         let baseUrl = process.env.BACKEND_URL || process.env.NEXT_PUBLIC_API_URL || process.env.NEXT_PUBLIC_BACKEND_URL || 'http://127.0.0.1:3001';
@@ -72,9 +78,9 @@ export async function GET(request: NextRequest) {
             const res = await fetch(url, { 
                 cache: 'no-store',
                 signal: controller.signal,
-                headers: {
+                headers: backendHeaders(session, {
                     'Content-Type': 'application/json',
-                }
+                })
             });
             
             clearTimeout(timeoutId);
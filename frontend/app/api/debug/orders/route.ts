@@ -1,10 +1,18 @@
 import { NextRequest, NextResponse } from 'next/server';
-import { createClient } from '../../../../lib/supabase/server';
+import { backendHeaders, getBackendSession, SIGN_IN_REQUIRED } from '../../../../lib/backend-auth';
 
 export async function GET(request: NextRequest) {
     try {
+        const session = await getBackendSession();
+        if (!session) {
+            return NextResponse.json(SIGN_IN_REQUIRED, { status: 401 });
+        }
+
         const baseUrl = process.env.NEXT_PUBLIC_API_URL || 'http://127.0.0.1:3001';
-        const res = await fetch(`${baseUrl}/api/orders/recent`, { cache: 'no-store' });
+        const res = await fetch(`${baseUrl}/api/orders/recent`, {
+            cache: 'no-store',
+            headers: backendHeaders(session),
+        });
         const data = await res.json();
         return NextResponse.json(data);
     } catch (error: any) {
@@ -6,6 +6,7 @@ import { executeOrder } from '../../../../lib/services/orders';
 import type { SupabaseClient, PostgrestError } from '@supabase/supabase-js';
 import { cookies } from 'next/headers';
 import { DEFAULT_USER_ID } from '../../../../lib/auth-utils';
+import { backendHeaders, getBackendSession } from '../../../../lib/backend-auth';
 import crypto from 'crypto';
 
 const COINDCX_API_URL = process.env.COINDCX_API_URL || 'https://api.coindcx.com';
@@ -26,7 +27,10 @@ export async function POST(request: NextRequest) {
       return NextResponse.json({ error: 'Unauthorized' }, { status: 401 });
     }
 
-    const userId = DEFAULT_USER_ID;
+    // Signed-in users trade as themselves so the backend (which checks order ownership
+    // against the JWT) can pick up their limit orders; access-code users share the default ID
+    const session = await getBackendSession();
+    const userId = session?.userId ?? DEFAULT_USER_ID;
     const supabase = await createClient();
 
     const useTestAPI = process.env.USE_TEST_API === 'true' || process.env.NODE_ENV === 'development';
@@ -193,22 +197,25 @@ export async function POST(request: NextRequest) {
           const backendUrl = process.env.BACKEND_URL || (process.env.NODE_ENV === 'development' ? 'http://127.0.0.1:3002' : '');
           if (!backendUrl) {
             console.warn('BACKEND_URL not set, skipping rust backend notification');
-            return; // Exit if no backend URL is available
+          } else if (!session) {
+            // The backend needs a JWT; it loads pending limit orders from the database on start
+            console.warn('No Supabase session, skipping rust backend notification');
+          } else {
+            fetch(`${backendUrl}/api/orders/process`, {
+              method: 'POST',
+              headers: backendHeaders(session, { 'Content-Type': 'application/json' }),
+              body: JSON.stringify({
+                id: order.id,
+                user_id: userId,
+                coin_id: normalizedCoinId,
+                coin_symbol: orderData.coin_symbol,
+                order_type: orderData.side, // "buy" or "sell"
+                quantity: quantity,
+                price: price,
+                current_price: effectivePrice,
+              })
+            }).catch(e => console.error("Failed to notify Rust backend:", e));
           }
-          fetch(`${backendUrl}/api/orders/process`, {
-            method: 'POST',
-            headers: { 'Content-Type': 'application/json' },
-            body: JSON.stringify({
-              id: order.id,
-              user_id: userId,
-              coin_id: normalizedCoinId,
-              coin_symbol: orderData.coin_symbol,
-              order_type: orderData.side, // "buy" or "sell"
-              quantity: quantity,
-              price: price,
-              current_price: effectivePrice,
-            })
-          }).catch(e => console.error("Failed to notify Rust backend:", e));
         } catch (e) {
           console.error("Failed to setup notification to Rust backend:", e);
         }
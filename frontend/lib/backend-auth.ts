@@ -0,0 +1,61 @@
+import { headers } from 'next/headers';
+import { createClient } from './supabase/server';
+
+/**
+ * Signed-in Supabase user, as needed to call the Rust backend on their behalf.
+ */
+export interface BackendSession {
+    userId: string;
+    accessToken: string;
+    clientIp: string | null; // Browser address, so the backend's per-IP limit isn't shared by every user
+}
+
+/**
+ * Returned by the backend proxies when there is no Supabase session. The backend verifies a
+ * JWT on every /api route, and the `app_access` cookie alone carries no token it could check.
+ */
+export const SIGN_IN_REQUIRED = {
+    error: 'Sign in to use automation: the access code alone cannot authorize backend requests',
+};
+
+/**
+ * Gets the current Supabase session for forwarding to the backend, or null when signed out.
+ */
+export async function getBackendSession(): Promise<BackendSession | null> {
+    const supabase = await createClient();
+    // getUser() revalidates the token with Supabase; getSession() alone trusts the cookie
+    const { data: { user } } = await supabase.auth.getUser();
+    if (!user) {
+        return null;
+    }
+    const { data: { session } } = await supabase.auth.getSession();
+    if (!session?.access_token) {
+        return null;
+    }
+    return { userId: user.id, accessToken: session.access_token, clientIp: await getClientIp() };
+}
+
+/**
+ * Address of the browser calling this route, as reported by the hosting platform's proxy
+ */
+async function getClientIp(): Promise<string | null> {
+    const requestHeaders = await headers();
+    const forwarded = requestHeaders.get('x-forwarded-for')?.split(',')[0]?.trim();
+    return forwarded || requestHeaders.get('x-real-ip') || null;
+}
+
+/**
+ * Headers for a backend request made with the user's session. With FRONTEND_PROXY_SECRET
+ * set (the same value as on the backend) the browser's address is passed on as X-Client-IP;
+ * the backend ignores that header without the secret.
+ */
+export function backendHeaders(session: BackendSession, extra: Record<string, string> = {}): Record<string, string> {
+    const proxySecret = process.env.FRONTEND_PROXY_SECRET;
+    return {
+        ...extra,
+        Authorization: `Bearer ${session.accessToken}`,
+        ...(proxySecret && session.clientIp
+            ? { 'X-Proxy-Secret': proxySecret, 'X-Client-IP': session.clientIp }
+            : {}),
+    };
+}